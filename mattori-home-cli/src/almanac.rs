@@ -0,0 +1,166 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, TimeZone, Utc};
+use thiserror::Error;
+
+/// Julian day of 2000-01-01 12:00 UTC
+const J2000: f64 = 2451545.0;
+const J2000_UNIX: i64 = 946_728_000;
+/// Sun's altitude at rise and set, allowing for refraction and the size of its disc
+const HORIZON: f64 = -0.833;
+const OBLIQUITY: f64 = 23.4397;
+
+#[derive(Error, Clone, Debug)]
+pub enum AlmanacError {
+    #[error("Latitude {0} must be within -90 and 90 degrees")]
+    Latitude(f64),
+    #[error("Longitude {0} must be within -180 and 180 degrees")]
+    Longitude(f64),
+    #[error("Invalid sun event {0}, expected sunrise or sunset")]
+    InvalidEvent(String),
+}
+
+/// Where the home is, in degrees north and east
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64) -> Result<Location, AlmanacError> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(AlmanacError::Latitude(latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(AlmanacError::Longitude(longitude));
+        }
+        Ok(Location {
+            latitude,
+            longitude,
+        })
+    }
+
+    /// When the sun rises or sets on `date` here, or `None` during polar day or night
+    pub fn sun_event(&self, event: SunEvent, date: NaiveDate) -> Option<DateTime<Utc>> {
+        let (transit, half_day) = self.solar_day(date)?;
+        let julian = match event {
+            SunEvent::Sunrise => transit - half_day,
+            SunEvent::Sunset => transit + half_day,
+        };
+        let millis = ((julian - J2000) * 86_400_000.0).round() as i64;
+        Some(j2000() + ChronoDuration::milliseconds(millis))
+    }
+
+    /// Julian day of solar noon on `date` and how many days the sun is up for either side of it,
+    /// following the sunrise equation, which is within a minute or so away from the poles
+    fn solar_day(&self, date: NaiveDate) -> Option<(f64, f64)> {
+        let days = date.signed_duration_since(j2000().date_naive()).num_days() as f64;
+        let mean_noon = days - self.longitude / 360.0;
+        let anomaly = (357.5291 + 0.98560028 * mean_noon).rem_euclid(360.0);
+        let anomaly_rad = anomaly.to_radians();
+        let center = 1.9148 * anomaly_rad.sin()
+            + 0.0200 * (2.0 * anomaly_rad).sin()
+            + 0.0003 * (3.0 * anomaly_rad).sin();
+        let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit = J2000 + mean_noon + 0.0053 * anomaly_rad.sin()
+            - 0.0069 * (2.0 * ecliptic_longitude).sin();
+        let declination = (ecliptic_longitude.sin() * OBLIQUITY.to_radians().sin()).asin();
+        let latitude = self.latitude.to_radians();
+        let cos_hour_angle = (HORIZON.to_radians().sin() - latitude.sin() * declination.sin())
+            / (latitude.cos() * declination.cos());
+        if !(-1.0..=1.0).contains(&cos_hour_angle) {
+            return None;
+        }
+        Some((transit, cos_hour_angle.acos().to_degrees() / 360.0))
+    }
+}
+
+fn j2000() -> DateTime<Utc> {
+    Utc.timestamp_opt(J2000_UNIX, 0).unwrap()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+impl FromStr for SunEvent {
+    type Err = AlmanacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sunrise" => Ok(SunEvent::Sunrise),
+            "sunset" => Ok(SunEvent::Sunset),
+            _ => Err(AlmanacError::InvalidEvent(s.to_string())),
+        }
+    }
+}
+
+impl Display for SunEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SunEvent::Sunrise => write!(f, "sunrise"),
+            SunEvent::Sunset => write!(f, "sunset"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Asserts `at` falls within two minutes of `hour:minute` Japan time
+    fn assert_jst(at: DateTime<Utc>, hour: u32, minute: u32) {
+        let at = at.with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap());
+        let expected = at.date_naive().and_hms_opt(hour, minute, 0).unwrap();
+        let off = (at.naive_local() - expected).num_seconds().abs();
+        assert!(
+            off <= 120,
+            "{} is {}s from {:02}:{:02} JST",
+            at,
+            off,
+            hour,
+            minute
+        );
+    }
+
+    #[test]
+    fn tokyo_on_the_equinox() {
+        // Published by the National Astronomical Observatory of Japan
+        let tokyo = Location::new(35.6581, 139.7414).unwrap();
+        let equinox = date("2022-03-21");
+        assert_jst(tokyo.sun_event(SunEvent::Sunrise, equinox).unwrap(), 5, 44);
+        assert_jst(tokyo.sun_event(SunEvent::Sunset, equinox).unwrap(), 17, 53);
+    }
+
+    #[test]
+    fn polar_day_and_night_have_no_sunrise_or_sunset() {
+        let longyearbyen = Location::new(78.2232, 15.6267).unwrap();
+        for day in ["2022-06-21", "2022-12-21"] {
+            assert_eq!(longyearbyen.sun_event(SunEvent::Sunrise, date(day)), None);
+            assert_eq!(longyearbyen.sun_event(SunEvent::Sunset, date(day)), None);
+        }
+    }
+
+    #[test]
+    fn rejects_coordinates_off_the_globe() {
+        assert!(matches!(
+            Location::new(91.0, 0.0),
+            Err(AlmanacError::Latitude(_))
+        ));
+        assert!(matches!(
+            Location::new(0.0, -180.5),
+            Err(AlmanacError::Longitude(_))
+        ));
+    }
+}
@@ -1,9 +1,27 @@
 use std::convert::TryFrom;
 use std::fmt::Display;
+use std::time::{Duration, UNIX_EPOCH};
 
+use chrono::{NaiveTime, Timelike};
+
+use crate::dehumidify::{Dehumidifier, DehumidifySettings, QuietHours};
+use crate::heat::{HeatAlertSettings, HeatWatch};
+use crate::rules::Rule;
+use crate::schedule::Schedule;
 use crate::server::mattori_home;
+use crate::thermostat::{Thermostat, ThermostatSettings};
+use crate::timer::AcTimer;
+use mattori_home_peripherals::atmosphere::forecast::{Forecast, Trend};
+use mattori_home_peripherals::atmosphere::health::{HealthState, SensorHealth};
+use mattori_home_peripherals::atmosphere::heat::HeatRisk;
+use mattori_home_peripherals::atmosphere::sensors::NamedSensor;
+use mattori_home_peripherals::atmosphere::user_calibration::UserCalibration;
 use mattori_home_peripherals::atmosphere::{AtmosphereFeatures, Reading};
-use mattori_home_peripherals::ir::types::{ACMode, IrStatus, IrTarget};
+use mattori_home_peripherals::ir::remote::Remote;
+use mattori_home_peripherals::ir::scene::types::Scene;
+use mattori_home_peripherals::ir::types::{
+    ACMode, IrStatus, IrTarget, Temperature, TemperatureRange, TemperatureUnit,
+};
 
 impl From<mattori_home::AtmosphereFeatures> for AtmosphereFeatures {
     fn from(
@@ -12,6 +30,14 @@ impl From<mattori_home::AtmosphereFeatures> for AtmosphereFeatures {
             pressure,
             humidity,
             altitude,
+            wbgt,
+            dew_point,
+            absolute_humidity,
+            discomfort_index,
+            heat_index,
+            apparent_temperature,
+            gas,
+            co2,
         }: mattori_home::AtmosphereFeatures,
     ) -> Self {
         AtmosphereFeatures {
@@ -19,6 +45,91 @@ impl From<mattori_home::AtmosphereFeatures> for AtmosphereFeatures {
             pressure,
             humidity,
             altitude,
+            wbgt,
+            dew_point,
+            absolute_humidity,
+            discomfort_index,
+            heat_index,
+            apparent_temperature,
+            gas,
+            co2,
+        }
+    }
+}
+
+impl From<AtmosphereFeatures> for mattori_home::AtmosphereFeatures {
+    fn from(
+        AtmosphereFeatures {
+            temperature,
+            pressure,
+            humidity,
+            altitude,
+            wbgt,
+            dew_point,
+            absolute_humidity,
+            discomfort_index,
+            heat_index,
+            apparent_temperature,
+            gas,
+            co2,
+        }: AtmosphereFeatures,
+    ) -> Self {
+        mattori_home::AtmosphereFeatures {
+            temperature,
+            pressure,
+            humidity,
+            altitude,
+            wbgt,
+            dew_point,
+            absolute_humidity,
+            discomfort_index,
+            heat_index,
+            apparent_temperature,
+            gas,
+            co2,
+        }
+    }
+}
+
+impl From<&NamedSensor> for mattori_home::AtmosphereSensor {
+    fn from(sensor: &NamedSensor) -> Self {
+        mattori_home::AtmosphereSensor {
+            id: sensor.id.clone(),
+            name: sensor.name.clone(),
+            location: sensor.location.clone(),
+            model: sensor.atmosphere.model().to_string(),
+            available: Some(sensor.atmosphere.available_features().into()),
+            primary: false,
+            calibration: None,
+            sea_level_pressure: 0.0,
+            health: None,
+        }
+    }
+}
+
+impl From<(&str, UserCalibration)> for mattori_home::AtmosphereCalibration {
+    fn from((sensor_id, calibration): (&str, UserCalibration)) -> Self {
+        mattori_home::AtmosphereCalibration {
+            sensor_id: sensor_id.to_string(),
+            temperature_scale: calibration.temperature_scale,
+            temperature_offset: calibration.temperature_offset,
+            humidity_scale: calibration.humidity_scale,
+            humidity_offset: calibration.humidity_offset,
+            pressure_offset: calibration.pressure_offset,
+        }
+    }
+}
+
+impl From<mattori_home::AtmosphereCalibration> for UserCalibration {
+    fn from(calibration: mattori_home::AtmosphereCalibration) -> Self {
+        // unset scales mean unscaled rather than zeroing the readings
+        let scale = |scale: f32| if scale == 0.0 { 1.0 } else { scale };
+        UserCalibration {
+            temperature_scale: scale(calibration.temperature_scale),
+            temperature_offset: calibration.temperature_offset,
+            humidity_scale: scale(calibration.humidity_scale),
+            humidity_offset: calibration.humidity_offset,
+            pressure_offset: calibration.pressure_offset,
         }
     }
 }
@@ -49,7 +160,7 @@ impl From<mattori_home::ac_status::Mode> for ACMode {
 
 impl<T: IrTarget> From<IrStatus<T>> for mattori_home::AcStatus
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     fn from(
         IrStatus {
@@ -60,7 +171,7 @@ where
     ) -> Self {
         let mut ac_status = mattori_home::AcStatus {
             powered,
-            temperature: temperature.into(),
+            temperature: Some(Into::<Temperature>::into(temperature).into()),
             ..mattori_home::AcStatus::default()
         };
         ac_status.set_mode(mode.into());
@@ -68,20 +179,351 @@ where
     }
 }
 
+impl From<TemperatureUnit> for mattori_home::temperature::Unit {
+    fn from(unit: TemperatureUnit) -> Self {
+        match unit {
+            TemperatureUnit::Celsius => mattori_home::temperature::Unit::Celsius,
+            TemperatureUnit::Fahrenheit => mattori_home::temperature::Unit::Fahrenheit,
+        }
+    }
+}
+
+impl From<mattori_home::temperature::Unit> for TemperatureUnit {
+    fn from(unit: mattori_home::temperature::Unit) -> Self {
+        match unit {
+            mattori_home::temperature::Unit::Celsius => TemperatureUnit::Celsius,
+            mattori_home::temperature::Unit::Fahrenheit => TemperatureUnit::Fahrenheit,
+        }
+    }
+}
+
+impl From<Temperature> for mattori_home::Temperature {
+    fn from(temperature: Temperature) -> Self {
+        let mut proto = mattori_home::Temperature {
+            degrees: temperature.degrees(),
+            ..mattori_home::Temperature::default()
+        };
+        proto.set_unit(temperature.unit().into());
+        proto
+    }
+}
+
+impl From<mattori_home::Temperature> for Temperature {
+    fn from(temperature: mattori_home::Temperature) -> Self {
+        Temperature::from_degrees(temperature.degrees, temperature.unit().into())
+    }
+}
+
+impl From<TemperatureRange> for mattori_home::TemperatureRange {
+    fn from(range: TemperatureRange) -> Self {
+        mattori_home::TemperatureRange {
+            min: Some(range.min.into()),
+            max: Some(range.max.into()),
+            step: range.step as f32 / 10.0,
+        }
+    }
+}
+
 impl From<Reading> for mattori_home::AtmosphereReading {
-    fn from(
-        Reading {
-            temperature,
-            pressure,
-            humidity,
-            altitude,
-        }: Reading,
-    ) -> Self {
-        mattori_home::AtmosphereReading {
-            temperature: temperature.unwrap_or_default(),
-            pressure: pressure.unwrap_or_default(),
-            humidity: humidity.unwrap_or_default(),
-            altitude: altitude.unwrap_or_default(),
+    fn from(reading: Reading) -> Self {
+        let mut proto = mattori_home::AtmosphereReading {
+            temperature: reading.temperature.unwrap_or_default(),
+            pressure: reading.pressure.unwrap_or_default(),
+            humidity: reading.humidity.unwrap_or_default(),
+            altitude: reading.altitude.unwrap_or_default(),
+            wbgt: reading.wbgt.unwrap_or_default(),
+            dew_point: reading.dew_point.unwrap_or_default(),
+            absolute_humidity: reading.absolute_humidity.unwrap_or_default(),
+            discomfort_index: reading.discomfort_index.unwrap_or_default(),
+            heat_index: reading.heat_index.unwrap_or_default(),
+            apparent_temperature: reading.apparent_temperature.unwrap_or_default(),
+            gas_resistance: reading.gas_resistance.unwrap_or_default(),
+            co2: reading.co2.unwrap_or_default(),
+            ..mattori_home::AtmosphereReading::default()
+        };
+        if let Some(risk) = reading.heat_risk() {
+            proto.set_heat_risk(risk.into());
+        }
+        proto
+    }
+}
+
+impl From<HeatRisk> for mattori_home::atmosphere_reading::HeatRisk {
+    fn from(risk: HeatRisk) -> Self {
+        match risk {
+            HeatRisk::Safe => mattori_home::atmosphere_reading::HeatRisk::Safe,
+            HeatRisk::Caution => mattori_home::atmosphere_reading::HeatRisk::Caution,
+            HeatRisk::Warning => mattori_home::atmosphere_reading::HeatRisk::Warning,
+            HeatRisk::SevereWarning => mattori_home::atmosphere_reading::HeatRisk::SevereWarning,
+            HeatRisk::Danger => mattori_home::atmosphere_reading::HeatRisk::Danger,
+        }
+    }
+}
+
+impl From<mattori_home::atmosphere_reading::HeatRisk> for HeatRisk {
+    fn from(risk: mattori_home::atmosphere_reading::HeatRisk) -> Self {
+        match risk {
+            mattori_home::atmosphere_reading::HeatRisk::Safe => HeatRisk::Safe,
+            mattori_home::atmosphere_reading::HeatRisk::Caution => HeatRisk::Caution,
+            mattori_home::atmosphere_reading::HeatRisk::Warning => HeatRisk::Warning,
+            mattori_home::atmosphere_reading::HeatRisk::SevereWarning => HeatRisk::SevereWarning,
+            mattori_home::atmosphere_reading::HeatRisk::Danger => HeatRisk::Danger,
+        }
+    }
+}
+
+impl From<Trend> for mattori_home::forecast::Trend {
+    fn from(trend: Trend) -> Self {
+        match trend {
+            Trend::Falling => mattori_home::forecast::Trend::Falling,
+            Trend::Steady => mattori_home::forecast::Trend::Steady,
+            Trend::Rising => mattori_home::forecast::Trend::Rising,
+        }
+    }
+}
+
+impl From<(&str, Forecast)> for mattori_home::Forecast {
+    fn from((sensor_id, forecast): (&str, Forecast)) -> Self {
+        mattori_home::Forecast {
+            sensor_id: sensor_id.to_string(),
+            trend: mattori_home::forecast::Trend::from(forecast.trend.trend) as i32,
+            rate: forecast.trend.rate,
+            sea_level_pressure: forecast.sea_level_pressure,
+            number: forecast.number as u32,
+            text: forecast.text().to_string(),
+        }
+    }
+}
+
+impl From<HealthState> for mattori_home::atmosphere_health::State {
+    fn from(state: HealthState) -> Self {
+        match state {
+            HealthState::Ok => mattori_home::atmosphere_health::State::Ok,
+            HealthState::Degraded => mattori_home::atmosphere_health::State::Degraded,
+            HealthState::Failed => mattori_home::atmosphere_health::State::Failed,
+        }
+    }
+}
+
+impl From<(&str, SensorHealth)> for mattori_home::AtmosphereHealth {
+    fn from((sensor_id, health): (&str, SensorHealth)) -> Self {
+        mattori_home::AtmosphereHealth {
+            sensor_id: sensor_id.to_string(),
+            state: mattori_home::atmosphere_health::State::from(health.state) as i32,
+            consecutive_errors: health.consecutive_errors,
+            total_errors: health.total_errors,
+            resets: health.resets,
+            last_success: health
+                .last_success
+                .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs()),
+            last_error: health.last_error.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&Remote> for mattori_home::Remote {
+    fn from(remote: &Remote) -> Self {
+        let state = remote.state();
+        let mut proto = mattori_home::Remote {
+            name: remote.name().to_string(),
+            buttons: remote.buttons().map(str::to_string).collect(),
+            brightness: state
+                .brightness
+                .zip(remote.brightness_steps())
+                .map(|(level, steps)| mattori_home::remote::Brightness {
+                    level: level as u32,
+                    steps: steps as u32,
+                }),
+            ..mattori_home::Remote::default()
+        };
+        proto.set_power(match state.powered {
+            None => mattori_home::remote::Power::Unknown,
+            Some(true) => mattori_home::remote::Power::On,
+            Some(false) => mattori_home::remote::Power::Off,
+        });
+        proto
+    }
+}
+
+impl From<(&str, &Scene)> for mattori_home::Scene {
+    fn from((name, scene): (&str, &Scene)) -> Self {
+        mattori_home::Scene {
+            name: name.to_string(),
+            steps: scene.steps.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl From<&ThermostatSettings> for mattori_home::ThermostatSettings {
+    fn from(settings: &ThermostatSettings) -> Self {
+        let mut proto = mattori_home::ThermostatSettings {
+            enabled: settings.enabled,
+            target: Some(settings.target.into()),
+            hysteresis: settings.hysteresis,
+            min_on_secs: settings.min_on.as_secs() as u32,
+            min_off_secs: settings.min_off.as_secs() as u32,
+            ..mattori_home::ThermostatSettings::default()
+        };
+        proto.set_mode(settings.mode.clone().into());
+        proto
+    }
+}
+
+impl From<mattori_home::ThermostatSettings> for ThermostatSettings {
+    fn from(settings: mattori_home::ThermostatSettings) -> Self {
+        ThermostatSettings {
+            enabled: settings.enabled,
+            mode: settings.mode().into(),
+            target: settings
+                .target
+                .map(Temperature::from)
+                .unwrap_or_else(|| ThermostatSettings::default().target),
+            hysteresis: settings.hysteresis,
+            min_on: Duration::from_secs(settings.min_on_secs as u64),
+            min_off: Duration::from_secs(settings.min_off_secs as u64),
+        }
+    }
+}
+
+impl From<&Thermostat> for mattori_home::Thermostat {
+    fn from(thermostat: &Thermostat) -> Self {
+        mattori_home::Thermostat {
+            settings: Some(thermostat.settings().into()),
+            running: thermostat.is_running(),
+            room_temperature: thermostat.room_temperature().unwrap_or(f32::NAN),
+        }
+    }
+}
+
+fn minute_of_day(time: NaiveTime) -> u32 {
+    time.hour() * 60 + time.minute()
+}
+
+fn from_minute_of_day(minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt((minute / 60) % 24, minute % 60, 0).unwrap_or(NaiveTime::MIN)
+}
+
+impl From<&DehumidifySettings> for mattori_home::DehumidifierSettings {
+    fn from(settings: &DehumidifySettings) -> Self {
+        let (quiet_start_minute, quiet_end_minute) = settings
+            .quiet_hours
+            .map(|quiet| (minute_of_day(quiet.start), minute_of_day(quiet.end)))
+            .unwrap_or_default();
+        mattori_home::DehumidifierSettings {
+            enabled: settings.enabled,
+            threshold: settings.threshold,
+            release: settings.release,
+            duration_secs: settings.duration.as_secs() as u32,
+            quiet_start_minute,
+            quiet_end_minute,
+        }
+    }
+}
+
+impl From<mattori_home::DehumidifierSettings> for DehumidifySettings {
+    fn from(settings: mattori_home::DehumidifierSettings) -> Self {
+        DehumidifySettings {
+            enabled: settings.enabled,
+            threshold: settings.threshold,
+            release: settings.release,
+            duration: Duration::from_secs(settings.duration_secs as u64),
+            quiet_hours: (settings.quiet_start_minute != settings.quiet_end_minute).then(|| {
+                QuietHours {
+                    start: from_minute_of_day(settings.quiet_start_minute),
+                    end: from_minute_of_day(settings.quiet_end_minute),
+                }
+            }),
+        }
+    }
+}
+
+impl From<&Dehumidifier> for mattori_home::Dehumidifier {
+    fn from(dehumidifier: &Dehumidifier) -> Self {
+        mattori_home::Dehumidifier {
+            settings: Some(dehumidifier.settings().into()),
+            drying: dehumidifier.is_drying(),
+        }
+    }
+}
+
+impl From<&Schedule> for mattori_home::Schedule {
+    fn from(schedule: &Schedule) -> Self {
+        mattori_home::Schedule {
+            id: schedule.id,
+            when: schedule.when.to_string(),
+            action: schedule.action.to_string(),
+        }
+    }
+}
+
+impl From<&AcTimer> for mattori_home::Timer {
+    fn from(timer: &AcTimer) -> Self {
+        mattori_home::Timer {
+            id: timer.id,
+            powered: timer.powered,
+            remaining_secs: timer.remaining(tokio::time::Instant::now()).as_secs() as u32,
+        }
+    }
+}
+
+impl From<(&Rule, bool)> for mattori_home::Rule {
+    fn from((rule, holding): (&Rule, bool)) -> Self {
+        mattori_home::Rule {
+            name: rule.name.clone(),
+            conditions: rule.when.iter().map(ToString::to_string).collect(),
+            actions: rule.then.iter().map(ToString::to_string).collect(),
+            debounce_secs: rule.debounce_secs as u32,
+            cooldown_secs: rule.cooldown_secs as u32,
+            holding,
+        }
+    }
+}
+
+impl From<&HeatAlertSettings> for mattori_home::HeatAlertSettings {
+    fn from(settings: &HeatAlertSettings) -> Self {
+        let mut proto = mattori_home::HeatAlertSettings {
+            enabled: settings.enabled,
+            led: settings.led,
+            lcd: settings.lcd,
+            force_cooling: settings.cool_from.is_some(),
+            cool_temperature: Some(settings.cool_temperature.into()),
+            ..mattori_home::HeatAlertSettings::default()
+        };
+        proto.set_alert_from(settings.alert_from.into());
+        if let Some(cool_from) = settings.cool_from {
+            proto.set_cool_from(cool_from.into());
+        }
+        proto
+    }
+}
+
+impl From<mattori_home::HeatAlertSettings> for HeatAlertSettings {
+    fn from(settings: mattori_home::HeatAlertSettings) -> Self {
+        HeatAlertSettings {
+            enabled: settings.enabled,
+            alert_from: settings.alert_from().into(),
+            led: settings.led,
+            lcd: settings.lcd,
+            cool_from: settings.force_cooling.then(|| settings.cool_from().into()),
+            cool_temperature: settings
+                .cool_temperature
+                .map(Temperature::from)
+                .unwrap_or_else(|| HeatAlertSettings::default().cool_temperature),
+        }
+    }
+}
+
+impl From<&HeatWatch> for mattori_home::HeatAlert {
+    fn from(heat: &HeatWatch) -> Self {
+        let mut proto = mattori_home::HeatAlert {
+            settings: Some(heat.settings().into()),
+            wbgt: heat.wbgt().unwrap_or(f32::NAN),
+            ..mattori_home::HeatAlert::default()
+        };
+        if let Some(risk) = heat.risk() {
+            proto.set_risk(risk.into());
         }
+        proto
     }
 }
@@ -0,0 +1,301 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use thiserror::Error;
+use tokio::time::Duration;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::server::HomeImpl;
+use mattori_home_peripherals::atmosphere::Reading;
+use mattori_home_peripherals::ir::types::{ACMode, IrStatus, IrTarget, Temperature};
+
+#[derive(Error, Clone, Debug)]
+pub enum DehumidifyError {
+    #[error("Release humidity {1} must be below the threshold {0}")]
+    Release(f32, f32),
+}
+
+/// Time of day when the AC shouldn't be switched, which may wrap past midnight
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DehumidifySettings {
+    pub enabled: bool,
+    /// Relative humidity in percent that starts drying once exceeded for `duration`
+    pub threshold: f32,
+    /// Relative humidity in percent that drying stops below
+    pub release: f32,
+    pub duration: Duration,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for DehumidifySettings {
+    fn default() -> Self {
+        DehumidifySettings {
+            enabled: false,
+            threshold: 70.0,
+            release: 60.0,
+            duration: Duration::from_secs(15 * 60),
+            quiet_hours: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DehumidifyAction {
+    Start,
+    Stop,
+}
+
+/// Decides when to switch the AC to dry from humidity readings
+#[derive(Clone, Debug, Default)]
+pub struct Dehumidifier {
+    settings: DehumidifySettings,
+    above_since: Option<NaiveDateTime>,
+    drying: bool,
+}
+
+impl Dehumidifier {
+    pub fn settings(&self) -> &DehumidifySettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: DehumidifySettings) -> Result<(), DehumidifyError> {
+        if settings.release >= settings.threshold {
+            return Err(DehumidifyError::Release(
+                settings.threshold,
+                settings.release,
+            ));
+        }
+        self.settings = settings;
+        Ok(())
+    }
+
+    pub fn is_drying(&self) -> bool {
+        self.drying
+    }
+
+    /// Starts drying once humidity has stayed above the threshold for the whole duration, and
+    /// stops once it falls below the release level, holding off either during quiet hours
+    pub fn update(&mut self, humidity: f32, now: NaiveDateTime) -> Option<DehumidifyAction> {
+        if humidity > self.settings.threshold {
+            self.above_since.get_or_insert(now);
+        } else {
+            self.above_since = None;
+        }
+        if self
+            .settings
+            .quiet_hours
+            .is_some_and(|quiet| quiet.contains(now.time()))
+        {
+            return None;
+        }
+        if self.drying {
+            // stop even if disabled meanwhile, so the previous mode is never lost
+            if humidity < self.settings.release || !self.settings.enabled {
+                self.drying = false;
+                return Some(DehumidifyAction::Stop);
+            }
+        } else if self.settings.enabled {
+            let long_enough = self.above_since.is_some_and(|since| {
+                now.signed_duration_since(since)
+                    .to_std()
+                    .is_ok_and(|above| above >= self.settings.duration)
+            });
+            if long_enough {
+                self.drying = true;
+                return Some(DehumidifyAction::Start);
+            }
+        }
+        None
+    }
+}
+
+/// Power and mode to switch the AC to, remembering what it was doing before drying so that
+/// stopping can put it back
+fn switch(
+    action: &DehumidifyAction,
+    current: (bool, ACMode),
+    previous: &mut Option<(bool, ACMode)>,
+) -> (bool, ACMode) {
+    match action {
+        DehumidifyAction::Start => {
+            *previous = Some(current);
+            (true, ACMode::Dry)
+        }
+        DehumidifyAction::Stop => previous.take().unwrap_or((false, ACMode::Dry)),
+    }
+}
+
+/// Feeds humidity readings to the home's dehumidifier, putting the AC back into whatever mode
+/// it was in before drying once done
+pub async fn run<T, S>(home: Arc<HomeImpl<T>>, readings: S)
+where
+    T: IrTarget + Debug + Send + Sync + 'static,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+    S: Stream<Item = Reading> + Unpin,
+{
+    let mut readings = readings.filter_map(|reading| reading.humidity);
+    let mut previous: Option<(bool, ACMode)> = None;
+    while let Some(humidity) = readings.next().await {
+        let action = home
+            .dehumidifier
+            .lock()
+            .await
+            .update(humidity, Local::now().naive_local());
+        let action = match action {
+            Some(action) => action,
+            None => continue,
+        };
+        info!("dehumidifier {:?} at {}%", action, humidity);
+        let mut ir_out = home.ir_out.lock().await;
+        let current = ir_out.status();
+        let (powered, mode) = switch(&action, (current.powered, current.mode), &mut previous);
+        let res = ir_out.send_status(IrStatus {
+            powered,
+            mode,
+            temperature: current.temperature,
+        });
+        if let Err(e) = res {
+            error!("dehumidifier could not control AC: {}", e);
+        }
+    }
+    info!("atmosphere stopped, stopping dehumidifier");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 7, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn dehumidifier(quiet_hours: Option<QuietHours>) -> Dehumidifier {
+        let mut dehumidifier = Dehumidifier::default();
+        dehumidifier
+            .set_settings(DehumidifySettings {
+                enabled: true,
+                quiet_hours,
+                ..DehumidifySettings::default()
+            })
+            .unwrap();
+        dehumidifier
+    }
+
+    /// Feeds one reading a minute from 12:00, returning the minutes at which anything happened
+    fn simulate(
+        dehumidifier: &mut Dehumidifier,
+        humidities: &[f32],
+    ) -> Vec<(u32, DehumidifyAction)> {
+        humidities
+            .iter()
+            .enumerate()
+            .filter_map(|(minute, humidity)| {
+                let minute = minute as u32;
+                dehumidifier
+                    .update(*humidity, at(12 + minute / 60, minute % 60))
+                    .map(|action| (minute, action))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_after_the_duration_and_stops_below_release() {
+        let mut humidities = vec![75.0; 20];
+        // between release and threshold keeps drying
+        humidities.extend([65.0; 10]);
+        humidities.extend([55.0; 5]);
+        let actions = simulate(&mut dehumidifier(None), &humidities);
+        assert_eq!(
+            actions,
+            vec![(15, DehumidifyAction::Start), (30, DehumidifyAction::Stop)]
+        );
+    }
+
+    #[test]
+    fn dipping_below_the_threshold_restarts_the_wait() {
+        let mut humidities = vec![75.0; 10];
+        humidities.push(68.0);
+        humidities.extend([75.0; 16]);
+        let actions = simulate(&mut dehumidifier(None), &humidities);
+        assert_eq!(actions, vec![(26, DehumidifyAction::Start)]);
+    }
+
+    #[test]
+    fn holds_off_during_quiet_hours() {
+        let quiet = QuietHours {
+            start: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+        };
+        let actions = simulate(&mut dehumidifier(Some(quiet)), &[75.0; 40]);
+        assert_eq!(actions, vec![(30, DehumidifyAction::Start)]);
+    }
+
+    #[test]
+    fn disabling_stops_drying() {
+        let mut dehumidifier = dehumidifier(None);
+        simulate(&mut dehumidifier, &[75.0; 16]);
+        assert!(dehumidifier.is_drying());
+        let settings = DehumidifySettings {
+            enabled: false,
+            ..dehumidifier.settings().clone()
+        };
+        dehumidifier.set_settings(settings).unwrap();
+        assert_eq!(
+            dehumidifier.update(75.0, at(13, 0)),
+            Some(DehumidifyAction::Stop)
+        );
+    }
+
+    #[test]
+    fn restores_the_previous_mode() {
+        let mut previous = None;
+        assert_eq!(
+            switch(
+                &DehumidifyAction::Start,
+                (true, ACMode::Cool),
+                &mut previous
+            ),
+            (true, ACMode::Dry)
+        );
+        assert_eq!(
+            switch(&DehumidifyAction::Stop, (true, ACMode::Dry), &mut previous),
+            (true, ACMode::Cool)
+        );
+        // nothing to go back to, as drying started before a restart
+        assert_eq!(
+            switch(&DehumidifyAction::Stop, (true, ACMode::Dry), &mut previous),
+            (false, ACMode::Dry)
+        );
+    }
+
+    #[test]
+    fn release_must_be_below_threshold() {
+        let settings = DehumidifySettings {
+            release: 70.0,
+            ..DehumidifySettings::default()
+        };
+        assert!(Dehumidifier::default().set_settings(settings).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use tokio::time::{interval, Duration};
+
+use crate::server::HomeImpl;
+use mattori_home_peripherals::atmosphere::forecast::Trend;
+use mattori_home_peripherals::ir::types::{IrTarget, Temperature};
+
+/// How often the forecast is checked, much shorter than the 3 hours the trend is taken over
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Shows the primary sensor's forecast on the lcd whenever it changes
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(home: Arc<HomeImpl<T>>)
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut checks = interval(CHECK_INTERVAL);
+    let mut shown = None;
+    loop {
+        checks.tick().await;
+        let forecast = match home.sensors.forecast(home.sensors.primary_id()) {
+            Ok(forecast) => forecast,
+            Err(e) => {
+                debug!("no forecast yet: {}", e);
+                continue;
+            }
+        };
+        let current = Some((forecast.number, forecast.trend.trend));
+        if shown == current {
+            continue;
+        }
+        shown = current;
+        info!("forecast: {}", forecast);
+        let trend = match forecast.trend.trend {
+            Trend::Falling => '-',
+            Trend::Steady => '=',
+            Trend::Rising => '+',
+        };
+        let message = format!(
+            "{:4.0}hPa{}{}",
+            forecast.sea_level_pressure,
+            trend,
+            forecast.short_text()
+        );
+        if let Err(e) = home.show_message(&message).await {
+            error!("could not show forecast: {}", e);
+        }
+    }
+}
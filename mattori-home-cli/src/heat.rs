@@ -0,0 +1,173 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use crate::server::HomeImpl;
+use mattori_home_peripherals::atmosphere::heat::HeatRisk;
+use mattori_home_peripherals::ir::types::{ACMode, IrStatus, IrTarget, Temperature};
+use mattori_home_peripherals::led::Leds;
+
+/// How far WBGT has to fall below a band before the risk is lowered, so readings hovering on a
+/// boundary don't keep raising alerts
+const HYSTERESIS: f32 = 0.5;
+
+#[derive(Clone, Debug)]
+pub struct HeatAlertSettings {
+    pub enabled: bool,
+    /// Risk from which the yellow led is lit
+    pub alert_from: HeatRisk,
+    pub led: bool,
+    /// Whether to show the WBGT and risk on the lcd whenever the risk changes
+    pub lcd: bool,
+    /// Risk from which the AC is switched to cooling, if it should be at all
+    pub cool_from: Option<HeatRisk>,
+    pub cool_temperature: Temperature,
+}
+
+impl Default for HeatAlertSettings {
+    fn default() -> Self {
+        HeatAlertSettings {
+            enabled: true,
+            alert_from: HeatRisk::Warning,
+            led: true,
+            lcd: true,
+            cool_from: None,
+            cool_temperature: Temperature::celsius(26),
+        }
+    }
+}
+
+/// Raised when the estimated WBGT crosses into another risk band
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatAlert {
+    /// `None` for the first estimate
+    pub from: Option<HeatRisk>,
+    pub to: HeatRisk,
+    pub wbgt: f32,
+}
+
+impl HeatAlert {
+    pub fn is_rising(&self) -> bool {
+        self.from.is_none_or(|from| from < self.to)
+    }
+}
+
+/// Follows the heatstroke risk band from WBGT estimates
+#[derive(Clone, Debug, Default)]
+pub struct HeatWatch {
+    settings: HeatAlertSettings,
+    risk: Option<HeatRisk>,
+    wbgt: Option<f32>,
+}
+
+impl HeatWatch {
+    pub fn settings(&self) -> &HeatAlertSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: HeatAlertSettings) {
+        self.settings = settings;
+    }
+
+    pub fn risk(&self) -> Option<HeatRisk> {
+        self.risk
+    }
+
+    pub fn wbgt(&self) -> Option<f32> {
+        self.wbgt
+    }
+
+    pub fn update(&mut self, wbgt: f32) -> Option<HeatAlert> {
+        self.wbgt = Some(wbgt);
+        let measured = HeatRisk::from_wbgt(wbgt);
+        let risk = match self.risk {
+            Some(current) if measured < current && wbgt >= current.lower_bound() - HYSTERESIS => {
+                current
+            }
+            _ => measured,
+        };
+        if self.risk == Some(risk) {
+            return None;
+        }
+        Some(HeatAlert {
+            from: self.risk.replace(risk),
+            to: risk,
+            wbgt,
+        })
+    }
+}
+
+/// Raises heat alerts from the WBGT of each reading, lighting the yellow led, showing them on
+/// the lcd and starting to cool as the settings ask
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(home: Arc<HomeImpl<T>>)
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut readings = home.sensors.primary().subscribe();
+    while readings.changed().await.is_ok() {
+        let wbgt = match &*readings.borrow() {
+            Ok(reading) => reading.wbgt,
+            Err(_) => None,
+        };
+        let wbgt = match wbgt {
+            Some(wbgt) => wbgt,
+            None => continue,
+        };
+        let (alert, settings) = {
+            let mut heat = home.heat.lock().await;
+            (heat.update(wbgt), heat.settings().clone())
+        };
+        let alert = match alert {
+            Some(alert) if settings.enabled => alert,
+            _ => continue,
+        };
+        info!(
+            "heat risk {} ({}) at WBGT {:.1}",
+            alert.to,
+            alert.to.official_name(),
+            alert.wbgt
+        );
+        if settings.led {
+            home.set_led(Leds::Yellow, alert.to >= settings.alert_from)
+                .await;
+        }
+        if settings.lcd {
+            let message = format!("WBGT{:4.1}{}", alert.wbgt, alert.to);
+            if let Err(e) = home.show_message(&message).await {
+                error!("could not show heat alert: {}", e);
+            }
+        }
+        let cool = settings
+            .cool_from
+            .is_some_and(|cool_from| alert.is_rising() && alert.to >= cool_from);
+        if cool {
+            if let Err(e) = start_cooling(&home, &settings).await {
+                error!("could not start cooling for heat alert: {}", e);
+            }
+        }
+    }
+    info!("atmosphere stopped, stopping heat alerts");
+}
+
+async fn start_cooling<T: IrTarget + Debug + Send + Sync + 'static>(
+    home: &HomeImpl<T>,
+    settings: &HeatAlertSettings,
+) -> Result<(), String>
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut ir_out = home.ir_out.lock().await;
+    let current = ir_out.status();
+    if current.powered && current.mode == ACMode::Cool {
+        return Ok(());
+    }
+    let temperature =
+        T::Temperature::try_from(settings.cool_temperature).map_err(|e| e.to_string())?;
+    ir_out
+        .send_status(IrStatus {
+            powered: true,
+            mode: ACMode::Cool,
+            temperature,
+        })
+        .map_err(|e| e.to_string())
+}
@@ -1,30 +1,51 @@
+mod almanac;
 mod conversions;
+mod dehumidify;
+mod forecast;
+mod heat;
+mod rules;
+mod schedule;
 mod server;
+mod thermostat;
+mod timer;
 
 extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+use crate::almanac::{AlmanacError, Location, SunEvent};
+use crate::dehumidify::Dehumidifier;
+use crate::heat::HeatWatch;
+use crate::rules::Rules;
+use crate::schedule::{LocalClock, ScheduleAction, ScheduleTime, Schedules};
 use crate::server::{mattori_home::home_server::HomeServer, HomeImpl};
+use crate::thermostat::Thermostat;
+use crate::timer::Timers;
+use chrono::{Local, NaiveDate, TimeZone};
 use color_eyre::eyre::WrapErr;
-use mattori_home_peripherals::atmosphere::Atmosphere;
-use mattori_home_peripherals::ir::format::Aeha;
+use mattori_home_peripherals::atmosphere::compensation::Compensation;
+use mattori_home_peripherals::atmosphere::config::Bme280Config;
+use mattori_home_peripherals::atmosphere::sensors::{Sensors, SensorsConfig};
+use mattori_home_peripherals::atmosphere::AtmosphereFeatures;
+use mattori_home_peripherals::ir::dynamic::{AnyIrTarget, AnyTemperatureCode, IrTargetRegistry};
 use mattori_home_peripherals::ir::input::IrIn;
 use mattori_home_peripherals::ir::output::IrOut;
-use mattori_home_peripherals::ir::sanyo::types::SanyoTemperatureCode;
-use mattori_home_peripherals::ir::sanyo::Sanyo;
-use mattori_home_peripherals::ir::types::{
-    ACMode, IrFormat, IrPulse, IrSequence, IrStatus, IrTarget,
-};
+use mattori_home_peripherals::ir::remote::Remotes;
+use mattori_home_peripherals::ir::scene::types::{Scene, SceneStep};
+use mattori_home_peripherals::ir::scene::Scenes;
+use mattori_home_peripherals::ir::types::{ACMode, IrPulse, IrSequence, IrStatus, Temperature};
 use mattori_home_peripherals::lcd::Lcd;
 use mattori_home_peripherals::led::{Led, Leds};
 use std::net::SocketAddr;
 use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 use structopt::StructOpt;
 use tokio::pin;
 use tokio::sync::Mutex;
+use tokio_stream::wrappers::WatchStream;
 use tokio_stream::StreamExt;
 use tonic::transport::Server;
 
@@ -56,17 +77,103 @@ enum IrOpt {
     Send(SendIrOpt),
 }
 
+#[derive(StructOpt, Debug)]
+enum RemoteOpt {
+    /// List the configured remotes and their buttons
+    List,
+    Press {
+        /// Name of the remote
+        remote: String,
+
+        /// Name of the button
+        button: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum SceneOpt {
+    /// List the saved scenes and their steps
+    List,
+    Run {
+        /// Name of the scene
+        name: String,
+    },
+    /// Add or replace a scene
+    Save {
+        /// Name of the scene
+        name: String,
+
+        /// Steps such as ac:cool:26, ac:off, button:light:off, aeha:<hex> or delay:500
+        #[structopt(required = true)]
+        steps: Vec<SceneStep>,
+    },
+    Delete {
+        /// Name of the scene
+        name: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum ScheduleOpt {
+    /// List the saved schedules
+    List,
+    Add {
+        /// When to run, such as "17:30 mon-fri", "sunset-30m" or "2022-08-01 07:00"
+        when: ScheduleTime,
+
+        /// What to run, such as scene:goodnight, dehumidifier:on or ac:cool:26
+        action: ScheduleAction,
+    },
+    Delete {
+        /// Id of the schedule
+        id: u32,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum RuleOpt {
+    /// List the rules with their conditions and actions
+    List,
+}
+
+#[derive(StructOpt, Debug)]
+enum SensorOpt {
+    /// List the configured climate sensors
+    List,
+    /// Set the sea level pressure that altitude is measured from, which is saved
+    SeaLevel {
+        /// Id of the sensor, or the primary sensor if not given
+        #[structopt(short, long)]
+        sensor: Option<String>,
+
+        /// QNH in hPa, such as from a nearby airport's weather report
+        #[structopt(long, required_unless = "altitude", conflicts_with = "altitude")]
+        qnh: Option<f32>,
+
+        /// Metres above sea level, deriving the sea level pressure from a reading
+        #[structopt(long)]
+        altitude: Option<f32>,
+    },
+    /// Reload a sensor's factory calibration and take a reading
+    Recalibrate {
+        /// Id of the sensor, or the primary sensor if not given
+        #[structopt(short, long)]
+        sensor: Option<String>,
+    },
+}
+
 #[derive(StructOpt, Debug)]
 struct AcState {
     #[structopt(short, long)]
     unpowered: bool,
     #[structopt(short, long, default_value = "cool")]
     mode: ACMode,
+    /// Degrees with an optional unit, such as 25, 24.5 or 77F
     #[structopt(short, long, default_value = "25")]
-    temperature: SanyoTemperatureCode,
+    temperature: Temperature,
 }
 
-impl From<AcState> for IrStatus<Sanyo> {
+impl From<AcState> for IrStatus<AnyIrTarget> {
     fn from(
         AcState {
             unpowered,
@@ -77,18 +184,97 @@ impl From<AcState> for IrStatus<Sanyo> {
         IrStatus {
             powered: !unpowered,
             mode,
-            temperature,
+            temperature: AnyTemperatureCode(temperature),
         }
     }
 }
 
 #[derive(StructOpt, Debug)]
-enum Opt {
+struct Opt {
+    /// Which AC model to control with ir
+    #[structopt(long, default_value = "sanyo")]
+    target: String,
+
+    /// Toml file with button remotes for other appliances
+    #[structopt(long, default_value = "remotes.toml")]
+    remotes: PathBuf,
+
+    /// Toml file where scenes are saved
+    #[structopt(long, default_value = "scenes.toml")]
+    scenes: PathBuf,
+
+    /// Toml file where schedules are saved, which the server only reads on start
+    #[structopt(long, default_value = "schedules.toml")]
+    schedules: PathBuf,
+
+    /// Toml file with rules reacting to readings, which the server only reads on start
+    #[structopt(long, default_value = "rules.toml")]
+    rules: PathBuf,
+
+    /// Toml file with the climate sensors, where they are and their calibration, which the server
+    /// saves calibration changes to
+    #[structopt(long, default_value = "sensors.toml")]
+    sensors: PathBuf,
+
+    /// Latitude of the home in degrees north, for sunrise and sunset schedules
+    #[structopt(long, allow_hyphen_values = true, requires = "longitude")]
+    latitude: Option<f64>,
+
+    /// Longitude of the home in degrees east, for sunrise and sunset schedules
+    #[structopt(long, allow_hyphen_values = true, requires = "latitude")]
+    longitude: Option<f64>,
+
+    #[structopt(subcommand)]
+    cmd: Cmd,
+}
+
+impl Opt {
+    fn location(&self) -> Result<Option<Location>, AlmanacError> {
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Location::new(latitude, longitude).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+enum Cmd {
     Ir(IrOpt),
+    Remote(RemoteOpt),
+    Scene(SceneOpt),
+    Schedule(ScheduleOpt),
+    Rule(RuleOpt),
+    Sensor(SensorOpt),
+    /// Show when the sun rises and sets at the home's location
+    Sun {
+        /// Day to show, or today if not given
+        #[structopt(short, long)]
+        date: Option<NaiveDate>,
+    },
     Atmosphere {
+        /// Id of the sensor to read, or the primary sensor if not given
+        #[structopt(short, long)]
+        sensor: Option<String>,
+
         /// Number of readings
         #[structopt(short, long, default_value = "1")]
         times: usize,
+
+        /// Only read these, such as temperature,dew_point,discomfort_index
+        #[structopt(short, long)]
+        features: Option<AtmosphereFeatures>,
+
+        /// Sampling preset from the datasheet, such as weather-monitoring or indoor-navigation
+        #[structopt(short, long)]
+        preset: Option<Bme280Config>,
+
+        /// How raw measurements are compensated, float, int32 or int64
+        #[structopt(short, long)]
+        compensation: Option<Compensation>,
+
+        /// Force a CO2 sensor to recalibrate to this ppm before reading, such as 420 outdoors
+        #[structopt(long)]
+        co2_reference: Option<u16>,
     },
     Led {
         /// Which LED to use
@@ -126,19 +312,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     debug!("opts: {:?}", opts);
 
-    match opts {
-        Opt::Ir(ir_opts) => match ir_opts {
+    let registry = IrTargetRegistry::default();
+    let location = opts.location()?;
+
+    match opts.cmd {
+        Cmd::Ir(ir_opts) => match ir_opts {
             IrOpt::Receive { resend } => {
+                let target = registry.create(&opts.target)?;
                 let mut ir_in = IrIn::default_pin()?;
                 let ir_stream = ir_in.pulse_stream();
                 pin!(ir_stream);
                 let pulse_seq = ir_stream.next().await.unwrap().unwrap().unwrap();
                 ir_in.stop().await?;
-                println!("Received pulse sequence: {}", pulse_seq.as_hex::<Aeha>()?);
+                println!(
+                    "Received pulse sequence: {}",
+                    target.decode(&pulse_seq)?.to_string()
+                );
 
                 if let Some(re) = resend {
                     sleep(Duration::from_secs(re as u64));
-                    let mut ir_out = IrOut::default_pin(Sanyo::default())?;
+                    let mut ir_out = IrOut::default_pin(target)?;
                     ir_out.send((*pulse_seq).clone())?;
                     sleep(Duration::from_secs(1));
                     println!("Finished sending!");
@@ -146,24 +339,199 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             IrOpt::Send(send_opts) => {
-                let mut ir_out = IrOut::default_pin(Sanyo::default())?;
+                let mut ir_out = IrOut::default_pin(registry.create(&opts.target)?)?;
                 match send_opts {
                     SendIrOpt::Raw { bytes } => ir_out.send(
-                        <Sanyo as IrTarget>::Format::encode(bytes)
+                        ir_out
+                            .target()
+                            .encode(bytes)
                             .wrap_err("Could not encode bytes")?,
                     )?,
                     SendIrOpt::Encoded { hex } => {
                         ir_out.send(IrSequence(hex.into_iter().map(IrPulse).collect()))?
                     }
-                    SendIrOpt::Registered(state) => ir_out.send_status(state.into())?,
+                    SendIrOpt::Registered(state) => {
+                        ir_out.target().check_temperature(state.temperature)?;
+                        ir_out.send_status(state.into())?
+                    }
                 }
                 sleep(Duration::from_secs(1));
                 println!("Finished sending!");
                 ir_out.stop()?;
             }
         },
-        Opt::Atmosphere { times } => {
-            let atmo = Atmosphere::default_addr()?;
+        Cmd::Remote(remote_opts) => {
+            let mut remotes = Remotes::load(&opts.remotes)?;
+            match remote_opts {
+                RemoteOpt::List => {
+                    for remote in remotes.iter() {
+                        println!(
+                            "{}: {}",
+                            remote.name(),
+                            remote.buttons().collect::<Vec<_>>().join(", ")
+                        );
+                    }
+                }
+                RemoteOpt::Press { remote, button } => {
+                    let mut ir_out = IrOut::default_pin(registry.create(&opts.target)?)?;
+                    ir_out.send_remote(remotes.get_mut(&remote)?, &button)?;
+                    sleep(Duration::from_secs(1));
+                    println!("Finished sending!");
+                    ir_out.stop()?;
+                }
+            }
+        }
+        Cmd::Scene(scene_opts) => {
+            let mut scenes = Scenes::load(&opts.scenes)?;
+            match scene_opts {
+                SceneOpt::List => {
+                    for (name, scene) in scenes.iter() {
+                        println!("{}: {}", name, scene);
+                    }
+                }
+                SceneOpt::Run { name } => {
+                    let ir_out = Mutex::new(IrOut::default_pin(registry.create(&opts.target)?)?);
+                    let remotes = Mutex::new(Remotes::load(&opts.remotes)?);
+                    scenes.get(&name)?.run(&ir_out, &remotes).await?;
+                    let mut ir_out = ir_out.into_inner();
+                    tokio::time::sleep_until(ir_out.idle_at().into()).await;
+                    sleep(Duration::from_secs(1));
+                    println!("Finished scene!");
+                    ir_out.stop()?;
+                }
+                SceneOpt::Save { name, steps } => {
+                    scenes.insert(name, Scene { steps })?;
+                }
+                SceneOpt::Delete { name } => {
+                    scenes.remove(&name)?;
+                }
+            }
+        }
+        Cmd::Schedule(schedule_opts) => {
+            let mut schedules = Schedules::load(&opts.schedules, location)?;
+            match schedule_opts {
+                ScheduleOpt::List => {
+                    for schedule in schedules.iter() {
+                        println!("{}: {} => {}", schedule.id, schedule.when, schedule.action);
+                    }
+                }
+                ScheduleOpt::Add { when, action } => {
+                    let schedule = schedules.create(when, action)?;
+                    println!("Added schedule {}", schedule.id);
+                }
+                ScheduleOpt::Delete { id } => {
+                    schedules.delete(id)?;
+                }
+            }
+        }
+        Cmd::Rule(RuleOpt::List) => {
+            for (rule, _) in Rules::load(&opts.rules)?.iter() {
+                println!(
+                    "{}: when {} then {}",
+                    rule.name,
+                    rule.when
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" and "),
+                    rule.then
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+            }
+        }
+        Cmd::Sensor(SensorOpt::List) => {
+            let config = SensorsConfig::load(&opts.sensors)?;
+            for (id, sensor) in config.iter() {
+                println!(
+                    "{}{}: {} in {}, {:?} at {:#04x}, sea level {} hPa",
+                    id,
+                    if id == config.primary() {
+                        " (primary)"
+                    } else {
+                        ""
+                    },
+                    sensor.name,
+                    sensor.location,
+                    sensor.model,
+                    sensor.address,
+                    sensor.sea_level_pressure
+                );
+            }
+        }
+        Cmd::Sensor(SensorOpt::SeaLevel {
+            sensor,
+            qnh,
+            altitude,
+        }) => {
+            let sensors = Sensors::load(&opts.sensors)?;
+            let id = sensor.unwrap_or_else(|| sensors.primary_id().to_string());
+            match (qnh, altitude) {
+                (Some(qnh), _) => sensors.set_sea_level_pressure(&id, qnh)?,
+                (None, Some(altitude)) => {
+                    sensors.get(&id)?.atmosphere.subscribe().changed().await?;
+                    let qnh = sensors.set_station_altitude(&id, altitude)?;
+                    println!("Derived sea level pressure: {} hPa", qnh);
+                }
+                (None, None) => unreachable!(),
+            }
+            for sensor in sensors.iter() {
+                sensor.atmosphere.stop()?;
+            }
+        }
+        Cmd::Sensor(SensorOpt::Recalibrate { sensor }) => {
+            let sensors = Sensors::load(&opts.sensors)?;
+            let id = sensor.unwrap_or_else(|| sensors.primary_id().to_string());
+            let mut atmo_receiver = sensors.get(&id)?.atmosphere.subscribe();
+            sensors.recalibrate(&id)?;
+            atmo_receiver.changed().await?;
+            println!("Atmosphere reading: {}", atmo_receiver.borrow().clone()?);
+            for sensor in sensors.iter() {
+                sensor.atmosphere.stop()?;
+            }
+        }
+        Cmd::Sun { date } => {
+            let location = location.ok_or("Sunrise and sunset need --latitude and --longitude")?;
+            let date = date.unwrap_or_else(|| Local::now().date_naive());
+            for event in [SunEvent::Sunrise, SunEvent::Sunset] {
+                match location.sun_event(event, date) {
+                    Some(at) => println!(
+                        "{}: {}",
+                        event,
+                        Local.from_utc_datetime(&at.naive_utc()).format("%H:%M")
+                    ),
+                    None => println!("{}: none", event),
+                }
+            }
+        }
+        Cmd::Atmosphere {
+            sensor,
+            times,
+            features,
+            preset,
+            compensation,
+            co2_reference,
+        } => {
+            let config = SensorsConfig::load(&opts.sensors)?;
+            let atmo = config
+                .get(sensor.as_deref().unwrap_or_else(|| config.primary()))?
+                .start()?;
+            println!("Atmosphere sensor: {}", atmo.model());
+            if preset.is_some() || compensation.is_some() {
+                let config = preset.unwrap_or_default();
+                atmo.change_config(Bme280Config {
+                    compensation: compensation.unwrap_or(config.compensation),
+                    ..config
+                })?;
+            }
+            if let Some(features) = features {
+                atmo.change_features(features)?;
+            }
+            if let Some(reference) = co2_reference {
+                atmo.force_recalibration(reference)?;
+            }
             let mut atmo_receiver = atmo.subscribe();
             for _ in 0..times {
                 atmo_receiver.changed().await?;
@@ -172,7 +540,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             atmo.stop()?;
         }
-        Opt::Led { led, duration } => {
+        Cmd::Led { led, duration } => {
             let mut led = Led::from_led(led)?;
             println!("Turning on led...");
             led.on();
@@ -180,7 +548,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             led.off();
             println!("Turned off led");
         }
-        Opt::Lcd { text, duration } => {
+        Cmd::Lcd { text, duration } => {
             let mut lcd = Lcd::default_addr()?;
             println!("Displaying text: {}", text);
             lcd.push_str(&text)?;
@@ -189,21 +557,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Clearing lcd");
             lcd.shutdown().await?;
         }
-        Opt::Server {
+        Cmd::Server {
             addr,
             initial_state,
         } => {
-            let mut out = IrOut::default_pin(Sanyo::default())?;
+            let mut out = IrOut::default_pin(registry.create(&opts.target)?)?;
+            out.target().check_temperature(initial_state.temperature)?;
             out.send_status(initial_state.into())?;
-            let home = HomeImpl {
-                atmosphere: Atmosphere::default_addr()?,
+            let home = Arc::new(HomeImpl {
+                sensors: Sensors::load(&opts.sensors)?,
                 ir_out: Mutex::new(out),
-            };
+                remotes: Mutex::new(Remotes::load(&opts.remotes)?),
+                scenes: Mutex::new(Scenes::load(&opts.scenes)?),
+                thermostat: Mutex::new(Thermostat::default()),
+                dehumidifier: Mutex::new(Dehumidifier::default()),
+                schedules: Mutex::new(Schedules::load(&opts.schedules, location)?),
+                timers: Mutex::new(Timers::default()),
+                rules: Mutex::new(Rules::load(&opts.rules)?),
+                heat: Mutex::new(HeatWatch::default()),
+                green_led: Mutex::new(Led::from_led(Leds::Green)?),
+                yellow_led: Mutex::new(Led::from_led(Leds::Yellow)?),
+                lcd: Mutex::new(Lcd::default_addr()?),
+            });
+            tokio::spawn(schedule::run(home.clone(), Arc::new(LocalClock)));
+            tokio::spawn(thermostat::run(home.clone()));
+            tokio::spawn(timer::run(home.clone()));
+            tokio::spawn(rules::run(home.clone()));
+            tokio::spawn(heat::run(home.clone()));
+            tokio::spawn(forecast::run(home.clone()));
+            tokio::spawn(dehumidify::run(
+                home.clone(),
+                WatchStream::new(home.sensors.primary().subscribe()).filter_map(Result::ok),
+            ));
 
-            println!("Starting server at {}", addr);
+            println!("Starting server at {} controlling {}", addr, opts.target);
 
             Server::builder()
-                .add_service(HomeServer::new(home))
+                .add_service(HomeServer::from_arc(home))
                 .serve(addr)
                 .await?;
         }
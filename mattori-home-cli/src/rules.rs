@@ -0,0 +1,593 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use tokio::time::{Duration, Instant};
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::{StreamExt, StreamMap};
+
+use crate::schedule::{
+    deserialize_from_str, parse_weekdays, run_action, serialize_display, ScheduleAction,
+};
+use crate::server::HomeImpl;
+use mattori_home_peripherals::atmosphere::Reading;
+use mattori_home_peripherals::ir::types::{ACMode, IrTarget, Temperature};
+use mattori_home_peripherals::persist::{self, PersistError};
+
+const TIME_FORMAT: &str = "%H:%M";
+
+#[derive(Error, Clone, Debug)]
+pub enum RuleError {
+    #[error("Invalid rule condition {0}, expected <temperature|humidity|pressure|altitude|wbgt|dew_point|absolute_humidity|discomfort_index|heat_index|apparent_temperature|gas_resistance|co2> <op> <value>, ac <on|off|mode>, time <HH:MM>-<HH:MM> or days <mon-fri,sun...>")]
+    InvalidCondition(String),
+    #[error("Rule {0} has no actions")]
+    NoActions(String),
+    #[error("Could not load rules")]
+    Load(#[from] PersistError),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadingField {
+    Temperature,
+    Humidity,
+    Pressure,
+    Altitude,
+    Wbgt,
+    DewPoint,
+    AbsoluteHumidity,
+    DiscomfortIndex,
+    HeatIndex,
+    ApparentTemperature,
+    GasResistance,
+    Co2,
+}
+
+impl ReadingField {
+    fn value(&self, reading: &Reading) -> Option<f32> {
+        match self {
+            ReadingField::Temperature => reading.temperature,
+            ReadingField::Humidity => reading.humidity,
+            ReadingField::Pressure => reading.pressure,
+            ReadingField::Altitude => reading.altitude,
+            ReadingField::Wbgt => reading.wbgt,
+            ReadingField::DewPoint => reading.dew_point,
+            ReadingField::AbsoluteHumidity => reading.absolute_humidity,
+            ReadingField::DiscomfortIndex => reading.discomfort_index,
+            ReadingField::HeatIndex => reading.heat_index,
+            ReadingField::ApparentTemperature => reading.apparent_temperature,
+            ReadingField::GasResistance => reading.gas_resistance,
+            ReadingField::Co2 => reading.co2,
+        }
+    }
+}
+
+impl FromStr for ReadingField {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "temperature" => Ok(ReadingField::Temperature),
+            "humidity" => Ok(ReadingField::Humidity),
+            "pressure" => Ok(ReadingField::Pressure),
+            "altitude" => Ok(ReadingField::Altitude),
+            "wbgt" => Ok(ReadingField::Wbgt),
+            "dew_point" => Ok(ReadingField::DewPoint),
+            "absolute_humidity" => Ok(ReadingField::AbsoluteHumidity),
+            "discomfort_index" => Ok(ReadingField::DiscomfortIndex),
+            "heat_index" => Ok(ReadingField::HeatIndex),
+            "apparent_temperature" => Ok(ReadingField::ApparentTemperature),
+            "gas_resistance" => Ok(ReadingField::GasResistance),
+            "co2" => Ok(ReadingField::Co2),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for ReadingField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadingField::Temperature => write!(f, "temperature"),
+            ReadingField::Humidity => write!(f, "humidity"),
+            ReadingField::Pressure => write!(f, "pressure"),
+            ReadingField::Altitude => write!(f, "altitude"),
+            ReadingField::Wbgt => write!(f, "wbgt"),
+            ReadingField::DewPoint => write!(f, "dew_point"),
+            ReadingField::AbsoluteHumidity => write!(f, "absolute_humidity"),
+            ReadingField::DiscomfortIndex => write!(f, "discomfort_index"),
+            ReadingField::HeatIndex => write!(f, "heat_index"),
+            ReadingField::ApparentTemperature => write!(f, "apparent_temperature"),
+            ReadingField::GasResistance => write!(f, "gas_resistance"),
+            ReadingField::Co2 => write!(f, "co2"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+impl Comparison {
+    fn compare(&self, value: f32, threshold: f32) -> bool {
+        match self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            ">" => Ok(Comparison::Above),
+            ">=" => Ok(Comparison::AtLeast),
+            "<" => Ok(Comparison::Below),
+            "<=" => Ok(Comparison::AtMost),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparison::Above => write!(f, ">"),
+            Comparison::AtLeast => write!(f, ">="),
+            Comparison::Below => write!(f, "<"),
+            Comparison::AtMost => write!(f, "<="),
+        }
+    }
+}
+
+/// What a rule can look at when deciding whether to fire
+#[derive(Clone, Debug)]
+pub struct RuleContext<'a> {
+    pub reading: &'a Reading,
+    pub powered: bool,
+    pub mode: ACMode,
+    pub now: NaiveDateTime,
+}
+
+/// Written in rule files as text, such as `temperature > 29`, `ac off` or `time 10:00-22:00`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// Never holds when the reading doesn't have the field
+    Reading(ReadingField, Comparison, f32),
+    Powered(bool),
+    Mode(ACMode),
+    /// Time of day from the start up to the end, which may wrap past midnight
+    Time(NaiveTime, NaiveTime),
+    Weekdays(Vec<Weekday>),
+}
+
+impl Condition {
+    pub fn holds(&self, context: &RuleContext) -> bool {
+        match self {
+            Condition::Reading(field, comparison, threshold) => field
+                .value(context.reading)
+                .is_some_and(|value| comparison.compare(value, *threshold)),
+            Condition::Powered(powered) => context.powered == *powered,
+            Condition::Mode(mode) => context.mode == *mode,
+            Condition::Time(start, end) => {
+                let time = context.now.time();
+                if start <= end {
+                    *start <= time && time < *end
+                } else {
+                    *start <= time || time < *end
+                }
+            }
+            Condition::Weekdays(weekdays) => weekdays.contains(&context.now.weekday()),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RuleError::InvalidCondition(s.to_string());
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["ac", "on"] => Ok(Condition::Powered(true)),
+            ["ac", "off"] => Ok(Condition::Powered(false)),
+            ["ac", mode] => mode.parse().map(Condition::Mode).map_err(|_| invalid()),
+            ["time", window] => {
+                let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+                let parse = |time| NaiveTime::parse_from_str(time, TIME_FORMAT).ok();
+                Ok(Condition::Time(
+                    parse(start).ok_or_else(invalid)?,
+                    parse(end).ok_or_else(invalid)?,
+                ))
+            }
+            ["days", weekdays] => parse_weekdays(weekdays)
+                .map(Condition::Weekdays)
+                .ok_or_else(invalid),
+            [field, comparison, value] => Ok(Condition::Reading(
+                field.parse().map_err(|_| invalid())?,
+                comparison.parse().map_err(|_| invalid())?,
+                value.parse().map_err(|_| invalid())?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Reading(field, comparison, value) => {
+                write!(f, "{} {} {}", field, comparison, value)
+            }
+            Condition::Powered(powered) => write!(f, "ac {}", if *powered { "on" } else { "off" }),
+            Condition::Mode(mode) => write!(f, "ac {}", mode),
+            Condition::Time(start, end) => write!(
+                f,
+                "time {}-{}",
+                start.format(TIME_FORMAT),
+                end.format(TIME_FORMAT)
+            ),
+            Condition::Weekdays(weekdays) => {
+                let weekdays = weekdays
+                    .iter()
+                    .map(|day| day.to_string().to_lowercase())
+                    .collect::<Vec<_>>();
+                write!(f, "days {}", weekdays.join(","))
+            }
+        }
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+/// Runs its actions in order once all of its conditions have held for `debounce_secs`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub when: Vec<Condition>,
+    pub then: Vec<ScheduleAction>,
+    /// How long the conditions must keep holding before firing
+    #[serde(default)]
+    pub debounce_secs: u64,
+    /// Shortest time between firings
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct RuleState {
+    rule: Rule,
+    holding_since: Option<Instant>,
+    /// Whether the rule fired since its conditions last started holding
+    fired: bool,
+    last_fired: Option<Instant>,
+}
+
+/// Rules read from a toml file, each firing once whenever its conditions start holding
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    rules: Vec<RuleState>,
+}
+
+impl Rules {
+    /// Reads rules from a toml file, treating a missing file as having no rules
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Rules, RuleError> {
+        let file: RulesFile = persist::load(path)?;
+        Self::from_rules(file.rules)
+    }
+
+    pub fn from_rules(rules: Vec<Rule>) -> Result<Rules, RuleError> {
+        if let Some(rule) = rules.iter().find(|rule| rule.then.is_empty()) {
+            return Err(RuleError::NoActions(rule.name.clone()));
+        }
+        Ok(Rules {
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleState {
+                    rule,
+                    holding_since: None,
+                    fired: false,
+                    last_fired: None,
+                })
+                .collect(),
+        })
+    }
+
+    /// Each rule along with whether its conditions currently hold
+    pub fn iter(&self) -> impl Iterator<Item = (&Rule, bool)> + '_ {
+        self.rules
+            .iter()
+            .map(|state| (&state.rule, state.holding_since.is_some()))
+    }
+
+    /// Rules that should fire now
+    pub fn update(&mut self, context: &RuleContext, now: Instant) -> Vec<Rule> {
+        let mut firing = Vec::new();
+        for state in self.rules.iter_mut() {
+            if !state
+                .rule
+                .when
+                .iter()
+                .all(|condition| condition.holds(context))
+            {
+                state.holding_since = None;
+                state.fired = false;
+                continue;
+            }
+            let holding_since = *state.holding_since.get_or_insert(now);
+            let debounced =
+                now.duration_since(holding_since) >= Duration::from_secs(state.rule.debounce_secs);
+            let cooled_down = state.last_fired.is_none_or(|last_fired| {
+                now.duration_since(last_fired) >= Duration::from_secs(state.rule.cooldown_secs)
+            });
+            if !state.fired && debounced && cooled_down {
+                state.fired = true;
+                state.last_fired = Some(now);
+                firing.push(state.rule.clone());
+            }
+        }
+        firing
+    }
+}
+
+/// Checks the home's rules against every atmosphere reading, running the actions of any that
+/// fire. Rules see the primary sensor's reading, with anything it lacks, such as CO2, taken from
+/// the other sensors.
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(home: Arc<HomeImpl<T>>)
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let primary = home.sensors.primary_id().to_string();
+    let mut readings = home
+        .sensors
+        .iter()
+        .map(|sensor| {
+            (
+                sensor.id.clone(),
+                WatchStream::new(sensor.atmosphere.subscribe()),
+            )
+        })
+        .collect::<StreamMap<_, _>>();
+    let mut latest = BTreeMap::new();
+    while let Some((id, reading)) = readings.next().await {
+        match reading {
+            Ok(reading) => latest.insert(id, reading),
+            Err(_) => continue,
+        };
+        let mut reading = latest.get(&primary).cloned().unwrap_or_else(Reading::empty);
+        for other in latest.values() {
+            reading.fill_from(other);
+        }
+        let status = home.ir_out.lock().await.status();
+        let firing = home.rules.lock().await.update(
+            &RuleContext {
+                reading: &reading,
+                powered: status.powered,
+                mode: status.mode,
+                now: Local::now().naive_local(),
+            },
+            Instant::now(),
+        );
+        for rule in firing {
+            info!("rule {} fired on {}", rule.name, reading);
+            for action in &rule.then {
+                if let Err(e) = run_action(&home, action).await {
+                    error!("rule {} could not {}: {}", rule.name, action, e);
+                }
+            }
+        }
+    }
+    info!("atmosphere stopped, stopping rules");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2022-08-01 is a Monday
+        NaiveDate::from_ymd_opt(2022, 8, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn temperature(celsius: f32) -> Reading {
+        Reading {
+            temperature: Some(celsius),
+            ..Reading::empty()
+        }
+    }
+
+    fn context(reading: &Reading, powered: bool, now: NaiveDateTime) -> RuleContext<'_> {
+        RuleContext {
+            reading,
+            powered,
+            mode: ACMode::Cool,
+            now,
+        }
+    }
+
+    fn rules(when: &[&str], debounce_secs: u64, cooldown_secs: u64) -> Rules {
+        Rules::from_rules(vec![Rule {
+            name: "test".to_string(),
+            when: when
+                .iter()
+                .map(|condition| condition.parse().unwrap())
+                .collect(),
+            then: vec!["lcd:Hot".parse().unwrap()],
+            debounce_secs,
+            cooldown_secs,
+        }])
+        .unwrap()
+    }
+
+    /// Feeds one reading a second, returning the seconds at which the rule fired
+    fn simulate(rules: &mut Rules, temperatures: &[f32]) -> Vec<u64> {
+        let start = Instant::now();
+        temperatures
+            .iter()
+            .enumerate()
+            .filter(|(second, celsius)| {
+                let now = start + Duration::from_secs(*second as u64);
+                let reading = temperature(**celsius);
+                !rules
+                    .update(&context(&reading, false, at(1, 12, 0)), now)
+                    .is_empty()
+            })
+            .map(|(second, _)| second as u64)
+            .collect()
+    }
+
+    #[test]
+    fn conditions_round_trip_through_text() {
+        for text in [
+            "temperature > 29",
+            "co2 >= 1000",
+            "humidity <= 40.5",
+            "dew_point < -2",
+            "ac on",
+            "ac off",
+            "ac cool",
+            "time 22:00-06:30",
+            "days mon,wed,sun",
+        ] {
+            let condition = text.parse::<Condition>().unwrap();
+            assert_eq!(condition.to_string(), text);
+        }
+        assert_eq!(
+            "days mon-wed".parse::<Condition>().unwrap(),
+            Condition::Weekdays(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        for text in [
+            "temperature ~ 29",
+            "rain > 1",
+            "temperature > warm",
+            "ac heat",
+            "time 22:00",
+            "days someday",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    text.parse::<Condition>(),
+                    Err(RuleError::InvalidCondition(_))
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn rules_file_round_trips() {
+        let file: RulesFile = persist::from_str(
+            "rules.toml",
+            r#"
+[[rules]]
+name = "evening-cool"
+when = ["temperature > 29", "ac off", "time 10:00-22:00", "days mon-fri"]
+then = ["ac:cool:26", "led:yellow:on", "lcd:Cooling"]
+debounce_secs = 60
+cooldown_secs = 600
+"#,
+        )
+        .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("mattori-home-rules-{}.toml", std::process::id()));
+        persist::save(&path, &file).unwrap();
+        let reloaded: RulesFile = persist::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(reloaded.rules, file.rules);
+        assert!(matches!(
+            Rules::from_rules(vec![Rule {
+                then: Vec::new(),
+                ..file.rules[0].clone()
+            }]),
+            Err(RuleError::NoActions(_))
+        ));
+    }
+
+    #[test]
+    fn time_windows_wrap_past_midnight() {
+        let night = "time 22:00-06:00".parse::<Condition>().unwrap();
+        let reading = Reading::empty();
+        let holds = |now| night.holds(&context(&reading, false, now));
+        assert!(holds(at(1, 22, 0)));
+        assert!(holds(at(1, 23, 59)));
+        assert!(holds(at(2, 5, 59)));
+        assert!(!holds(at(2, 6, 0)));
+        assert!(!holds(at(1, 12, 0)));
+
+        let day = "time 10:00-22:00".parse::<Condition>().unwrap();
+        assert!(day.holds(&context(&reading, false, at(1, 10, 0))));
+        assert!(!day.holds(&context(&reading, false, at(1, 22, 0))));
+    }
+
+    #[test]
+    fn weekdays_and_power() {
+        let reading = temperature(30.0);
+        let weekdays = "days mon-fri".parse::<Condition>().unwrap();
+        assert!(weekdays.holds(&context(&reading, false, at(1, 12, 0))));
+        assert!(!weekdays.holds(&context(&reading, false, at(7, 12, 0))));
+        let off = "ac off".parse::<Condition>().unwrap();
+        assert!(off.holds(&context(&reading, false, at(1, 12, 0))));
+        assert!(!off.holds(&context(&reading, true, at(1, 12, 0))));
+        // readings without the field never hold
+        let co2 = "co2 > 0".parse::<Condition>().unwrap();
+        assert!(!co2.holds(&context(&reading, false, at(1, 12, 0))));
+    }
+
+    #[test]
+    fn fires_once_debounced_per_holding_period() {
+        let mut rules = rules(&["temperature > 29"], 3, 0);
+        let mut temperatures = vec![30.0, 30.0, 28.0];
+        // holds from 3, fires 3 seconds later and not again while it keeps holding
+        temperatures.extend([30.0; 8]);
+        temperatures.push(28.0);
+        temperatures.extend([30.0; 4]);
+        assert_eq!(simulate(&mut rules, &temperatures), vec![6, 15]);
+        assert!(rules.iter().all(|(_, holding)| holding));
+    }
+
+    #[test]
+    fn cooldown_holds_off_firing_again() {
+        let mut rules = rules(&["temperature > 29"], 0, 5);
+        // fires at 0, starts holding again at 2 but waits out the cooldown until 5
+        let temperatures = [30.0, 28.0, 30.0, 30.0, 30.0, 30.0, 30.0, 28.0, 30.0];
+        assert_eq!(simulate(&mut rules, &temperatures), vec![0, 5]);
+    }
+}
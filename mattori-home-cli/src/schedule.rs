@@ -0,0 +1,694 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{
+    Datelike, Duration as ChronoDuration, Local, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use tokio::time::{interval, Duration};
+
+use crate::almanac::{Location, SunEvent};
+use crate::dehumidify::DehumidifySettings;
+use crate::server::HomeImpl;
+use crate::thermostat::ThermostatSettings;
+use mattori_home_peripherals::ir::scene::types::{Scene, SceneStep};
+use mattori_home_peripherals::ir::types::{IrTarget, Temperature};
+use mattori_home_peripherals::led::Leds;
+use mattori_home_peripherals::persist::{self, PersistError};
+
+/// How often the scheduler looks for due schedules
+const TICK: Duration = Duration::from_secs(1);
+
+const TIME_FORMAT: &str = "%H:%M";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// Furthest a schedule can be from sunrise or sunset, so it stays on the same day
+const MAX_SUN_OFFSET: i64 = 12 * 60;
+
+#[derive(Error, Clone, Debug)]
+pub enum ScheduleError {
+    #[error("Invalid schedule time {0}, expected <HH:MM|sunrise[+-offset]|sunset[+-offset]> [mon-fri,sun...] or <YYYY-MM-DD HH:MM>")]
+    InvalidWhen(String),
+    #[error("Invalid schedule action {0}, expected scene:<name>, dehumidifier:<on|off>, thermostat:<on|off>, led:<green|yellow>:<on|off>, lcd:<message> or a scene step")]
+    InvalidAction(String),
+    #[error("Sunrise and sunset schedules need the home's latitude and longitude")]
+    NoLocation,
+    #[error("Unknown schedule {0}")]
+    UnknownSchedule(u32),
+    #[error("Could not persist schedules")]
+    Persist(#[from] PersistError),
+}
+
+/// Source of the current local time, so schedules can be driven by something other than the
+/// system clock
+pub trait Clock: Send + Sync {
+    fn now(&self) -> NaiveDateTime;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleTime {
+    /// Every day at a time, or only on some weekdays if any are given
+    Daily {
+        time: NaiveTime,
+        weekdays: Vec<Weekday>,
+    },
+    /// Some time before or after sunrise or sunset each day, or only on some weekdays if any
+    /// are given
+    Sun {
+        event: SunEvent,
+        offset: ChronoDuration,
+        weekdays: Vec<Weekday>,
+    },
+    Once(NaiveDateTime),
+}
+
+fn on_weekday(weekdays: &[Weekday], day: Weekday) -> bool {
+    weekdays.is_empty() || weekdays.contains(&day)
+}
+
+impl ScheduleTime {
+    /// The first time this schedule is due strictly after `after`, where sunrise and sunset
+    /// schedules are never due without a location
+    pub fn next_after(
+        &self,
+        after: NaiveDateTime,
+        location: Option<&Location>,
+    ) -> Option<NaiveDateTime> {
+        match self {
+            ScheduleTime::Daily { time, weekdays } => (0..=7)
+                .map(|days| (after.date() + ChronoDuration::days(days)).and_time(*time))
+                .find(|candidate| *candidate > after && on_weekday(weekdays, candidate.weekday())),
+            ScheduleTime::Sun {
+                event,
+                offset,
+                weekdays,
+            } => {
+                let location = location?;
+                // from the day before, as an offset can push the time past midnight
+                (-1..=8)
+                    .map(|days| after.date() + ChronoDuration::days(days))
+                    .filter(|date| on_weekday(weekdays, date.weekday()))
+                    .filter_map(|date| {
+                        let at = location.sun_event(*event, date)?;
+                        Some(Local.from_utc_datetime(&at.naive_utc()).naive_local() + *offset)
+                    })
+                    .find(|candidate| *candidate > after)
+            }
+            ScheduleTime::Once(at) => (*at > after).then_some(*at),
+        }
+    }
+
+    pub fn is_once(&self) -> bool {
+        matches!(self, ScheduleTime::Once(_))
+    }
+
+    pub fn needs_location(&self) -> bool {
+        matches!(self, ScheduleTime::Sun { .. })
+    }
+}
+
+/// Parses offsets such as `+30m`, `-1h` or `+1h30m`
+fn parse_sun_offset(s: &str) -> Option<ChronoDuration> {
+    let (sign, mut rest) = match s.chars().next()? {
+        '+' => (1, &s[1..]),
+        '-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let mut minutes: i64 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let value = rest[..digits].parse::<i64>().ok()?;
+        let mut unit = rest[digits..].chars();
+        minutes = minutes.checked_add(match unit.next()? {
+            'h' => value.checked_mul(60)?,
+            'm' => value,
+            _ => return None,
+        })?;
+        rest = unit.as_str();
+    }
+    (minutes <= MAX_SUN_OFFSET).then(|| ChronoDuration::minutes(sign * minutes))
+}
+
+pub(crate) fn parse_weekdays(s: &str) -> Option<Vec<Weekday>> {
+    s.split(',').try_fold(Vec::new(), |mut weekdays, part| {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let mut day = first.parse::<Weekday>().ok()?;
+                let last = last.parse::<Weekday>().ok()?;
+                weekdays.push(day);
+                while day != last {
+                    day = day.succ();
+                    weekdays.push(day);
+                }
+            }
+            None => weekdays.push(part.parse().ok()?),
+        }
+        Some(weekdays)
+    })
+}
+
+impl FromStr for ScheduleTime {
+    type Err = ScheduleError;
+
+    /// Parses forms such as `17:30`, `17:30 mon-fri`, `07:00 sat,sun`, `sunset-30m mon-fri`,
+    /// `sunrise+1h` or `2022-08-01 07:00`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ScheduleError::InvalidWhen(s.to_string());
+        if let Ok(at) = NaiveDateTime::parse_from_str(s.trim(), DATE_TIME_FORMAT) {
+            return Ok(ScheduleTime::Once(at));
+        }
+        let mut parts = s.split_whitespace();
+        let time = parts.next().ok_or_else(invalid)?;
+        let weekdays = match parts.next() {
+            Some(weekdays) => parse_weekdays(weekdays).ok_or_else(invalid)?,
+            None => Vec::new(),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        if let Ok(time) = NaiveTime::parse_from_str(time, TIME_FORMAT) {
+            return Ok(ScheduleTime::Daily { time, weekdays });
+        }
+        let (event, offset) = match time.find(['+', '-']) {
+            Some(sign) => (
+                &time[..sign],
+                parse_sun_offset(&time[sign..]).ok_or_else(invalid)?,
+            ),
+            None => (time, ChronoDuration::zero()),
+        };
+        Ok(ScheduleTime::Sun {
+            event: event.parse().map_err(|_| invalid())?,
+            offset,
+            weekdays,
+        })
+    }
+}
+
+impl Display for ScheduleTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let weekdays = match self {
+            ScheduleTime::Daily { time, weekdays } => {
+                write!(f, "{}", time.format(TIME_FORMAT))?;
+                weekdays
+            }
+            ScheduleTime::Sun {
+                event,
+                offset,
+                weekdays,
+            } => {
+                write!(f, "{}", event)?;
+                if !offset.is_zero() {
+                    write!(f, "{:+}m", offset.num_minutes())?;
+                }
+                weekdays
+            }
+            ScheduleTime::Once(at) => return write!(f, "{}", at.format(DATE_TIME_FORMAT)),
+        };
+        if !weekdays.is_empty() {
+            let weekdays = weekdays
+                .iter()
+                .map(|day| day.to_string().to_lowercase())
+                .collect::<Vec<_>>();
+            write!(f, " {}", weekdays.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleAction {
+    Scene(String),
+    Step(SceneStep),
+    /// Turns the dehumidifier automation on or off
+    Dehumidifier(bool),
+    /// Turns the thermostat automation on or off
+    Thermostat(bool),
+    Led(Leds, bool),
+    /// Shows a message on the lcd until something else is shown
+    Lcd(String),
+}
+
+fn parse_switch(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+impl FromStr for ScheduleAction {
+    type Err = ScheduleError;
+
+    /// Parses `scene:<name>`, `dehumidifier:on`, `thermostat:off`, `led:yellow:on`,
+    /// `lcd:<message>`, or any scene step such as `ac:cool:26`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ScheduleError::InvalidAction(s.to_string());
+        match s.split_once(':') {
+            Some(("scene", name)) if !name.is_empty() => {
+                Ok(ScheduleAction::Scene(name.to_string()))
+            }
+            Some(("dehumidifier", switch)) => parse_switch(switch)
+                .map(ScheduleAction::Dehumidifier)
+                .ok_or_else(invalid),
+            Some(("thermostat", switch)) => parse_switch(switch)
+                .map(ScheduleAction::Thermostat)
+                .ok_or_else(invalid),
+            Some(("led", args)) => args
+                .split_once(':')
+                .and_then(|(led, switch)| Some((led.parse().ok()?, parse_switch(switch)?)))
+                .map(|(led, on)| ScheduleAction::Led(led, on))
+                .ok_or_else(invalid),
+            Some(("lcd", message)) => Ok(ScheduleAction::Lcd(message.to_string())),
+            _ => s.parse().map(ScheduleAction::Step).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Display for ScheduleAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleAction::Scene(name) => write!(f, "scene:{}", name),
+            ScheduleAction::Step(step) => write!(f, "{}", step),
+            ScheduleAction::Dehumidifier(enabled) => {
+                write!(f, "dehumidifier:{}", if *enabled { "on" } else { "off" })
+            }
+            ScheduleAction::Thermostat(enabled) => {
+                write!(f, "thermostat:{}", if *enabled { "on" } else { "off" })
+            }
+            ScheduleAction::Led(led, on) => {
+                write!(f, "led:{}:{}", led, if *on { "on" } else { "off" })
+            }
+            ScheduleAction::Lcd(message) => write!(f, "lcd:{}", message),
+        }
+    }
+}
+
+impl Serialize for ScheduleAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScheduleAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+pub(crate) fn serialize_display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+pub(crate) fn deserialize_from_str<'de, T: FromStr, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error>
+where
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u32,
+    #[serde(
+        serialize_with = "serialize_display",
+        deserialize_with = "deserialize_from_str"
+    )]
+    pub when: ScheduleTime,
+    pub action: ScheduleAction,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SchedulesFile {
+    #[serde(default)]
+    schedules: Vec<Schedule>,
+}
+
+/// Schedules keyed by id, saved back to their file whenever they change
+#[derive(Debug)]
+pub struct Schedules {
+    path: PathBuf,
+    schedules: BTreeMap<u32, Schedule>,
+    location: Option<Location>,
+}
+
+impl Schedules {
+    /// Reads schedules from a toml file, treating a missing file as having none saved, with
+    /// `location` used for sunrise and sunset schedules
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        location: Option<Location>,
+    ) -> Result<Schedules, ScheduleError> {
+        let file: SchedulesFile = persist::load(&path)?;
+        Ok(Schedules {
+            path: path.as_ref().to_path_buf(),
+            location,
+            schedules: file
+                .schedules
+                .into_iter()
+                .map(|schedule| (schedule.id, schedule))
+                .collect(),
+        })
+    }
+
+    fn save(&self) -> Result<(), ScheduleError> {
+        Ok(persist::save(
+            &self.path,
+            &SchedulesFile {
+                schedules: self.schedules.values().cloned().collect(),
+            },
+        )?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Schedule> + '_ {
+        self.schedules.values()
+    }
+
+    /// When a schedule is next due after `after`
+    pub fn next_after(&self, schedule: &Schedule, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        schedule.when.next_after(after, self.location.as_ref())
+    }
+
+    pub fn create(
+        &mut self,
+        when: ScheduleTime,
+        action: ScheduleAction,
+    ) -> Result<Schedule, ScheduleError> {
+        if when.needs_location() && self.location.is_none() {
+            return Err(ScheduleError::NoLocation);
+        }
+        let id = self.schedules.keys().next_back().map_or(1, |id| id + 1);
+        let schedule = Schedule { id, when, action };
+        self.schedules.insert(id, schedule.clone());
+        self.save()?;
+        Ok(schedule)
+    }
+
+    pub fn delete(&mut self, id: u32) -> Result<Schedule, ScheduleError> {
+        let schedule = self
+            .schedules
+            .remove(&id)
+            .ok_or(ScheduleError::UnknownSchedule(id))?;
+        self.save()?;
+        Ok(schedule)
+    }
+
+    /// Schedules due in `(from, to]`, with one-shot schedules removed as they are handed out
+    pub fn take_due(
+        &mut self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<Schedule>, ScheduleError> {
+        let due = self
+            .schedules
+            .values()
+            .filter(|schedule| {
+                self.next_after(schedule, from)
+                    .is_some_and(|next| next <= to)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let before = self.schedules.len();
+        self.schedules
+            .retain(|_, schedule| !(schedule.when.is_once() && due.contains(schedule)));
+        if self.schedules.len() != before {
+            self.save()?;
+        }
+        Ok(due)
+    }
+}
+
+/// Hands out schedules as `clock` passes them
+struct DueSchedules {
+    clock: Arc<dyn Clock>,
+    last_check: NaiveDateTime,
+}
+
+impl DueSchedules {
+    fn new(clock: Arc<dyn Clock>) -> DueSchedules {
+        let last_check = clock.now();
+        DueSchedules { clock, last_check }
+    }
+
+    /// Schedules due since the last call
+    fn take(&mut self, schedules: &mut Schedules) -> Result<Vec<Schedule>, ScheduleError> {
+        let now = self.clock.now();
+        let from = std::mem::replace(&mut self.last_check, now);
+        if now < from {
+            // clock went backwards, so skip rather than run schedules twice
+            return Ok(Vec::new());
+        }
+        schedules.take_due(from, now)
+    }
+}
+
+/// Checks for due schedules every tick of `clock`, running their actions in order
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(
+    home: Arc<HomeImpl<T>>,
+    clock: Arc<dyn Clock>,
+) where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut ticks = interval(TICK);
+    let mut due_schedules = DueSchedules::new(clock);
+    loop {
+        ticks.tick().await;
+        let due = due_schedules.take(&mut *home.schedules.lock().await);
+        let due = match due {
+            Ok(due) => due,
+            Err(e) => {
+                error!("could not update schedules: {}", e);
+                continue;
+            }
+        };
+        for schedule in due {
+            info!("running schedule {}: {}", schedule.id, schedule.action);
+            let res = run_action(&home, &schedule.action).await;
+            if let Err(e) = res {
+                error!("schedule {} failed: {}", schedule.id, e);
+            }
+        }
+    }
+}
+
+/// Runs an action from a schedule or rule
+pub async fn run_action<T: IrTarget + Debug + Send + Sync + 'static>(
+    home: &HomeImpl<T>,
+    action: &ScheduleAction,
+) -> Result<(), String>
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    match action {
+        ScheduleAction::Scene(name) => home.run_scene(name).await.map_err(|e| e.to_string()),
+        ScheduleAction::Step(step) => Scene {
+            steps: vec![step.clone()],
+        }
+        .run(&home.ir_out, &home.remotes)
+        .await
+        .map_err(|e| e.to_string()),
+        ScheduleAction::Dehumidifier(enabled) => {
+            let mut dehumidifier = home.dehumidifier.lock().await;
+            let settings = DehumidifySettings {
+                enabled: *enabled,
+                ..dehumidifier.settings().clone()
+            };
+            dehumidifier
+                .set_settings(settings)
+                .map_err(|e| e.to_string())
+        }
+        ScheduleAction::Thermostat(enabled) => {
+            let mut thermostat = home.thermostat.lock().await;
+            let settings = ThermostatSettings {
+                enabled: *enabled,
+                ..thermostat.settings().clone()
+            };
+            let running = thermostat.is_running();
+            thermostat
+                .set_settings(settings, running)
+                .map_err(|e| e.to_string())
+        }
+        ScheduleAction::Led(led, on) => {
+            home.set_led(*led, *on).await;
+            Ok(())
+        }
+        ScheduleAction::Lcd(message) => home.show_message(message).await.map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::sync::Mutex;
+
+    struct TestClock(Mutex<NaiveDateTime>);
+
+    impl TestClock {
+        fn set(&self, at: NaiveDateTime) {
+            *self.0.lock().unwrap() = at;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> NaiveDateTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2022-07-01 is a Friday
+        NaiveDate::from_ymd_opt(2022, 7, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedules(name: &str) -> Schedules {
+        let path = std::env::temp_dir().join(format!(
+            "mattori-home-schedules-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        Schedules::load(path, None).unwrap()
+    }
+
+    fn ids(due: Vec<Schedule>) -> Vec<u32> {
+        due.into_iter().map(|schedule| schedule.id).collect()
+    }
+
+    #[test]
+    fn parses_sun_offsets() {
+        assert_eq!(parse_sun_offset("+30m"), Some(ChronoDuration::minutes(30)));
+        assert_eq!(parse_sun_offset("-1h"), Some(ChronoDuration::minutes(-60)));
+        assert_eq!(
+            parse_sun_offset("+1h30m"),
+            Some(ChronoDuration::minutes(90))
+        );
+        assert_eq!(parse_sun_offset("+13h"), None);
+        assert_eq!(parse_sun_offset("+30"), None);
+        assert_eq!(parse_sun_offset("30m"), None);
+        assert_eq!(parse_sun_offset("+9223372036854775807m+1m"), None);
+    }
+
+    #[test]
+    fn rejects_non_ascii_units() {
+        assert_eq!(parse_sun_offset("+30é"), None);
+        assert_eq!(parse_sun_offset("-1时"), None);
+        assert!("sunset+30é".parse::<ScheduleTime>().is_err());
+        assert!("sunrise-1时".parse::<ScheduleTime>().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for when in [
+            "17:30 mon,tue,wed,thu,fri",
+            "2022-08-01 07:00",
+            "sunset-30m sat,sun",
+            "sunrise+90m",
+        ] {
+            assert_eq!(when.parse::<ScheduleTime>().unwrap().to_string(), when);
+        }
+        assert_eq!(
+            "scene:goodnight".parse::<ScheduleAction>().unwrap(),
+            ScheduleAction::Scene("goodnight".to_string())
+        );
+    }
+
+    #[test]
+    fn weekday_schedules_follow_the_clock() {
+        let mut schedules = schedules("weekday");
+        let weekdays = schedules
+            .create(
+                "17:30 mon-fri".parse().unwrap(),
+                "ac:cool:26".parse().unwrap(),
+            )
+            .unwrap();
+        let clock = Arc::new(TestClock(Mutex::new(at(1, 17, 0))));
+        let mut due = DueSchedules::new(clock.clone());
+
+        clock.set(at(1, 17, 29));
+        assert!(due.take(&mut schedules).unwrap().is_empty());
+        clock.set(at(1, 17, 30));
+        assert_eq!(ids(due.take(&mut schedules).unwrap()), vec![weekdays.id]);
+        // saturday and sunday pass without it
+        clock.set(at(3, 23, 0));
+        assert!(due.take(&mut schedules).unwrap().is_empty());
+        clock.set(at(4, 18, 0));
+        assert_eq!(ids(due.take(&mut schedules).unwrap()), vec![weekdays.id]);
+    }
+
+    #[test]
+    fn one_shot_schedules_run_once() {
+        let mut schedules = schedules("once");
+        let once = schedules
+            .create(
+                "2022-07-02 07:00".parse().unwrap(),
+                "dehumidifier:on".parse().unwrap(),
+            )
+            .unwrap();
+        let clock = Arc::new(TestClock(Mutex::new(at(1, 12, 0))));
+        let mut due = DueSchedules::new(clock.clone());
+
+        clock.set(at(2, 8, 0));
+        assert_eq!(ids(due.take(&mut schedules).unwrap()), vec![once.id]);
+        assert_eq!(schedules.iter().count(), 0);
+        assert_eq!(
+            Schedules::load(&schedules.path, None)
+                .unwrap()
+                .iter()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn skips_a_check_when_the_clock_goes_backwards() {
+        let mut schedules = schedules("backwards");
+        schedules
+            .create("17:30".parse().unwrap(), "led:green:on".parse().unwrap())
+            .unwrap();
+        let clock = Arc::new(TestClock(Mutex::new(at(1, 17, 0))));
+        let mut due = DueSchedules::new(clock.clone());
+
+        clock.set(at(1, 17, 45));
+        assert_eq!(due.take(&mut schedules).unwrap().len(), 1);
+        clock.set(at(1, 17, 15));
+        assert!(due.take(&mut schedules).unwrap().is_empty());
+        // the clock really does pass 17:30 again
+        clock.set(at(1, 17, 40));
+        assert_eq!(due.take(&mut schedules).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sun_schedules_need_a_location() {
+        let mut schedules = schedules("sun");
+        assert!(matches!(
+            schedules.create(
+                "sunset-30m".parse().unwrap(),
+                "led:green:on".parse().unwrap()
+            ),
+            Err(ScheduleError::NoLocation)
+        ));
+    }
+}
@@ -2,17 +2,39 @@ use std::convert::TryFrom;
 use std::fmt::{Debug, Display};
 use std::pin::Pin;
 
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use tokio_stream::wrappers::WatchStream;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::{Stream, StreamExt, StreamMap};
 
 use mattori_home::home_server::Home;
-use mattori_home::{AcStatus, AcStatusParam, AtmosphereReading};
-use mattori_home_peripherals::atmosphere::Atmosphere;
-use mattori_home_peripherals::ir::output::IrOut;
-use mattori_home_peripherals::ir::types::{ACMode, IrStatus, IrTarget};
+use mattori_home::{
+    AcStatus, AcStatusParam, AtmosphereReading, AtmosphereSensorId, ButtonPress, DehumidifierParam,
+    ForecastParam, HeatAlertParam, ListAtmosphereSensorsParam, ListRemotesParam, ListRulesParam,
+    ListScenesParam, ListSchedulesParam, ListTimersParam, RemoteList, RuleList, SceneList,
+    SceneName, ScheduleId, ScheduleList, SeaLevelPressure, StationAltitude, TemperatureReference,
+    ThermostatParam, TimerId, TimerList,
+};
+use mattori_home_peripherals::atmosphere::sensors::{NamedSensor, Sensors, SensorsError};
+use mattori_home_peripherals::atmosphere::user_calibration::UserCalibration;
+use mattori_home_peripherals::atmosphere::AtmosphereFeatures;
+use mattori_home_peripherals::ir::output::{IrOut, IrOutError};
+use mattori_home_peripherals::ir::remote::{RemoteError, Remotes};
+use mattori_home_peripherals::ir::scene::types::{Scene, SceneStep};
+use mattori_home_peripherals::ir::scene::{SceneError, Scenes};
+use mattori_home_peripherals::ir::types::{ACMode, IrStatus, IrTarget, Temperature};
+use mattori_home_peripherals::lcd::{Lcd, LcdError};
+use mattori_home_peripherals::led::{Led, Leds};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::dehumidify::{Dehumidifier, DehumidifySettings};
+use crate::heat::{HeatAlertSettings, HeatWatch};
+use crate::rules::Rules;
+use crate::schedule::{ScheduleAction, ScheduleError, ScheduleTime, Schedules};
+use crate::thermostat::{Thermostat, ThermostatSettings};
+use crate::timer::Timers;
 
 pub mod mattori_home {
     tonic::include_proto!("mattori_home");
@@ -21,16 +43,85 @@ pub mod mattori_home {
 #[derive(Debug)]
 pub struct HomeImpl<T: IrTarget + Debug + Send + Sync + 'static>
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
-    pub atmosphere: Atmosphere,
+    pub sensors: Sensors,
     pub ir_out: Mutex<IrOut<T>>,
+    pub remotes: Mutex<Remotes>,
+    pub scenes: Mutex<Scenes>,
+    pub thermostat: Mutex<Thermostat>,
+    pub dehumidifier: Mutex<Dehumidifier>,
+    pub schedules: Mutex<Schedules>,
+    pub timers: Mutex<Timers>,
+    pub rules: Mutex<Rules>,
+    pub heat: Mutex<HeatWatch>,
+    pub green_led: Mutex<Led>,
+    pub yellow_led: Mutex<Led>,
+    pub lcd: Mutex<Lcd>,
+}
+
+impl<T: IrTarget + Debug + Send + Sync + 'static> HomeImpl<T>
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    /// Runs a saved scene, without holding the scenes lock while it is being sent
+    pub async fn run_scene(&self, name: &str) -> Result<(), SceneError> {
+        let scene = self.scenes.lock().await.get(name)?.clone();
+        scene.run(&self.ir_out, &self.remotes).await
+    }
+
+    pub async fn set_led(&self, led: Leds, on: bool) {
+        let mut led = match led {
+            Leds::Green => self.green_led.lock().await,
+            Leds::Yellow => self.yellow_led.lock().await,
+        };
+        if on {
+            led.on();
+        } else {
+            led.off();
+        }
+    }
+
+    pub async fn show_message(&self, message: &str) -> Result<(), LcdError> {
+        self.lcd.lock().await.show(message)
+    }
+
+    async fn ac_status(&self) -> AcStatus {
+        let ir_out = self.ir_out.lock().await;
+        AcStatus {
+            temperature_range: Some(ir_out.target().temperature_range().into()),
+            ..ir_out.status().into()
+        }
+    }
+
+    fn atmosphere_sensor(
+        &self,
+        sensor: &NamedSensor,
+    ) -> Result<mattori_home::AtmosphereSensor, SensorsError> {
+        let calibration = self.sensors.calibration(&sensor.id)?;
+        Ok(mattori_home::AtmosphereSensor {
+            primary: sensor.id == self.sensors.primary_id(),
+            calibration: Some((sensor.id.as_str(), calibration).into()),
+            sea_level_pressure: self.sensors.sea_level_pressure(&sensor.id)?,
+            health: Some((sensor.id.as_str(), self.sensors.health(&sensor.id)?).into()),
+            ..sensor.into()
+        })
+    }
+
+    /// The requested sensor, or the primary sensor if none was given
+    fn sensor_id(&self, sensor_id: &str) -> String {
+        if sensor_id.is_empty() {
+            self.sensors.primary_id().to_string()
+        } else {
+            sensor_id.to_string()
+        }
+    }
 }
 
 #[tonic::async_trait]
 impl<T: IrTarget + Debug + Send + Sync + 'static> Home for HomeImpl<T>
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     type ReadAtmosphereStream = Pin<
         Box<dyn Stream<Item = Result<AtmosphereReading, tonic::Status>> + Send + Sync + 'static>,
@@ -38,23 +129,44 @@ where
 
     async fn read_atmosphere(
         &self,
-        request: tonic::Request<tonic::Streaming<mattori_home::AtmosphereFeatures>>,
+        request: tonic::Request<tonic::Streaming<mattori_home::AtmosphereRequest>>,
     ) -> Result<tonic::Response<Self::ReadAtmosphereStream>, tonic::Status> {
         let mut feature_stream = request.into_inner();
+        let (sensor_id, features) = feature_stream
+            .message()
+            .await?
+            .map(|request| (request.sensor_id, requested_features(request.features)))
+            .unwrap_or_default();
+        let (features_sender, features) = watch::channel(features);
+        let mut readings = StreamMap::new();
+        if sensor_id.is_empty() {
+            for sensor in self.sensors.iter() {
+                readings.insert(
+                    sensor.id.clone(),
+                    WatchStream::new(sensor.atmosphere.subscribe()),
+                );
+            }
+        } else {
+            let sensor = self.sensors.get(&sensor_id).map_err(sensors_status)?;
+            readings.insert(sensor_id, WatchStream::new(sensor.atmosphere.subscribe()));
+        }
         let running = Arc::new(AtomicBool::new(true));
         let reading_stream = {
             let running = running.clone();
-            WatchStream::new(self.atmosphere.subscribe())
-                .map(|res| {
-                    res.map(mattori_home::AtmosphereReading::from)
-                        .map_err(|e| tonic::Status::internal(e.to_string()))
+            readings
+                .map(move |(sensor_id, res)| {
+                    res.map(|reading| mattori_home::AtmosphereReading {
+                        sensor_id,
+                        ..reading.filtered(&features.borrow()).into()
+                    })
+                    .map_err(|e| tonic::Status::internal(e.to_string()))
                 })
                 .take_while(move |_| running.load(Ordering::Acquire))
         };
 
         tokio::spawn(async move {
-            while let Some(Ok(_)) = feature_stream.next().await {
-                // todo implement
+            while let Some(Ok(request)) = feature_stream.next().await {
+                let _ = features_sender.send(requested_features(request.features));
             }
             running.store(false, Ordering::Release);
         });
@@ -68,9 +180,7 @@ where
         &self,
         _: tonic::Request<AcStatusParam>,
     ) -> Result<tonic::Response<AcStatus>, tonic::Status> {
-        Ok(tonic::Response::new(
-            self.ir_out.lock().await.status().into(),
-        ))
+        Ok(tonic::Response::new(self.ac_status().await))
     }
 
     async fn set_ac_status(
@@ -78,10 +188,23 @@ where
         request: tonic::Request<AcStatus>,
     ) -> Result<tonic::Response<AcStatus>, tonic::Status> {
         let ac_status = request.into_inner();
+        let temperature = Temperature::from(
+            ac_status
+                .temperature
+                .clone()
+                .ok_or_else(|| tonic::Status::invalid_argument("Missing temperature"))?,
+        );
+        let range = self.ir_out.lock().await.target().temperature_range();
+        let temperature = range.normalize(temperature).ok_or_else(|| {
+            tonic::Status::invalid_argument(format!(
+                "Temperature {} out of range {}",
+                temperature, range
+            ))
+        })?;
         let new_status = IrStatus {
             powered: ac_status.powered,
             mode: ACMode::from(ac_status.mode()),
-            temperature: T::Temperature::try_from(ac_status.temperature)
+            temperature: T::Temperature::try_from(temperature)
                 .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?,
         };
         self.ir_out
@@ -89,8 +212,418 @@ where
             .await
             .send_status(new_status)
             .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        let delays = [
+            (false, ac_status.off_after_secs),
+            (true, ac_status.on_after_secs),
+        ];
+        let mut timers = self.timers.lock().await;
+        for (powered, secs) in delays.into_iter().filter(|(_, secs)| *secs > 0) {
+            timers.set(powered, Instant::now() + Duration::from_secs(secs.into()));
+        }
+        drop(timers);
+        Ok(tonic::Response::new(self.ac_status().await))
+    }
+
+    async fn list_remotes(
+        &self,
+        _: tonic::Request<ListRemotesParam>,
+    ) -> Result<tonic::Response<RemoteList>, tonic::Status> {
+        Ok(tonic::Response::new(RemoteList {
+            remotes: self
+                .remotes
+                .lock()
+                .await
+                .iter()
+                .map(mattori_home::Remote::from)
+                .collect(),
+        }))
+    }
+
+    async fn press_button(
+        &self,
+        request: tonic::Request<ButtonPress>,
+    ) -> Result<tonic::Response<mattori_home::Remote>, tonic::Status> {
+        let ButtonPress { remote, button } = request.into_inner();
+        let mut remotes = self.remotes.lock().await;
+        let remote = remotes
+            .get_mut(&remote)
+            .map_err(|e| tonic::Status::not_found(e.to_string()))?;
+        self.ir_out
+            .lock()
+            .await
+            .send_remote(remote, &button)
+            .map_err(|e| match e {
+                IrOutError::Remote(e @ RemoteError::UnknownButton(..)) => {
+                    tonic::Status::not_found(e.to_string())
+                }
+                e => tonic::Status::internal(e.to_string()),
+            })?;
+        Ok(tonic::Response::new(mattori_home::Remote::from(&*remote)))
+    }
+
+    async fn list_scenes(
+        &self,
+        _: tonic::Request<ListScenesParam>,
+    ) -> Result<tonic::Response<SceneList>, tonic::Status> {
+        Ok(tonic::Response::new(SceneList {
+            scenes: self
+                .scenes
+                .lock()
+                .await
+                .iter()
+                .map(mattori_home::Scene::from)
+                .collect(),
+        }))
+    }
+
+    async fn save_scene(
+        &self,
+        request: tonic::Request<mattori_home::Scene>,
+    ) -> Result<tonic::Response<mattori_home::Scene>, tonic::Status> {
+        let mattori_home::Scene { name, steps } = request.into_inner();
+        let scene = Scene {
+            steps: steps
+                .iter()
+                .map(|step| step.parse::<SceneStep>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?,
+        };
+        let response = mattori_home::Scene::from((name.as_str(), &scene));
+        self.scenes
+            .lock()
+            .await
+            .insert(name, scene)
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn delete_scene(
+        &self,
+        request: tonic::Request<SceneName>,
+    ) -> Result<tonic::Response<mattori_home::Scene>, tonic::Status> {
+        let SceneName { name } = request.into_inner();
+        let scene = self
+            .scenes
+            .lock()
+            .await
+            .remove(&name)
+            .map_err(scene_status)?;
+        Ok(tonic::Response::new(mattori_home::Scene::from((
+            name.as_str(),
+            &scene,
+        ))))
+    }
+
+    async fn run_scene(
+        &self,
+        request: tonic::Request<SceneName>,
+    ) -> Result<tonic::Response<AcStatus>, tonic::Status> {
+        HomeImpl::run_scene(self, &request.into_inner().name)
+            .await
+            .map_err(scene_status)?;
+        Ok(tonic::Response::new(self.ac_status().await))
+    }
+
+    async fn get_thermostat(
+        &self,
+        _: tonic::Request<ThermostatParam>,
+    ) -> Result<tonic::Response<mattori_home::Thermostat>, tonic::Status> {
         Ok(tonic::Response::new(
-            self.ir_out.lock().await.status().into(),
+            (&*self.thermostat.lock().await).into(),
         ))
     }
+
+    async fn set_thermostat(
+        &self,
+        request: tonic::Request<mattori_home::ThermostatSettings>,
+    ) -> Result<tonic::Response<mattori_home::Thermostat>, tonic::Status> {
+        let mut settings = ThermostatSettings::from(request.into_inner());
+        let (range, powered) = {
+            let ir_out = self.ir_out.lock().await;
+            (ir_out.target().temperature_range(), ir_out.status().powered)
+        };
+        settings.target = range.normalize(settings.target).ok_or_else(|| {
+            tonic::Status::invalid_argument(format!(
+                "Temperature {} out of range {}",
+                settings.target, range
+            ))
+        })?;
+        let mut thermostat = self.thermostat.lock().await;
+        thermostat
+            .set_settings(settings, powered)
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        Ok(tonic::Response::new((&*thermostat).into()))
+    }
+
+    async fn get_dehumidifier(
+        &self,
+        _: tonic::Request<DehumidifierParam>,
+    ) -> Result<tonic::Response<mattori_home::Dehumidifier>, tonic::Status> {
+        Ok(tonic::Response::new(
+            (&*self.dehumidifier.lock().await).into(),
+        ))
+    }
+
+    async fn set_dehumidifier(
+        &self,
+        request: tonic::Request<mattori_home::DehumidifierSettings>,
+    ) -> Result<tonic::Response<mattori_home::Dehumidifier>, tonic::Status> {
+        let mut dehumidifier = self.dehumidifier.lock().await;
+        dehumidifier
+            .set_settings(DehumidifySettings::from(request.into_inner()))
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        Ok(tonic::Response::new((&*dehumidifier).into()))
+    }
+
+    async fn create_schedule(
+        &self,
+        request: tonic::Request<mattori_home::Schedule>,
+    ) -> Result<tonic::Response<mattori_home::Schedule>, tonic::Status> {
+        let mattori_home::Schedule { when, action, .. } = request.into_inner();
+        let when = when
+            .parse::<ScheduleTime>()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        let action = action
+            .parse::<ScheduleAction>()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        let schedule = self
+            .schedules
+            .lock()
+            .await
+            .create(when, action)
+            .map_err(schedule_status)?;
+        Ok(tonic::Response::new((&schedule).into()))
+    }
+
+    async fn list_schedules(
+        &self,
+        _: tonic::Request<ListSchedulesParam>,
+    ) -> Result<tonic::Response<ScheduleList>, tonic::Status> {
+        Ok(tonic::Response::new(ScheduleList {
+            schedules: self
+                .schedules
+                .lock()
+                .await
+                .iter()
+                .map(mattori_home::Schedule::from)
+                .collect(),
+        }))
+    }
+
+    async fn delete_schedule(
+        &self,
+        request: tonic::Request<ScheduleId>,
+    ) -> Result<tonic::Response<mattori_home::Schedule>, tonic::Status> {
+        let schedule = self
+            .schedules
+            .lock()
+            .await
+            .delete(request.into_inner().id)
+            .map_err(schedule_status)?;
+        Ok(tonic::Response::new((&schedule).into()))
+    }
+
+    async fn list_timers(
+        &self,
+        _: tonic::Request<ListTimersParam>,
+    ) -> Result<tonic::Response<TimerList>, tonic::Status> {
+        Ok(tonic::Response::new(TimerList {
+            timers: self
+                .timers
+                .lock()
+                .await
+                .iter()
+                .map(mattori_home::Timer::from)
+                .collect(),
+        }))
+    }
+
+    async fn cancel_timer(
+        &self,
+        request: tonic::Request<TimerId>,
+    ) -> Result<tonic::Response<mattori_home::Timer>, tonic::Status> {
+        let timer = self
+            .timers
+            .lock()
+            .await
+            .cancel(request.into_inner().id)
+            .map_err(|e| tonic::Status::not_found(e.to_string()))?;
+        Ok(tonic::Response::new((&timer).into()))
+    }
+
+    async fn list_rules(
+        &self,
+        _: tonic::Request<ListRulesParam>,
+    ) -> Result<tonic::Response<RuleList>, tonic::Status> {
+        Ok(tonic::Response::new(RuleList {
+            rules: self
+                .rules
+                .lock()
+                .await
+                .iter()
+                .map(mattori_home::Rule::from)
+                .collect(),
+        }))
+    }
+
+    async fn get_heat_alert(
+        &self,
+        _: tonic::Request<HeatAlertParam>,
+    ) -> Result<tonic::Response<mattori_home::HeatAlert>, tonic::Status> {
+        Ok(tonic::Response::new((&*self.heat.lock().await).into()))
+    }
+
+    async fn set_heat_alert(
+        &self,
+        request: tonic::Request<mattori_home::HeatAlertSettings>,
+    ) -> Result<tonic::Response<mattori_home::HeatAlert>, tonic::Status> {
+        let mut settings = HeatAlertSettings::from(request.into_inner());
+        let range = self.ir_out.lock().await.target().temperature_range();
+        settings.cool_temperature =
+            range.normalize(settings.cool_temperature).ok_or_else(|| {
+                tonic::Status::invalid_argument(format!(
+                    "Temperature {} out of range {}",
+                    settings.cool_temperature, range
+                ))
+            })?;
+        let mut heat = self.heat.lock().await;
+        heat.set_settings(settings);
+        Ok(tonic::Response::new((&*heat).into()))
+    }
+
+    async fn list_atmosphere_sensors(
+        &self,
+        _: tonic::Request<ListAtmosphereSensorsParam>,
+    ) -> Result<tonic::Response<mattori_home::AtmosphereSensorList>, tonic::Status> {
+        let sensors = self
+            .sensors
+            .iter()
+            .map(|sensor| self.atmosphere_sensor(sensor))
+            .collect::<Result<_, _>>()
+            .map_err(sensors_status)?;
+        Ok(tonic::Response::new(mattori_home::AtmosphereSensorList {
+            sensors,
+        }))
+    }
+
+    async fn set_atmosphere_calibration(
+        &self,
+        request: tonic::Request<mattori_home::AtmosphereCalibration>,
+    ) -> Result<tonic::Response<mattori_home::AtmosphereCalibration>, tonic::Status> {
+        let calibration = request.into_inner();
+        let sensor_id = self.sensor_id(&calibration.sensor_id);
+        let calibration = UserCalibration::from(calibration);
+        self.sensors
+            .set_calibration(&sensor_id, calibration)
+            .map_err(sensors_status)?;
+        Ok(tonic::Response::new(
+            (sensor_id.as_str(), calibration).into(),
+        ))
+    }
+
+    async fn calibrate_temperature(
+        &self,
+        request: tonic::Request<TemperatureReference>,
+    ) -> Result<tonic::Response<mattori_home::AtmosphereCalibration>, tonic::Status> {
+        let TemperatureReference {
+            sensor_id,
+            celsius,
+            restart,
+        } = request.into_inner();
+        let sensor_id = self.sensor_id(&sensor_id);
+        let calibration = self
+            .sensors
+            .calibrate_temperature(&sensor_id, celsius, restart)
+            .map_err(sensors_status)?;
+        Ok(tonic::Response::new(
+            (sensor_id.as_str(), calibration).into(),
+        ))
+    }
+
+    async fn set_sea_level_pressure(
+        &self,
+        request: tonic::Request<SeaLevelPressure>,
+    ) -> Result<tonic::Response<SeaLevelPressure>, tonic::Status> {
+        let SeaLevelPressure { sensor_id, hpa } = request.into_inner();
+        let sensor_id = self.sensor_id(&sensor_id);
+        self.sensors
+            .set_sea_level_pressure(&sensor_id, hpa)
+            .map_err(sensors_status)?;
+        Ok(tonic::Response::new(SeaLevelPressure { sensor_id, hpa }))
+    }
+
+    async fn set_station_altitude(
+        &self,
+        request: tonic::Request<StationAltitude>,
+    ) -> Result<tonic::Response<SeaLevelPressure>, tonic::Status> {
+        let StationAltitude { sensor_id, metres } = request.into_inner();
+        let sensor_id = self.sensor_id(&sensor_id);
+        let hpa = self
+            .sensors
+            .set_station_altitude(&sensor_id, metres)
+            .map_err(sensors_status)?;
+        Ok(tonic::Response::new(SeaLevelPressure { sensor_id, hpa }))
+    }
+
+    async fn recalibrate_atmosphere(
+        &self,
+        request: tonic::Request<AtmosphereSensorId>,
+    ) -> Result<tonic::Response<mattori_home::AtmosphereSensor>, tonic::Status> {
+        let sensor_id = self.sensor_id(&request.into_inner().sensor_id);
+        self.sensors
+            .recalibrate(&sensor_id)
+            .map_err(sensors_status)?;
+        let sensor = self.sensors.get(&sensor_id).map_err(sensors_status)?;
+        Ok(tonic::Response::new(
+            self.atmosphere_sensor(sensor).map_err(sensors_status)?,
+        ))
+    }
+
+    async fn get_forecast(
+        &self,
+        request: tonic::Request<ForecastParam>,
+    ) -> Result<tonic::Response<mattori_home::Forecast>, tonic::Status> {
+        let sensor_id = self.sensor_id(&request.into_inner().sensor_id);
+        let forecast = self.sensors.forecast(&sensor_id).map_err(sensors_status)?;
+        Ok(tonic::Response::new((sensor_id.as_str(), forecast).into()))
+    }
+
+    async fn get_atmosphere_health(
+        &self,
+        request: tonic::Request<AtmosphereSensorId>,
+    ) -> Result<tonic::Response<mattori_home::AtmosphereHealth>, tonic::Status> {
+        let sensor_id = self.sensor_id(&request.into_inner().sensor_id);
+        let health = self.sensors.health(&sensor_id).map_err(sensors_status)?;
+        Ok(tonic::Response::new((sensor_id.as_str(), health).into()))
+    }
+}
+
+fn scene_status(e: SceneError) -> tonic::Status {
+    match e {
+        SceneError::UnknownScene(_) => tonic::Status::not_found(e.to_string()),
+        e => tonic::Status::internal(e.to_string()),
+    }
+}
+
+fn schedule_status(e: ScheduleError) -> tonic::Status {
+    match e {
+        ScheduleError::UnknownSchedule(_) => tonic::Status::not_found(e.to_string()),
+        e => tonic::Status::internal(e.to_string()),
+    }
+}
+
+fn sensors_status(e: SensorsError) -> tonic::Status {
+    match e {
+        SensorsError::UnknownSensor(_) => tonic::Status::not_found(e.to_string()),
+        SensorsError::NoReading(_, _) => tonic::Status::failed_precondition(e.to_string()),
+        SensorsError::ImplausiblePressure(_) => tonic::Status::invalid_argument(e.to_string()),
+        SensorsError::InvalidCalibration(_) => tonic::Status::invalid_argument(e.to_string()),
+        SensorsError::NoPressureTrend(_) => tonic::Status::failed_precondition(e.to_string()),
+        e => tonic::Status::internal(e.to_string()),
+    }
+}
+
+/// Fields a reading stream should carry, everything unless the request says otherwise
+fn requested_features(features: Option<mattori_home::AtmosphereFeatures>) -> AtmosphereFeatures {
+    features.map(AtmosphereFeatures::from).unwrap_or_default()
 }
@@ -0,0 +1,329 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::time::{Duration, Instant};
+
+use crate::server::HomeImpl;
+use mattori_home_peripherals::ir::types::{
+    ACMode, IrStatus, IrTarget, Temperature, TemperatureUnit,
+};
+
+#[derive(Error, Clone, Debug)]
+pub enum ThermostatError {
+    #[error("Thermostat can only cool, warm or dry, not {0}")]
+    Mode(ACMode),
+    #[error("Hysteresis must not be negative")]
+    Hysteresis,
+    #[error("Could not set AC to thermostat target: {0}")]
+    Target(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ThermostatSettings {
+    pub enabled: bool,
+    /// Room temperature to keep, which is also what the AC is set to
+    pub target: Temperature,
+    /// How far past the target the room can drift before the AC is started again, in celsius
+    pub hysteresis: f32,
+    pub mode: ACMode,
+    /// Shortest time the AC is left running before it can be stopped
+    pub min_on: Duration,
+    /// Shortest time the AC is left stopped before it can be started
+    pub min_off: Duration,
+}
+
+impl Default for ThermostatSettings {
+    fn default() -> Self {
+        ThermostatSettings {
+            enabled: false,
+            target: Temperature::celsius(26),
+            hysteresis: 0.5,
+            mode: ACMode::Cool,
+            min_on: Duration::from_secs(5 * 60),
+            min_off: Duration::from_secs(3 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThermostatAction {
+    Start,
+    Stop,
+}
+
+/// Decides when to start and stop the AC from room temperature readings
+#[derive(Clone, Debug, Default)]
+pub struct Thermostat {
+    settings: ThermostatSettings,
+    running: bool,
+    last_switch: Option<Instant>,
+    room_temperature: Option<f32>,
+}
+
+impl Thermostat {
+    pub fn settings(&self) -> &ThermostatSettings {
+        &self.settings
+    }
+
+    /// Replaces the settings, with `running` being whether the AC is currently powered
+    pub fn set_settings(
+        &mut self,
+        settings: ThermostatSettings,
+        running: bool,
+    ) -> Result<(), ThermostatError> {
+        match settings.mode {
+            ACMode::Cool | ACMode::Warm | ACMode::Dry => {}
+            mode => return Err(ThermostatError::Mode(mode)),
+        }
+        if settings.hysteresis < 0.0 {
+            return Err(ThermostatError::Hysteresis);
+        }
+        self.settings = settings;
+        self.running = running;
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn room_temperature(&self) -> Option<f32> {
+        self.room_temperature
+    }
+
+    /// Cooling starts once the room is `hysteresis` above the target and stops once it is back
+    /// at the target, and the reverse for warming, as long as the minimum times have passed.
+    /// `powered` is whether the AC is on now, as anything else may have switched it since.
+    pub fn update(
+        &mut self,
+        temperature: f32,
+        powered: bool,
+        now: Instant,
+    ) -> Option<ThermostatAction> {
+        self.room_temperature = Some(temperature);
+        self.running = powered;
+        if !self.settings.enabled {
+            return None;
+        }
+        let target = self
+            .settings
+            .target
+            .to_unit(TemperatureUnit::Celsius)
+            .degrees();
+        let hysteresis = self.settings.hysteresis;
+        let wants_running = match self.settings.mode {
+            ACMode::Warm if temperature < target - hysteresis => true,
+            ACMode::Warm if temperature >= target => false,
+            ACMode::Warm => return None,
+            _ if temperature > target + hysteresis => true,
+            _ if temperature <= target => false,
+            _ => return None,
+        };
+        if wants_running == self.running {
+            return None;
+        }
+        let min_time = if self.running {
+            self.settings.min_on
+        } else {
+            self.settings.min_off
+        };
+        if let Some(last_switch) = self.last_switch {
+            if now.duration_since(last_switch) < min_time {
+                trace!("thermostat waiting out minimum time before switching");
+                return None;
+            }
+        }
+        self.running = wants_running;
+        self.last_switch = Some(now);
+        Some(if wants_running {
+            ThermostatAction::Start
+        } else {
+            ThermostatAction::Stop
+        })
+    }
+}
+
+/// Feeds atmosphere readings to the home's thermostat and sends whatever it decides
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(home: Arc<HomeImpl<T>>)
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut readings = home.sensors.primary().subscribe();
+    while readings.changed().await.is_ok() {
+        let temperature = match &*readings.borrow() {
+            Ok(reading) => reading.temperature,
+            Err(e) => {
+                warn!("thermostat skipping failed reading: {}", e);
+                None
+            }
+        };
+        let temperature = match temperature {
+            Some(temperature) => temperature,
+            None => continue,
+        };
+        let powered = home.ir_out.lock().await.status().powered;
+        let (action, settings) = {
+            let mut thermostat = home.thermostat.lock().await;
+            (
+                thermostat.update(temperature, powered, Instant::now()),
+                thermostat.settings().clone(),
+            )
+        };
+        if let Some(action) = action {
+            info!("thermostat {:?} at {}°C", action, temperature);
+            if let Err(e) = apply(&home, action, &settings).await {
+                error!("thermostat could not control AC: {}", e);
+            }
+        }
+    }
+    info!("atmosphere stopped, stopping thermostat");
+}
+
+async fn apply<T: IrTarget + Debug + Send + Sync + 'static>(
+    home: &HomeImpl<T>,
+    action: ThermostatAction,
+    settings: &ThermostatSettings,
+) -> Result<(), ThermostatError>
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut ir_out = home.ir_out.lock().await;
+    let status = IrStatus {
+        powered: action == ThermostatAction::Start,
+        mode: settings.mode.clone(),
+        temperature: T::Temperature::try_from(settings.target)
+            .map_err(|e| ThermostatError::Target(e.to_string()))?,
+    };
+    ir_out
+        .send_status(status)
+        .map_err(|e| ThermostatError::Target(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thermostat(mode: ACMode) -> Thermostat {
+        let mut thermostat = Thermostat::default();
+        thermostat
+            .set_settings(
+                ThermostatSettings {
+                    enabled: true,
+                    mode,
+                    ..ThermostatSettings::default()
+                },
+                false,
+            )
+            .unwrap();
+        thermostat
+    }
+
+    /// Feeds one reading a minute, powering the AC as the thermostat says, returning the minutes
+    /// at which it did anything
+    fn simulate(thermostat: &mut Thermostat, temperatures: &[f32]) -> Vec<(u64, ThermostatAction)> {
+        let start = Instant::now();
+        let mut powered = thermostat.is_running();
+        temperatures
+            .iter()
+            .enumerate()
+            .filter_map(|(minute, temperature)| {
+                let minute = minute as u64;
+                let now = start + Duration::from_secs(minute * 60);
+                let action = thermostat.update(*temperature, powered, now)?;
+                powered = action == ThermostatAction::Start;
+                Some((minute, action))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cooling_follows_the_hysteresis() {
+        // target 26 with 0.5 of hysteresis
+        let temperatures = [26.3, 26.5, 26.6, 26.4, 26.2, 26.1, 26.1, 26.0];
+        let actions = simulate(&mut thermostat(ACMode::Cool), &temperatures);
+        assert_eq!(
+            actions,
+            vec![(2, ThermostatAction::Start), (7, ThermostatAction::Stop)]
+        );
+    }
+
+    #[test]
+    fn warming_is_the_reverse() {
+        let mut warm = thermostat(ACMode::Warm);
+        let temperatures = [25.8, 25.4, 25.9, 26.0, 26.3, 27.0];
+        let actions = simulate(&mut warm, &temperatures);
+        assert_eq!(actions, vec![(1, ThermostatAction::Start)]);
+        let later = Instant::now() + Duration::from_secs(60 * 60);
+        assert_eq!(warm.update(26.0, true, later), Some(ThermostatAction::Stop));
+    }
+
+    #[test]
+    fn waits_out_the_minimum_times() {
+        // starts at once, then the room cools straight back to the target
+        let mut temperatures = vec![27.0];
+        temperatures.extend([25.0; 6]);
+        // and warms again straight after stopping
+        temperatures.extend([27.0; 4]);
+        let actions = simulate(&mut thermostat(ACMode::Cool), &temperatures);
+        assert_eq!(
+            actions,
+            vec![
+                (0, ThermostatAction::Start),
+                (5, ThermostatAction::Stop),
+                (8, ThermostatAction::Start)
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_the_ac_being_switched_elsewhere() {
+        let mut thermostat = thermostat(ACMode::Cool);
+        let start = Instant::now();
+        assert_eq!(
+            thermostat.update(27.0, false, start),
+            Some(ThermostatAction::Start)
+        );
+        // turned off by hand while the room is still warm
+        let later = start + Duration::from_secs(30 * 60);
+        assert_eq!(
+            thermostat.update(27.0, false, later),
+            Some(ThermostatAction::Start)
+        );
+        // and turned on by hand once the room is cool
+        let later = later + Duration::from_secs(30 * 60);
+        assert_eq!(
+            thermostat.update(25.0, true, later),
+            Some(ThermostatAction::Stop)
+        );
+    }
+
+    #[test]
+    fn does_nothing_while_disabled() {
+        let mut thermostat = Thermostat::default();
+        assert_eq!(thermostat.update(35.0, false, Instant::now()), None);
+        assert_eq!(thermostat.room_temperature(), Some(35.0));
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let mut thermostat = Thermostat::default();
+        let fan = ThermostatSettings {
+            mode: ACMode::Fan,
+            ..ThermostatSettings::default()
+        };
+        assert!(matches!(
+            thermostat.set_settings(fan, false),
+            Err(ThermostatError::Mode(ACMode::Fan))
+        ));
+        let negative = ThermostatSettings {
+            hysteresis: -1.0,
+            ..ThermostatSettings::default()
+        };
+        assert!(matches!(
+            thermostat.set_settings(negative, false),
+            Err(ThermostatError::Hysteresis)
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::time::{interval, Duration, Instant};
+
+use crate::server::HomeImpl;
+use mattori_home_peripherals::ir::types::{IrStatus, IrTarget, Temperature};
+
+/// How often the server looks for timers it has to run itself
+const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Error, Clone, Debug)]
+pub enum TimerError {
+    #[error("Unknown timer {0}")]
+    UnknownTimer(u32),
+}
+
+/// Powers the AC on or off once `at` is reached
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AcTimer {
+    pub id: u32,
+    pub powered: bool,
+    pub at: Instant,
+}
+
+impl AcTimer {
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.at.saturating_duration_since(now)
+    }
+}
+
+/// Pending timers, with at most one for powering on and one for powering off like an AC remote
+#[derive(Debug, Default)]
+pub struct Timers {
+    timers: BTreeMap<u32, AcTimer>,
+    next_id: u32,
+}
+
+impl Timers {
+    /// Adds a timer, replacing any pending one for the same power state
+    pub fn set(&mut self, powered: bool, at: Instant) -> AcTimer {
+        self.timers.retain(|_, timer| timer.powered != powered);
+        self.next_id += 1;
+        let timer = AcTimer {
+            id: self.next_id,
+            powered,
+            at,
+        };
+        self.timers.insert(timer.id, timer.clone());
+        timer
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AcTimer> + '_ {
+        self.timers.values()
+    }
+
+    /// Clears a timer before it runs
+    pub fn cancel(&mut self, id: u32) -> Result<AcTimer, TimerError> {
+        self.timers.remove(&id).ok_or(TimerError::UnknownTimer(id))
+    }
+
+    /// Timers due by `now` in the order they fell due, removed as they are handed out
+    pub fn take_due(&mut self, now: Instant) -> Vec<AcTimer> {
+        let mut due = self
+            .timers
+            .values()
+            .filter(|timer| timer.at <= now)
+            .cloned()
+            .collect::<Vec<_>>();
+        self.timers.retain(|_, timer| timer.at > now);
+        due.sort_by_key(|timer| timer.at);
+        due
+    }
+}
+
+/// Powers the AC on or off for due timers, leaving the mode and temperature as they are
+pub async fn run<T: IrTarget + Debug + Send + Sync + 'static>(home: Arc<HomeImpl<T>>)
+where
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    let mut ticks = interval(TICK);
+    loop {
+        ticks.tick().await;
+        let due = home.timers.lock().await.take_due(Instant::now());
+        for timer in due {
+            info!(
+                "running timer {} to power {}",
+                timer.id,
+                power_name(timer.powered)
+            );
+            let mut ir_out = home.ir_out.lock().await;
+            let current = ir_out.status();
+            let res = ir_out.send_status(IrStatus {
+                powered: timer.powered,
+                ..current
+            });
+            if let Err(e) = res {
+                error!("timer {} could not control AC: {}", timer.id, e);
+            }
+        }
+    }
+}
+
+fn power_name(powered: bool) -> &'static str {
+    if powered {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_timers_for_the_same_power_state() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let off = timers.set(false, now + Duration::from_secs(10));
+        let on = timers.set(true, now + Duration::from_secs(5));
+        let later_off = timers.set(false, now + Duration::from_secs(20));
+        assert_eq!(timers.iter().count(), 2);
+        assert!(matches!(
+            timers.cancel(off.id),
+            Err(TimerError::UnknownTimer(_))
+        ));
+        assert!(timers.take_due(now + Duration::from_secs(4)).is_empty());
+        assert_eq!(
+            timers.take_due(now + Duration::from_secs(30)),
+            vec![on, later_off]
+        );
+        assert_eq!(timers.iter().count(), 0);
+    }
+}
@@ -1,70 +1,74 @@
-use std::sync::{mpsc, Mutex};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::sleep;
+use std::time::SystemTime;
 
 use thiserror::Error;
 use tokio::sync::watch;
 use tokio::task::spawn_blocking;
 use tokio::time::{Duration, Instant};
 
+use crate::atmosphere::chip::Chip;
+use crate::atmosphere::config::Bme280Config;
+use crate::atmosphere::forecast::{PressureHistory, PressureTrend};
+use crate::atmosphere::health::{HealthMonitor, SensorHealth};
+use crate::atmosphere::heat::HeatRisk;
+use crate::atmosphere::sensor::Sensor;
 use crate::atmosphere::types::{AtmoI2c, AtmoI2cError};
+use crate::atmosphere::user_calibration::UserCalibration;
+use crate::co2::Co2Error;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-mod calibration;
+pub mod bme680;
+pub mod calibration;
+pub mod chip;
+pub mod comfort;
 mod commands;
+pub mod compensation;
+pub mod config;
+pub mod forecast;
+pub mod health;
+pub mod heat;
+pub mod sea_level;
+pub mod sensor;
+pub mod sensors;
 mod types;
+pub mod user_calibration;
 
-const ATMOSPHERE_ADDR: u16 = 0x76;
+pub const ATMOSPHERE_ADDR: u16 = 0x76;
 
-const READ_RATE: Duration = Duration::from_secs(1);
+pub const READ_RATE: Duration = Duration::from_secs(1);
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Reading {
     pub temperature: Option<f32>,
     pub pressure: Option<f32>,
     pub humidity: Option<f32>,
     pub altitude: Option<f32>,
+    /// Estimated indoor WBGT in celsius, when both temperature and humidity were read
+    pub wbgt: Option<f32>,
+    pub dew_point: Option<f32>,
+    /// Grams of water vapour per cubic metre
+    pub absolute_humidity: Option<f32>,
+    pub discomfort_index: Option<f32>,
+    pub heat_index: Option<f32>,
+    pub apparent_temperature: Option<f32>,
+    /// Ohms across the BME680's heated metal oxide, which falls as volatile organic compounds
+    /// rise
+    pub gas_resistance: Option<f32>,
+    /// Parts per million, from a Sensirion SCD30 or SCD4x
+    pub co2: Option<f32>,
 }
 
 impl Display for Reading {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let any = [
-            self.temperature,
-            self.pressure,
-            self.humidity,
-            self.altitude,
-        ]
-        .iter()
-        .any(Option::is_some);
-        if any {
-            write!(f, "{{ ")?;
-        }
-        let mut has_prev = false;
-        if let Some(t) = self.temperature {
-            write!(f, "temperature: {}", t)?;
-            has_prev = true;
-        }
-        if let Some(p) = self.pressure {
-            if has_prev {
-                write!(f, ", ")?;
-            }
-            write!(f, "pressure: {}", p)?;
-            has_prev = true;
-        }
-        if let Some(h) = self.humidity {
-            if has_prev {
-                write!(f, ", ")?;
-            }
-            write!(f, "humidity: {}", h)?;
-            has_prev = true;
-        }
-        if let Some(a) = self.altitude {
-            if has_prev {
-                write!(f, ", ")?;
-            }
-            write!(f, "altitude: {}", a)?;
-        }
-        if any {
-            write!(f, " }}")?;
+        let fields = self
+            .fields()
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| format!("{}: {}", name, value)))
+            .collect::<Vec<_>>();
+        if !fields.is_empty() {
+            write!(f, "{{ {} }}", fields.join(", "))?;
         }
         Ok(())
     }
@@ -77,6 +81,70 @@ impl Reading {
             pressure: None,
             humidity: None,
             altitude: None,
+            wbgt: None,
+            dew_point: None,
+            absolute_humidity: None,
+            discomfort_index: None,
+            heat_index: None,
+            apparent_temperature: None,
+            gas_resistance: None,
+            co2: None,
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, Option<f32>); 12] {
+        [
+            ("temperature", self.temperature),
+            ("pressure", self.pressure),
+            ("humidity", self.humidity),
+            ("altitude", self.altitude),
+            ("wbgt", self.wbgt),
+            ("dew point", self.dew_point),
+            ("absolute humidity", self.absolute_humidity),
+            ("discomfort index", self.discomfort_index),
+            ("heat index", self.heat_index),
+            ("apparent temperature", self.apparent_temperature),
+            ("gas resistance", self.gas_resistance),
+            ("co2", self.co2),
+        ]
+    }
+
+    pub fn heat_risk(&self) -> Option<HeatRisk> {
+        self.wbgt.map(HeatRisk::from_wbgt)
+    }
+
+    /// Fills in whatever this reading lacks from another sensor's reading
+    pub fn fill_from(&mut self, other: &Reading) {
+        self.temperature = self.temperature.or(other.temperature);
+        self.pressure = self.pressure.or(other.pressure);
+        self.humidity = self.humidity.or(other.humidity);
+        self.altitude = self.altitude.or(other.altitude);
+        self.wbgt = self.wbgt.or(other.wbgt);
+        self.dew_point = self.dew_point.or(other.dew_point);
+        self.absolute_humidity = self.absolute_humidity.or(other.absolute_humidity);
+        self.discomfort_index = self.discomfort_index.or(other.discomfort_index);
+        self.heat_index = self.heat_index.or(other.heat_index);
+        self.apparent_temperature = self.apparent_temperature.or(other.apparent_temperature);
+        self.gas_resistance = self.gas_resistance.or(other.gas_resistance);
+        self.co2 = self.co2.or(other.co2);
+    }
+
+    /// Only the fields `features` asks for
+    pub fn filtered(&self, features: &AtmosphereFeatures) -> Reading {
+        let keep = |enabled: bool, value: Option<f32>| value.filter(|_| enabled);
+        Reading {
+            temperature: keep(features.temperature, self.temperature),
+            pressure: keep(features.pressure, self.pressure),
+            humidity: keep(features.humidity, self.humidity),
+            altitude: keep(features.altitude, self.altitude),
+            wbgt: keep(features.wbgt, self.wbgt),
+            dew_point: keep(features.dew_point, self.dew_point),
+            absolute_humidity: keep(features.absolute_humidity, self.absolute_humidity),
+            discomfort_index: keep(features.discomfort_index, self.discomfort_index),
+            heat_index: keep(features.heat_index, self.heat_index),
+            apparent_temperature: keep(features.apparent_temperature, self.apparent_temperature),
+            gas_resistance: keep(features.gas, self.gas_resistance),
+            co2: keep(features.co2, self.co2),
         }
     }
 }
@@ -87,6 +155,14 @@ pub struct AtmosphereFeatures {
     pub pressure: bool,
     pub humidity: bool,
     pub altitude: bool,
+    pub wbgt: bool,
+    pub dew_point: bool,
+    pub absolute_humidity: bool,
+    pub discomfort_index: bool,
+    pub heat_index: bool,
+    pub apparent_temperature: bool,
+    pub gas: bool,
+    pub co2: bool,
 }
 
 impl Default for AtmosphereFeatures {
@@ -96,13 +172,87 @@ impl Default for AtmosphereFeatures {
             pressure: true,
             humidity: true,
             altitude: true,
+            wbgt: true,
+            dew_point: true,
+            absolute_humidity: true,
+            discomfort_index: true,
+            heat_index: true,
+            apparent_temperature: true,
+            gas: true,
+            co2: true,
+        }
+    }
+}
+
+#[derive(Error, Clone, Debug)]
+#[error("Invalid atmosphere feature {0}, expected temperature, pressure, humidity, altitude, wbgt, dew_point, absolute_humidity, discomfort_index, heat_index, apparent_temperature, gas or co2")]
+pub struct ParseFeaturesError(String);
+
+impl FromStr for AtmosphereFeatures {
+    type Err = ParseFeaturesError;
+
+    /// Parses a comma separated list of the features to enable, such as `temperature,dew_point`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut features = AtmosphereFeatures::none();
+        for feature in s.split(',') {
+            let enabled = match feature.trim().to_lowercase().replace('-', "_").as_str() {
+                "temperature" => &mut features.temperature,
+                "pressure" => &mut features.pressure,
+                "humidity" => &mut features.humidity,
+                "altitude" => &mut features.altitude,
+                "wbgt" => &mut features.wbgt,
+                "dew_point" => &mut features.dew_point,
+                "absolute_humidity" => &mut features.absolute_humidity,
+                "discomfort_index" => &mut features.discomfort_index,
+                "heat_index" => &mut features.heat_index,
+                "apparent_temperature" => &mut features.apparent_temperature,
+                "gas" => &mut features.gas,
+                "co2" => &mut features.co2,
+                _ => return Err(ParseFeaturesError(feature.to_string())),
+            };
+            *enabled = true;
         }
+        Ok(features)
     }
 }
 
 impl AtmosphereFeatures {
+    pub fn none() -> Self {
+        Self {
+            temperature: false,
+            pressure: false,
+            humidity: false,
+            altitude: false,
+            wbgt: false,
+            dew_point: false,
+            absolute_humidity: false,
+            discomfort_index: false,
+            heat_index: false,
+            apparent_temperature: false,
+            gas: false,
+            co2: false,
+        }
+    }
+
+    /// Everything the chip can measure or derive
+    pub fn available(chip: Chip) -> Self {
+        let humidity = chip.has_humidity();
+        Self {
+            humidity,
+            wbgt: humidity,
+            dew_point: humidity,
+            absolute_humidity: humidity,
+            discomfort_index: humidity,
+            heat_index: humidity,
+            apparent_temperature: humidity,
+            gas: chip.has_gas(),
+            co2: false,
+            ..Self::default()
+        }
+    }
+
     pub fn temperature_enabled(&self) -> bool {
-        self.temperature || self.pressure_enabled() || self.humidity_enabled()
+        self.temperature || self.pressure_enabled() || self.humidity_enabled() || self.gas
     }
 
     pub fn pressure_enabled(&self) -> bool {
@@ -111,6 +261,12 @@ impl AtmosphereFeatures {
 
     pub fn humidity_enabled(&self) -> bool {
         self.humidity
+            || self.wbgt
+            || self.dew_point
+            || self.absolute_humidity
+            || self.discomfort_index
+            || self.heat_index
+            || self.apparent_temperature
     }
 
     pub fn altitude_enabled(&self) -> bool {
@@ -126,6 +282,9 @@ pub enum ReaderMessage {
     ChangeEnabled(AtmosphereFeatures),
     Recalibrate,
     ChangeSeaLevelPressure(f32),
+    ChangeConfig(Bme280Config),
+    ForceRecalibration(u16),
+    ChangeUserCalibration(UserCalibration),
     Stop,
 }
 
@@ -135,44 +294,80 @@ pub enum AtmosphereError {
     Internal(#[from] AtmoI2cError),
     #[error("Could not communicate with i2c thread")]
     Send,
-    #[error("Could not acquire message sender mutex")]
+    #[error("Could not acquire atmosphere mutex")]
     Mutex,
+    #[error(transparent)]
+    Co2(#[from] Co2Error),
+    #[error("{0} does not support {1}")]
+    Unsupported(String, &'static str),
 }
 
 pub type Result<T> = std::result::Result<T, AtmosphereError>;
 
 #[derive(Debug)]
 pub struct Atmosphere {
+    model: String,
+    available: AtmosphereFeatures,
     reading_receiver: watch::Receiver<Result<Reading>>,
     message_sender: Mutex<mpsc::Sender<ReaderMessage>>,
+    pressure_history: Arc<Mutex<PressureHistory>>,
+    health: Arc<Mutex<HealthMonitor>>,
 }
 
 impl Atmosphere {
     pub fn start(addr: u16) -> Result<Atmosphere> {
+        Self::start_with_rate(addr, READ_RATE)
+    }
+
+    /// Starts reading a Bosch sensor, reading at most once per `read_rate`
+    pub fn start_with_rate(addr: u16, read_rate: Duration) -> Result<Atmosphere> {
         let atmo_i2c = AtmoI2c::new(addr)?;
-        let (message_sender, message_receiver) = mpsc::channel();
-        let reading_receiver = Self::start_reading(atmo_i2c, message_receiver);
+        info!("found {} atmosphere sensor at {:#04x}", atmo_i2c.chip, addr);
+        Ok(Self::with_sensor(atmo_i2c, read_rate))
+    }
 
-        Ok(Atmosphere {
+    /// Reads any sensor on its own thread, at most once per `read_rate`
+    pub fn with_sensor<S: Sensor>(sensor: S, read_rate: Duration) -> Atmosphere {
+        let model = sensor.model();
+        let available = sensor.available_features();
+        let (message_sender, message_receiver) = mpsc::channel();
+        let pressure_history = Arc::new(Mutex::new(PressureHistory::default()));
+        let health = Arc::new(Mutex::new(HealthMonitor::default()));
+        let reading_receiver = Self::start_reading(
+            sensor,
+            read_rate,
+            message_receiver,
+            pressure_history.clone(),
+            health.clone(),
+        );
+
+        Atmosphere {
+            model,
+            available,
             reading_receiver,
             message_sender: Mutex::new(message_sender),
-        })
+            pressure_history,
+            health,
+        }
     }
 
     pub fn default_addr() -> Result<Self> {
         Self::start(ATMOSPHERE_ADDR)
     }
 
-    fn start_reading(
-        mut atmo_i2c: AtmoI2c,
+    fn start_reading<S: Sensor>(
+        mut sensor: S,
+        read_rate: Duration,
         message_receiver: mpsc::Receiver<ReaderMessage>,
+        pressure_history: Arc<Mutex<PressureHistory>>,
+        health: Arc<Mutex<HealthMonitor>>,
     ) -> watch::Receiver<Result<Reading>> {
         let (reading_sender, reading_receiver) = watch::channel(Ok(Reading::empty()));
 
         spawn_blocking(move || {
             let mut features = AtmosphereFeatures::default();
             let mut running = true;
-            let mut next_tick = Instant::now() + READ_RATE;
+            let mut next_tick = Instant::now() + read_rate;
             loop {
                 let now = Instant::now();
                 if now < next_tick {
@@ -181,7 +376,10 @@ impl Atmosphere {
                 } else {
                     info!("next tick already surpassed, might need to increase read rate");
                 }
-                next_tick += READ_RATE;
+                // when free-running, read as often as the sensor has new data, up to the read rate
+                next_tick += sensor
+                    .data_interval()
+                    .map_or(read_rate, |interval| interval.min(read_rate));
 
                 if reading_sender.receiver_count() <= 1 {
                     trace!("skipping due to no reading receivers");
@@ -218,11 +416,8 @@ impl Atmosphere {
                         }
                         Ok(ReaderMessage::Recalibrate) => {
                             info!("atmosphere thread recalibrating");
-                            if let Err(e) = atmo_i2c.reload_calibration() {
-                                if reading_sender
-                                    .send(Err(AtmosphereError::Internal(e)))
-                                    .is_err()
-                                {
+                            if let Err(e) = sensor.recalibrate() {
+                                if reading_sender.send(Err(e)).is_err() {
                                     error!("could not trigger recalibration in atmosphere i2c");
                                 }
                             }
@@ -232,12 +427,56 @@ impl Atmosphere {
                                 "atmosphere thread changing sea level pressure to {}",
                                 pressure
                             );
-                            atmo_i2c.set_sea_level_pressure(pressure);
+                            sensor.set_sea_level_pressure(pressure);
+                        }
+                        Ok(ReaderMessage::ChangeConfig(config)) => {
+                            info!("atmosphere thread applying new config: {:?}", config);
+                            if let Err(e) = sensor.set_config(config) {
+                                if reading_sender.send(Err(e)).is_err() {
+                                    error!("could not apply config to atmosphere i2c");
+                                }
+                            }
+                        }
+                        Ok(ReaderMessage::ChangeUserCalibration(calibration)) => {
+                            info!(
+                                "atmosphere thread applying user calibration: {:?}",
+                                calibration
+                            );
+                            sensor.set_user_calibration(calibration);
+                        }
+                        Ok(ReaderMessage::ForceRecalibration(reference)) => {
+                            info!(
+                                "atmosphere thread recalibrating against {} ppm CO2",
+                                reference
+                            );
+                            if let Err(e) = sensor.force_recalibration(reference) {
+                                if reading_sender.send(Err(e)).is_err() {
+                                    error!("could not force recalibration of atmosphere sensor");
+                                }
+                            }
                         }
                     }
                 }
 
-                let reading = Self::perform_reading(&mut atmo_i2c, running, &features);
+                let reading = if running {
+                    let reading = sensor.read(&features);
+                    Self::check_health(&mut sensor, &reading, &health);
+                    reading
+                } else {
+                    trace!("skip reading");
+                    Ok(Reading::empty())
+                };
+
+                if let Ok(Reading {
+                    pressure: Some(pressure),
+                    ..
+                }) = reading
+                {
+                    match pressure_history.lock() {
+                        Ok(mut history) => history.push(Instant::now(), pressure),
+                        Err(_) => error!("could not acquire pressure history mutex"),
+                    }
+                }
 
                 if reading_sender.send(reading).is_err() {
                     info!("sent to no reading receivers");
@@ -248,45 +487,70 @@ impl Atmosphere {
         reading_receiver
     }
 
-    fn perform_reading(
-        atmo_i2c: &mut AtmoI2c,
-        running: bool,
-        features: &AtmosphereFeatures,
-    ) -> Result<Reading> {
-        Ok(if running && features.temperature_enabled() {
-            trace!("running && temperature enabled");
-            let (temp_fine, temperature) = atmo_i2c.read_temperature()?;
-            trace!("read temperature: {:?} {:?}", temp_fine, temperature);
-
-            let pressure = features
-                .pressure_enabled()
-                .then(|| atmo_i2c.read_pressure(temp_fine))
-                .transpose()?;
-            trace!("read pressure: {:?}", pressure);
-
-            let humidity = features
-                .humidity_enabled()
-                .then(|| atmo_i2c.read_humidity(temp_fine))
-                .transpose()?;
-            trace!("read humidity: {:?}", humidity);
-
-            let altitude = pressure.and_then(|p| {
-                features
-                    .altitude_enabled()
-                    .then(|| atmo_i2c.read_altitude(p))
-            });
-            trace!("read altitude: {:?}", altitude);
-
-            Reading {
-                temperature: Some(temperature),
-                pressure,
-                humidity,
-                altitude,
+    /// Records how the read went, resetting and recalibrating the sensor once it has failed
+    /// enough times in a row
+    fn check_health<S: Sensor>(
+        sensor: &mut S,
+        reading: &Result<Reading>,
+        health: &Mutex<HealthMonitor>,
+    ) {
+        let mut health = match health.lock() {
+            Ok(health) => health,
+            Err(_) => {
+                error!("could not acquire sensor health mutex");
+                return;
             }
-        } else {
-            trace!("skip reading");
-            Reading::empty()
-        })
+        };
+        let e = match reading {
+            Ok(_) => {
+                health.succeeded(SystemTime::now());
+                return;
+            }
+            Err(e) => e,
+        };
+        if !health.failed(Instant::now(), e.to_string()) {
+            return;
+        }
+        warn!(
+            "{} failed {} reads in a row, resetting: {}",
+            sensor.model(),
+            health.health().consecutive_errors,
+            e
+        );
+        health.reset(Instant::now());
+        match sensor.reset().and_then(|_| sensor.recalibrate()) {
+            Ok(()) => info!("{} reset", sensor.model()),
+            Err(e) => error!("could not reset {}: {}", sensor.model(), e),
+        }
+    }
+
+    /// Model of the sensor, such as BME280
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Features the sensor supports, regardless of which are enabled
+    pub fn available_features(&self) -> AtmosphereFeatures {
+        self.available.clone()
+    }
+
+    /// How pressure changed over the last 3 hours, once there is at least an hour of readings
+    pub fn pressure_trend(&self) -> Result<Option<PressureTrend>> {
+        Ok(self
+            .pressure_history
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .trend())
+    }
+
+    /// How reads have been going, and how often the sensor had to be reset
+    pub fn health(&self) -> Result<SensorHealth> {
+        Ok(self
+            .health
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .health()
+            .clone())
     }
 
     pub fn subscribe(&self) -> watch::Receiver<Result<Reading>> {
@@ -325,6 +589,14 @@ impl Atmosphere {
             .map_err(|_| AtmosphereError::Send)
     }
 
+    pub fn change_features(&self, features: AtmosphereFeatures) -> Result<()> {
+        self.message_sender
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .send(ReaderMessage::ChangeEnabled(features))
+            .map_err(|_| AtmosphereError::Send)
+    }
+
     pub fn change_sea_level_pressure(&self, pressure: f32) -> Result<()> {
         self.message_sender
             .lock()
@@ -332,4 +604,64 @@ impl Atmosphere {
             .send(ReaderMessage::ChangeSeaLevelPressure(pressure))
             .map_err(|_| AtmosphereError::Send)
     }
+
+    pub fn change_config(&self, config: Bme280Config) -> Result<()> {
+        self.message_sender
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .send(ReaderMessage::ChangeConfig(config))
+            .map_err(|_| AtmosphereError::Send)
+    }
+
+    /// Corrects every following reading, such as for a sensor warmed by the Pi next to it
+    pub fn change_user_calibration(&self, calibration: UserCalibration) -> Result<()> {
+        self.message_sender
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .send(ReaderMessage::ChangeUserCalibration(calibration))
+            .map_err(|_| AtmosphereError::Send)
+    }
+
+    /// Recalibrates a CO2 sensor against the given concentration, which it should have been
+    /// measuring steadily for a few minutes
+    pub fn force_recalibration(&self, reference_ppm: u16) -> Result<()> {
+        self.message_sender
+            .lock()
+            .map_err(|_| AtmosphereError::Mutex)?
+            .send(ReaderMessage::ForceRecalibration(reference_ppm))
+            .map_err(|_| AtmosphereError::Send)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_readings_to_the_features() {
+        let reading = Reading {
+            temperature: Some(30.0),
+            humidity: Some(70.0),
+            wbgt: Some(27.5),
+            dew_point: Some(23.9),
+            gas_resistance: Some(120_000.0),
+            co2: Some(800.0),
+            ..Reading::empty()
+        };
+        let features = "temperature,dew_point,gas".parse().unwrap();
+        assert_eq!(
+            reading.filtered(&features),
+            Reading {
+                temperature: Some(30.0),
+                dew_point: Some(23.9),
+                gas_resistance: Some(120_000.0),
+                ..Reading::empty()
+            }
+        );
+        assert_eq!(reading.filtered(&AtmosphereFeatures::default()), reading);
+        assert_eq!(
+            reading.filtered(&AtmosphereFeatures::none()),
+            Reading::empty()
+        );
+    }
 }
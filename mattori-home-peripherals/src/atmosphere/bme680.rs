@@ -0,0 +1,214 @@
+use num_traits::clamp;
+use tokio::time::Duration;
+
+/// Uncompensated gas resistance measurement
+#[derive(Clone, Copy, Debug)]
+pub struct RawGas {
+    pub adc: u16,
+    pub range: u8,
+    pub valid: bool,
+    /// Whether the heater reached its target temperature in time
+    pub heat_stable: bool,
+}
+
+/// Hot plate settings for gas measurements
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Heater {
+    /// Celsius, at most 400
+    pub temperature: u16,
+    pub duration: Duration,
+}
+
+impl Default for Heater {
+    fn default() -> Self {
+        Heater {
+            temperature: 320,
+            duration: Duration::from_millis(150),
+        }
+    }
+}
+
+impl Heater {
+    /// Heating duration as the gas_wait register wants it, a 6 bit value with a multiplier of
+    /// 1, 4, 16 or 64 in the top two bits
+    pub fn wait_code(&self) -> u8 {
+        let mut millis = self.duration.as_millis();
+        if millis >= 0xfc0 {
+            return 0xff;
+        }
+        let mut factor = 0;
+        while millis > 0x3f {
+            millis /= 4;
+            factor += 1;
+        }
+        (millis + factor * 64) as u8
+    }
+}
+
+// switching error corrections by gas range
+const GAS_RANGE_K1: [f64; 16] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, -0.8, 0.0, 0.0, -0.2, -0.5, 0.0, -1.0, 0.0, 0.0,
+];
+const GAS_RANGE_K2: [f64; 16] = [
+    0.0, 0.0, 0.0, 0.0, 0.1, 0.7, 0.0, -0.8, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+];
+
+/// Calibration of the BME680, whose layout and compensation differ from the BME280
+pub struct Calibration {
+    pub t1: u16,
+    pub t2: i16,
+    pub t3: i8,
+    pub p1: u16,
+    pub p2: i16,
+    pub p3: i8,
+    pub p4: i16,
+    pub p5: i16,
+    pub p6: i8,
+    pub p7: i8,
+    pub p8: i16,
+    pub p9: i16,
+    pub p10: u8,
+    pub h1: u16,
+    pub h2: u16,
+    pub h3: i8,
+    pub h4: i8,
+    pub h5: i8,
+    pub h6: u8,
+    pub h7: i8,
+    pub gh1: i8,
+    pub gh2: i16,
+    pub gh3: i8,
+    pub res_heat_range: u8,
+    pub res_heat_val: i8,
+    pub range_sw_err: i8,
+}
+
+impl Calibration {
+    /// Parses the coefficient blocks starting at 0x8a, 0xe1 and 0x00
+    pub fn from_blocks(first: &[u8; 23], second: &[u8; 14], third: &[u8; 5]) -> Calibration {
+        let u16_at = |block: &[u8], lsb: usize| u16::from_le_bytes([block[lsb], block[lsb + 1]]);
+        let i16_at = |block: &[u8], lsb: usize| i16::from_le_bytes([block[lsb], block[lsb + 1]]);
+        Calibration {
+            t1: u16_at(second, 8),
+            t2: i16_at(first, 0),
+            t3: first[2] as i8,
+            p1: u16_at(first, 4),
+            p2: i16_at(first, 6),
+            p3: first[8] as i8,
+            p4: i16_at(first, 10),
+            p5: i16_at(first, 12),
+            p6: first[15] as i8,
+            p7: first[14] as i8,
+            p8: i16_at(first, 18),
+            p9: i16_at(first, 20),
+            p10: first[22],
+            // h1 and h2 share a byte, with h1 in its lower nibble
+            h1: ((second[2] as u16) << 4) | (second[1] & 0x0f) as u16,
+            h2: ((second[0] as u16) << 4) | (second[1] >> 4) as u16,
+            h3: second[3] as i8,
+            h4: second[4] as i8,
+            h5: second[5] as i8,
+            h6: second[6],
+            h7: second[7] as i8,
+            gh1: second[12] as i8,
+            gh2: i16_at(second, 10),
+            gh3: second[13] as i8,
+            res_heat_range: (third[2] & 0x30) >> 4,
+            res_heat_val: third[0] as i8,
+            range_sw_err: (third[4] as i8) >> 4,
+        }
+    }
+
+    /// Returns the fine temperature and celsius
+    pub fn temperature(&self, adc: u32) -> (i32, f32) {
+        let adc = adc as f64;
+        let (t1, t2, t3) = (self.t1 as f64, self.t2 as f64, self.t3 as f64);
+        let var1 = (adc / 16384.0 - t1 / 1024.0) * t2;
+        let var2 = (adc / 131072.0 - t1 / 8192.0) * (adc / 131072.0 - t1 / 8192.0) * (t3 * 16.0);
+        ((var1 + var2) as i32, ((var1 + var2) / 5120.0) as f32)
+    }
+
+    /// Pressure in hPa, or `None` if the calibration would divide by zero
+    pub fn pressure(&self, adc: u32, t_fine: i32) -> Option<f32> {
+        let (p1, p2, p3, p4, p5) = (
+            self.p1 as f64,
+            self.p2 as f64,
+            self.p3 as f64,
+            self.p4 as f64,
+            self.p5 as f64,
+        );
+        let (p6, p7, p8, p9, p10) = (
+            self.p6 as f64,
+            self.p7 as f64,
+            self.p8 as f64,
+            self.p9 as f64,
+            self.p10 as f64,
+        );
+        let var1 = t_fine as f64 / 2.0 - 64000.0;
+        let var2 = var1 * var1 * (p6 / 131072.0);
+        let var2 = var2 + var1 * p5 * 2.0;
+        let var2 = var2 / 4.0 + p4 * 65536.0;
+        let var1 = (p3 * var1 * var1 / 16384.0 + p2 * var1) / 524288.0;
+        let var1 = (1.0 + var1 / 32768.0) * p1;
+        if var1 == 0.0 {
+            return None;
+        }
+        let p = 1048576.0 - adc as f64;
+        let p = (p - var2 / 4096.0) * 6250.0 / var1;
+        let var1 = p9 * p * p / 2147483648.0;
+        let var2 = p * (p8 / 32768.0);
+        let var3 = (p / 256.0) * (p / 256.0) * (p / 256.0) * (p10 / 131072.0);
+        let p = p + (var1 + var2 + var3 + p7 * 128.0) / 16.0;
+        Some((p / 100.0) as f32)
+    }
+
+    /// Relative humidity in percent
+    pub fn humidity(&self, adc: u16, t_fine: i32) -> f32 {
+        let (h1, h2, h3, h4) = (
+            self.h1 as f64,
+            self.h2 as f64,
+            self.h3 as f64,
+            self.h4 as f64,
+        );
+        let (h5, h6, h7) = (self.h5 as f64, self.h6 as f64, self.h7 as f64);
+        let temperature = t_fine as f64 / 5120.0;
+        let var1 = adc as f64 - (h1 * 16.0 + h3 / 2.0 * temperature);
+        let var2 = var1
+            * (h2 / 262144.0
+                * (1.0 + h4 / 16384.0 * temperature + h5 / 1048576.0 * temperature * temperature));
+        let var3 = h6 / 16384.0;
+        let var4 = h7 / 2097152.0;
+        let humidity = var2 + (var3 + var4 * temperature) * var2 * var2;
+        clamp(humidity, 0.0, 100.0) as f32
+    }
+
+    /// Gas resistance in ohms, or `None` if the measurement isn't usable
+    pub fn gas_resistance(&self, gas: &RawGas) -> Option<f32> {
+        if !gas.valid || !gas.heat_stable {
+            return None;
+        }
+        let range = (gas.range & 0x0f) as usize;
+        let var1 = 1340.0 + 5.0 * self.range_sw_err as f64;
+        let var2 = var1 * (1.0 + GAS_RANGE_K1[range] / 100.0);
+        let var3 = 1.0 + GAS_RANGE_K2[range] / 100.0;
+        let resistance = 1.0
+            / (var3 * 1.25e-7 * (1u32 << range) as f64 * ((gas.adc as f64 - 512.0) / var2 + 1.0));
+        Some(resistance as f32)
+    }
+
+    /// Value for res_heat to reach the heater's temperature from the ambient temperature
+    pub fn heater_resistance(&self, heater: &Heater, ambient: f32) -> u8 {
+        let target = heater.temperature.min(400) as f64;
+        let var1 = self.gh1 as f64 / 16.0 + 49.0;
+        let var2 = self.gh2 as f64 / 32768.0 * 0.0005 + 0.00235;
+        let var3 = self.gh3 as f64 / 1024.0;
+        let var4 = var1 * (1.0 + var2 * target);
+        let var5 = var4 + var3 * ambient as f64;
+        let resistance = 3.4
+            * (var5
+                * (4.0 / (4.0 + self.res_heat_range as f64))
+                * (1.0 / (1.0 + self.res_heat_val as f64 * 0.002))
+                - 25.0);
+        clamp(resistance, 0.0, 255.0) as u8
+    }
+}
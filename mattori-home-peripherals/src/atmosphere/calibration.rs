@@ -6,6 +6,8 @@ use packed_struct::prelude::*;
 use packed_struct::PackedStructInfo;
 use rppal::i2c::I2c;
 
+use crate::atmosphere::bme680;
+use crate::atmosphere::chip::Chip;
 use crate::atmosphere::types::{
     AtmoI2c, AtmoI2cBaseError, AtmoI2cError, AtmoI2cRawReadingType, BaseResult, Register, Result,
 };
@@ -69,11 +71,35 @@ impl Humidity {
 pub struct Calibration {
     pub temperature: Temperature,
     pub pressure: Pressure,
-    pub humidity: Humidity,
+    /// `None` on the BMP280
+    pub humidity: Option<Humidity>,
+}
+
+/// First bytes of a register read, for blocks that aren't unpacked as a whole
+pub(crate) fn prefix<const N: usize>(buf: [u8; 32]) -> [u8; N] {
+    let mut block = [0; N];
+    block.copy_from_slice(&buf[..N]);
+    block
+}
+
+pub enum ChipCalibration {
+    /// Also used for the BMP280, which shares its temperature and pressure layout
+    Bme280(Calibration),
+    Bme680(bme680::Calibration),
 }
 
 impl AtmoI2c {
-    pub fn read_calibration(guard: &MutexGuard<I2c>) -> Result<Calibration> {
+    pub fn read_calibration(guard: &MutexGuard<I2c>, chip: Chip) -> Result<ChipCalibration> {
+        match chip {
+            Chip::Bmp280 => {
+                Self::read_bme280_calibration(guard, false).map(ChipCalibration::Bme280)
+            }
+            Chip::Bme280 => Self::read_bme280_calibration(guard, true).map(ChipCalibration::Bme280),
+            Chip::Bme680 => Self::read_bme680_calibration(guard).map(ChipCalibration::Bme680),
+        }
+    }
+
+    fn read_bme280_calibration(guard: &MutexGuard<I2c>, humidity: bool) -> Result<Calibration> {
         let (temperature, pressure) = Self::read_register_from(
             guard,
             Register::DigT1,
@@ -98,6 +124,13 @@ impl AtmoI2c {
         )
         .and_then(convert::identity)
         .map_err(AtmoI2cError::Calibration)?;
+        if !humidity {
+            return Ok(Calibration {
+                temperature,
+                pressure,
+                humidity: None,
+            });
+        }
         let humidity_h1 =
             Self::read_byte_from(guard, Register::DigH1).map_err(AtmoI2cError::Calibration)?;
         let packed_humidity = Self::read_register_from(guard, Register::DigH2, |buf| {
@@ -115,13 +148,25 @@ impl AtmoI2c {
         Ok(Calibration {
             temperature,
             pressure,
-            humidity,
+            humidity: Some(humidity),
         })
     }
 
+    fn read_bme680_calibration(guard: &MutexGuard<I2c>) -> Result<bme680::Calibration> {
+        let first = Self::read_register_from(guard, Register::Bme680Coefficients1, prefix::<23>)
+            .map_err(AtmoI2cError::Calibration)?;
+        let second = Self::read_register_from(guard, Register::Bme680Coefficients2, prefix::<14>)
+            .map_err(AtmoI2cError::Calibration)?;
+        let third = Self::read_register_from(guard, Register::Bme680Coefficients3, prefix::<5>)
+            .map_err(AtmoI2cError::Calibration)?;
+        Ok(bme680::Calibration::from_blocks(&first, &second, &third))
+    }
+
     pub fn reload_calibration(&mut self) -> Result<()> {
-        let calibration =
-            Self::read_calibration(&self.lock_i2c().map_err(AtmoI2cError::Calibration)?)?;
+        let calibration = Self::read_calibration(
+            &self.lock_i2c().map_err(AtmoI2cError::Calibration)?,
+            self.chip,
+        )?;
         self.calibration = calibration;
         Ok(())
     }
@@ -0,0 +1,45 @@
+use std::fmt::{Display, Formatter};
+
+/// Bosch sensors sharing the BME280's i2c addresses, told apart by their chip id
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Chip {
+    /// Temperature and pressure only
+    Bmp280,
+    Bme280,
+    /// Adds a heated gas resistance sensor, but can't free-run in normal mode
+    Bme680,
+}
+
+impl Chip {
+    pub fn from_id(id: u8) -> Option<Chip> {
+        match id {
+            // 0x56 and 0x57 are engineering samples
+            0x56..=0x58 => Some(Chip::Bmp280),
+            0x60 => Some(Chip::Bme280),
+            0x61 => Some(Chip::Bme680),
+            _ => None,
+        }
+    }
+
+    pub fn has_humidity(&self) -> bool {
+        *self != Chip::Bmp280
+    }
+
+    pub fn has_gas(&self) -> bool {
+        *self == Chip::Bme680
+    }
+
+    pub fn has_normal_mode(&self) -> bool {
+        *self != Chip::Bme680
+    }
+}
+
+impl Display for Chip {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip::Bmp280 => write!(f, "BMP280"),
+            Chip::Bme280 => write!(f, "BME280"),
+            Chip::Bme680 => write!(f, "BME680"),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/// Dew point in celsius from temperature in celsius and relative humidity in percent, by the
+/// Magnus formula
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    const A: f32 = 17.62;
+    const B: f32 = 243.12;
+    let gamma = (humidity.max(0.01) / 100.0).ln() + A * temperature / (B + temperature);
+    B * gamma / (A - gamma)
+}
+
+/// Saturation vapour pressure of water in hPa at a temperature in celsius
+fn saturation_pressure(temperature: f32) -> f32 {
+    6.112 * (17.67 * temperature / (temperature + 243.5)).exp()
+}
+
+/// Grams of water vapour per cubic metre of air
+pub fn absolute_humidity(temperature: f32, humidity: f32) -> f32 {
+    saturation_pressure(temperature) * humidity * 2.1674 / (273.15 + temperature)
+}
+
+/// 不快指数, where around 60 to 75 is comfortable and above 80 is unbearable for most
+pub fn discomfort_index(temperature: f32, humidity: f32) -> f32 {
+    0.81 * temperature + 0.01 * humidity * (0.99 * temperature - 14.3) + 46.3
+}
+
+/// Heat index in celsius following the US National Weather Service, which only differs from the
+/// temperature once it is warm
+pub fn heat_index(temperature: f32, humidity: f32) -> f32 {
+    // in f64 as the regression's coefficients are more precise than f32
+    let t = temperature as f64 * 9.0 / 5.0 + 32.0;
+    let rh = humidity as f64;
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let fahrenheit = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let regression = -42.379 + 2.04901523 * t + 10.14333127 * rh
+            - 0.22475541 * t * rh
+            - 0.00683783 * t * t
+            - 0.05481717 * rh * rh
+            + 0.00122874 * t * t * rh
+            + 0.00085282 * t * rh * rh
+            - 0.00000199 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            regression - (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt()
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            regression + (rh - 85.0) / 10.0 * (87.0 - t) / 5.0
+        } else {
+            regression
+        }
+    };
+    ((fahrenheit - 32.0) * 5.0 / 9.0) as f32
+}
+
+/// Apparent temperature in celsius by Steadman's formula as used by the Australian Bureau of
+/// Meteorology, taking the air indoors as still
+pub fn apparent_temperature(temperature: f32, humidity: f32) -> f32 {
+    let vapour_pressure =
+        humidity / 100.0 * 6.105 * (17.27 * temperature / (237.7 + temperature)).exp();
+    temperature + 0.33 * vapour_pressure - 4.0
+}
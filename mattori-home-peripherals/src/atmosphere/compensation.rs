@@ -0,0 +1,358 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use num_traits::clamp;
+use thiserror::Error;
+
+use crate::atmosphere::calibration::{Humidity, Pressure, Temperature};
+
+/// Which of the datasheet's routines turn raw measurements into readings. All of them share the
+/// fine temperature, which carries the temperature into pressure and humidity compensation.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Compensation {
+    /// Double precision floating point
+    Float,
+    /// 32 bit integers, giving pressure in whole pascals and a few pascals off the others
+    Integer32,
+    /// 32 bit integers, but with 64 bit pressure compensation, as recommended by the datasheet
+    #[default]
+    Integer64,
+}
+
+impl Compensation {
+    /// Returns the fine temperature and celsius
+    pub fn temperature(&self, calibration: &Temperature, adc: u32) -> (i32, f32) {
+        match self {
+            Compensation::Float => float_temperature(calibration, adc),
+            Compensation::Integer32 | Compensation::Integer64 => {
+                let (t_fine, centi_celsius) = int_temperature(calibration, adc);
+                (t_fine, centi_celsius as f32 / 100.0)
+            }
+        }
+    }
+
+    /// Pressure in hPa, or `None` if the calibration would divide by zero
+    pub fn pressure(&self, calibration: &Pressure, adc: u32, t_fine: i32) -> Option<f32> {
+        match self {
+            Compensation::Float => float_pressure(calibration, adc, t_fine).map(|pa| pa / 100.0),
+            Compensation::Integer32 => {
+                int32_pressure(calibration, adc, t_fine).map(|pa| (pa as f64 / 100.0) as f32)
+            }
+            Compensation::Integer64 => int64_pressure(calibration, adc, t_fine)
+                .map(|q24_8| (q24_8 as f64 / 25600.0) as f32),
+        }
+    }
+
+    /// Relative humidity in percent
+    pub fn humidity(&self, calibration: &Humidity, adc: u16, t_fine: i32) -> f32 {
+        match self {
+            Compensation::Float => float_humidity(calibration, adc, t_fine),
+            Compensation::Integer32 | Compensation::Integer64 => {
+                int_humidity(calibration, adc, t_fine) as f32 / 1024.0
+            }
+        }
+    }
+}
+
+fn float_temperature(calibration: &Temperature, adc: u32) -> (i32, f32) {
+    let adc = adc as f64;
+    let (t1, t2, t3) = (
+        calibration.a as f64,
+        calibration.b as f64,
+        calibration.c as f64,
+    );
+    let var1 = (adc / 16384.0 - t1 / 1024.0) * t2;
+    let var2 = (adc / 131072.0 - t1 / 8192.0) * (adc / 131072.0 - t1 / 8192.0) * t3;
+    ((var1 + var2) as i32, ((var1 + var2) / 5120.0) as f32)
+}
+
+/// Returns pascals
+fn float_pressure(calibration: &Pressure, adc: u32, t_fine: i32) -> Option<f32> {
+    let (p1, p2, p3, p4, p5, p6, p7, p8, p9) = (
+        calibration.a as f64,
+        calibration.b as f64,
+        calibration.c as f64,
+        calibration.d as f64,
+        calibration.e as f64,
+        calibration.f as f64,
+        calibration.g as f64,
+        calibration.h as f64,
+        calibration.i as f64,
+    );
+    let var1 = t_fine as f64 / 2.0 - 64000.0;
+    let var2 = var1 * var1 * p6 / 32768.0;
+    let var2 = var2 + var1 * p5 * 2.0;
+    let var2 = var2 / 4.0 + p4 * 65536.0;
+    let var1 = (p3 * var1 * var1 / 524288.0 + p2 * var1) / 524288.0;
+    let var1 = (1.0 + var1 / 32768.0) * p1;
+    if var1 == 0.0 {
+        return None;
+    }
+    let p = 1048576.0 - adc as f64;
+    let p = (p - var2 / 4096.0) * 6250.0 / var1;
+    let var1 = p9 * p * p / 2147483648.0;
+    let var2 = p * p8 / 32768.0;
+    Some((p + (var1 + var2 + p7) / 16.0) as f32)
+}
+
+fn float_humidity(calibration: &Humidity, adc: u16, t_fine: i32) -> f32 {
+    let (h1, h2, h3, h4, h5, h6) = (
+        calibration.a as f64,
+        calibration.b as f64,
+        calibration.c as f64,
+        calibration.d as f64,
+        calibration.e as f64,
+        calibration.f as f64,
+    );
+    let var = t_fine as f64 - 76800.0;
+    let var = (adc as f64 - (h4 * 64.0 + h5 / 16384.0 * var))
+        * (h2 / 65536.0 * (1.0 + h6 / 67108864.0 * var * (1.0 + h3 / 67108864.0 * var)));
+    let humidity = var * (1.0 - h1 * var / 524288.0);
+    clamp(humidity, 0.0, 100.0) as f32
+}
+
+// temperature and humidity are computed in i64 so out of range measurements can't overflow, which
+// otherwise gives the same results as the datasheet's 32 bit arithmetic
+
+/// Returns the fine temperature and hundredths of a degree celsius
+fn int_temperature(calibration: &Temperature, adc: u32) -> (i32, i32) {
+    let adc = adc as i64;
+    let (t1, t2, t3) = (
+        calibration.a as i64,
+        calibration.b as i64,
+        calibration.c as i64,
+    );
+    let var1 = (((adc >> 3) - (t1 << 1)) * t2) >> 11;
+    let var2 = (((((adc >> 4) - t1) * ((adc >> 4) - t1)) >> 12) * t3) >> 14;
+    let t_fine = var1 + var2;
+    (t_fine as i32, ((t_fine * 5 + 128) >> 8) as i32)
+}
+
+/// Returns whole pascals
+fn int32_pressure(calibration: &Pressure, adc: u32, t_fine: i32) -> Option<u32> {
+    let (p1, p2, p3, p4, p5, p6, p7, p8, p9) = (
+        calibration.a as i32,
+        calibration.b as i32,
+        calibration.c as i32,
+        calibration.d as i32,
+        calibration.e as i32,
+        calibration.f as i32,
+        calibration.g as i32,
+        calibration.h as i32,
+        calibration.i as i32,
+    );
+    let var1 = (t_fine >> 1) - 64000;
+    let var2 = (((var1 >> 2) * (var1 >> 2)) >> 11) * p6;
+    let var2 = var2 + ((var1 * p5) << 1);
+    let var2 = (var2 >> 2) + (p4 << 16);
+    let var1 = (((p3 * (((var1 >> 2) * (var1 >> 2)) >> 13)) >> 3) + ((p2 * var1) >> 1)) >> 18;
+    let var1 = ((32768 + var1) * p1) >> 15;
+    if var1 == 0 {
+        return None;
+    }
+    // unsigned wrapping as in the datasheet, where an intermediate may exceed i32
+    let p = (1048576i32.wrapping_sub(adc as i32) as u32)
+        .wrapping_sub((var2 >> 12) as u32)
+        .wrapping_mul(3125);
+    let p = if p < 0x80000000 {
+        (p << 1) / var1 as u32
+    } else {
+        (p / var1 as u32) * 2
+    };
+    let var1 = (p9 * ((((p >> 3) * (p >> 3)) >> 13) as i32)) >> 12;
+    let var2 = (((p >> 2) as i32) * p8) >> 13;
+    Some((p as i32 + ((var1 + var2 + p7) >> 4)) as u32)
+}
+
+/// Returns pascals as Q24.8, so in 1/256 Pa
+fn int64_pressure(calibration: &Pressure, adc: u32, t_fine: i32) -> Option<u32> {
+    let (p1, p2, p3, p4, p5, p6, p7, p8, p9) = (
+        calibration.a as i64,
+        calibration.b as i64,
+        calibration.c as i64,
+        calibration.d as i64,
+        calibration.e as i64,
+        calibration.f as i64,
+        calibration.g as i64,
+        calibration.h as i64,
+        calibration.i as i64,
+    );
+    let var1 = t_fine as i64 - 128000;
+    let var2 = var1 * var1 * p6;
+    let var2 = var2 + ((var1 * p5) << 17);
+    let var2 = var2 + (p4 << 35);
+    let var1 = ((var1 * var1 * p3) >> 8) + ((var1 * p2) << 12);
+    let var1 = (((1i64 << 47) + var1) * p1) >> 33;
+    if var1 == 0 {
+        return None;
+    }
+    let p = 1048576 - adc as i64;
+    let p = (((p << 31) - var2) * 3125) / var1;
+    let var1 = (p9 * (p >> 13) * (p >> 13)) >> 25;
+    let var2 = (p8 * p) >> 19;
+    Some((((p + var1 + var2) >> 8) + (p7 << 4)) as u32)
+}
+
+/// Returns relative humidity as Q22.10, so in 1/1024 %
+fn int_humidity(calibration: &Humidity, adc: u16, t_fine: i32) -> u32 {
+    let (h1, h2, h3, h4, h5, h6) = (
+        calibration.a as i64,
+        calibration.b as i64,
+        calibration.c as i64,
+        calibration.d as i64,
+        calibration.e as i64,
+        calibration.f as i64,
+    );
+    let adc = adc as i64;
+    let v = t_fine as i64 - 76800;
+    let v = ((((adc << 14) - (h4 << 20) - (h5 * v)) + 16384) >> 15)
+        * (((((((v * h6) >> 10) * (((v * h3) >> 11) + 32768)) >> 10) + 2097152) * h2 + 8192) >> 14);
+    let v = v - (((((v >> 15) * (v >> 15)) >> 7) * h1) >> 4);
+    (clamp(v, 0, 419430400) >> 12) as u32
+}
+
+#[derive(Error, Clone, Debug)]
+#[error("Unknown compensation {0}, expected float, int32 or int64")]
+pub struct ParseCompensationError(String);
+
+impl FromStr for Compensation {
+    type Err = ParseCompensationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "float" => Ok(Compensation::Float),
+            "int32" => Ok(Compensation::Integer32),
+            "int64" => Ok(Compensation::Integer64),
+            _ => Err(ParseCompensationError(s.to_string())),
+        }
+    }
+}
+
+impl Display for Compensation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compensation::Float => write!(f, "float"),
+            Compensation::Integer32 => write!(f, "int32"),
+            Compensation::Integer64 => write!(f, "int64"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Compensation; 3] = [
+        Compensation::Float,
+        Compensation::Integer32,
+        Compensation::Integer64,
+    ];
+
+    // worked example from Bosch's BMP280 datasheet, whose temperature and pressure routines the
+    // BME280 shares
+    const ADC_T: u32 = 519888;
+    const ADC_P: u32 = 415148;
+    const T_FINE: i32 = 128422;
+
+    fn temperature() -> Temperature {
+        Temperature {
+            a: 27504,
+            b: 26435,
+            c: -1000,
+        }
+    }
+
+    fn pressure() -> Pressure {
+        Pressure {
+            a: 36477,
+            b: -10685,
+            c: 3024,
+            d: 2855,
+            e: 140,
+            f: -7,
+            g: 15500,
+            h: -14600,
+            i: 6000,
+        }
+    }
+
+    #[test]
+    fn temperature_matches_the_datasheet() {
+        for compensation in ALL {
+            let (t_fine, celsius) = compensation.temperature(&temperature(), ADC_T);
+            assert_eq!(t_fine, T_FINE, "{}", compensation);
+            assert!(
+                (celsius - 25.08).abs() < 0.005,
+                "{} {}",
+                compensation,
+                celsius
+            );
+        }
+    }
+
+    #[test]
+    fn pressure_matches_the_datasheet() {
+        let float = Compensation::Float
+            .pressure(&pressure(), ADC_P, T_FINE)
+            .unwrap();
+        assert!((float - 1006.5327).abs() < 0.0005, "{}", float);
+        // the datasheet prints 25767236 / 256 Pa, but its own routine gives 25767233 for these
+        let int64 = Compensation::Integer64
+            .pressure(&pressure(), ADC_P, T_FINE)
+            .unwrap();
+        assert_eq!(int64, (25767233.0f64 / 25600.0) as f32);
+        // within the few pascals the 32 bit routine is known to be off by
+        let int32 = Compensation::Integer32
+            .pressure(&pressure(), ADC_P, T_FINE)
+            .unwrap();
+        assert_eq!(int32, 1006.56);
+    }
+
+    #[test]
+    fn pressure_without_calibration_is_none() {
+        let uncalibrated = Pressure { a: 0, ..pressure() };
+        for compensation in ALL {
+            assert_eq!(
+                compensation.pressure(&uncalibrated, ADC_P, T_FINE),
+                None,
+                "{}",
+                compensation
+            );
+        }
+    }
+
+    #[test]
+    fn humidity_agrees_and_is_clamped() {
+        // calibration of a real sensor
+        let calibration = Humidity {
+            a: 75,
+            b: 362,
+            c: 0,
+            d: 313,
+            e: 50,
+            f: 30,
+        };
+        for adc in [20000, 27000, 32000, 40000] {
+            let float = Compensation::Float.humidity(&calibration, adc, T_FINE);
+            for compensation in [Compensation::Integer32, Compensation::Integer64] {
+                let int = compensation.humidity(&calibration, adc, T_FINE);
+                assert!((float - int).abs() < 0.05, "{} {} {}", adc, float, int);
+            }
+        }
+        for compensation in ALL {
+            assert_eq!(compensation.humidity(&calibration, 0, T_FINE), 0.0);
+            assert_eq!(compensation.humidity(&calibration, u16::MAX, T_FINE), 100.0);
+        }
+    }
+
+    #[test]
+    fn parses_and_displays() {
+        for compensation in ALL {
+            assert_eq!(
+                compensation.to_string().parse::<Compensation>().unwrap(),
+                compensation
+            );
+        }
+        assert!("double".parse::<Compensation>().is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::str::FromStr;
+
+use thiserror::Error;
+use tokio::time::Duration;
+
+use crate::atmosphere::compensation::Compensation;
+
+/// How many samples are averaged for a channel, where skipped channels aren't measured at all
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Overscan {
+    Skip,
+    X1,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+impl Overscan {
+    pub fn samples(&self) -> u8 {
+        match self {
+            Overscan::Skip => 0,
+            Overscan::X1 => 1,
+            Overscan::X2 => 2,
+            Overscan::X4 => 4,
+            Overscan::X8 => 8,
+            Overscan::X16 => 16,
+        }
+    }
+}
+
+impl From<Overscan> for u8 {
+    fn from(o: Overscan) -> Self {
+        match o {
+            Overscan::Skip => 0x00,
+            Overscan::X1 => 0x01,
+            Overscan::X2 => 0x02,
+            Overscan::X4 => 0x03,
+            Overscan::X8 => 0x04,
+            Overscan::X16 => 0x05,
+        }
+    }
+}
+
+/// IIR filter coefficient smoothing temperature and pressure against short disturbances
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Filter {
+    Off,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+impl From<Filter> for u8 {
+    fn from(f: Filter) -> Self {
+        match f {
+            Filter::Off => 0x00,
+            Filter::X2 => 0x01,
+            Filter::X4 => 0x02,
+            Filter::X8 => 0x03,
+            Filter::X16 => 0x04,
+        }
+    }
+}
+
+/// Time the sensor rests between measurements in normal mode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Standby {
+    Ms0_5,
+    Ms10,
+    Ms20,
+    Ms62_5,
+    Ms125,
+    Ms250,
+    Ms500,
+    Ms1000,
+}
+
+impl Standby {
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(match self {
+            Standby::Ms0_5 => 500,
+            Standby::Ms10 => 10_000,
+            Standby::Ms20 => 20_000,
+            Standby::Ms62_5 => 62_500,
+            Standby::Ms125 => 125_000,
+            Standby::Ms250 => 250_000,
+            Standby::Ms500 => 500_000,
+            Standby::Ms1000 => 1_000_000,
+        })
+    }
+}
+
+impl From<Standby> for u8 {
+    fn from(s: Standby) -> Self {
+        match s {
+            Standby::Ms0_5 => 0x00,
+            Standby::Ms62_5 => 0x01,
+            Standby::Ms125 => 0x02,
+            Standby::Ms250 => 0x03,
+            Standby::Ms500 => 0x04,
+            Standby::Ms1000 => 0x05,
+            Standby::Ms10 => 0x06,
+            Standby::Ms20 => 0x07,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SensorMode {
+    /// A single measurement is taken whenever a reading is made, sleeping in between
+    Forced,
+    /// The sensor measures continuously, resting for the standby time between measurements
+    Normal,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Bme280Config {
+    pub mode: SensorMode,
+    pub temperature: Overscan,
+    pub pressure: Overscan,
+    pub humidity: Overscan,
+    pub filter: Filter,
+    pub standby: Standby,
+    /// Not a setting of the sensor itself, but how its measurements are compensated
+    pub compensation: Compensation,
+}
+
+impl Default for Bme280Config {
+    fn default() -> Self {
+        Bme280Config {
+            mode: SensorMode::Normal,
+            temperature: Overscan::X1,
+            pressure: Overscan::X16,
+            humidity: Overscan::X1,
+            filter: Filter::Off,
+            standby: Standby::Ms1000,
+            compensation: Compensation::default(),
+        }
+    }
+}
+
+impl Bme280Config {
+    /// Datasheet's recommendation for weather monitoring, with the lowest power use
+    pub fn weather_monitoring() -> Self {
+        Bme280Config {
+            mode: SensorMode::Forced,
+            temperature: Overscan::X1,
+            pressure: Overscan::X1,
+            humidity: Overscan::X1,
+            filter: Filter::Off,
+            standby: Standby::Ms1000,
+            compensation: Compensation::default(),
+        }
+    }
+
+    /// Datasheet's recommendation for humidity sensing, which skips pressure
+    pub fn humidity_sensing() -> Self {
+        Bme280Config {
+            pressure: Overscan::Skip,
+            ..Self::weather_monitoring()
+        }
+    }
+
+    /// Datasheet's recommendation for indoor navigation, with the lowest pressure noise
+    pub fn indoor_navigation() -> Self {
+        Bme280Config {
+            mode: SensorMode::Normal,
+            temperature: Overscan::X2,
+            pressure: Overscan::X16,
+            humidity: Overscan::X1,
+            filter: Filter::X16,
+            standby: Standby::Ms0_5,
+            compensation: Compensation::default(),
+        }
+    }
+
+    /// Datasheet's recommendation for gaming, which skips humidity for a faster response
+    pub fn gaming() -> Self {
+        Bme280Config {
+            mode: SensorMode::Normal,
+            temperature: Overscan::X1,
+            pressure: Overscan::X4,
+            humidity: Overscan::Skip,
+            filter: Filter::X16,
+            standby: Standby::Ms0_5,
+            compensation: Compensation::default(),
+        }
+    }
+
+    /// Longest a single measurement can take with these oversampling rates
+    pub fn max_measurement_time(&self) -> Duration {
+        let channel = |overscan: Overscan, overhead: u64| match overscan {
+            Overscan::Skip => 0,
+            overscan => 2300 * overscan.samples() as u64 + overhead,
+        };
+        Duration::from_micros(
+            1250 + channel(self.temperature, 0)
+                + channel(self.pressure, 575)
+                + channel(self.humidity, 575),
+        )
+    }
+}
+
+#[derive(Error, Clone, Debug)]
+#[error("Unknown preset {0}, expected weather-monitoring, humidity-sensing, indoor-navigation or gaming")]
+pub struct ParsePresetError(String);
+
+impl FromStr for Bme280Config {
+    type Err = ParsePresetError;
+
+    /// Parses the name of a datasheet preset
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "default" => Ok(Bme280Config::default()),
+            "weather-monitoring" => Ok(Bme280Config::weather_monitoring()),
+            "humidity-sensing" => Ok(Bme280Config::humidity_sensing()),
+            "indoor-navigation" => Ok(Bme280Config::indoor_navigation()),
+            "gaming" => Ok(Bme280Config::gaming()),
+            _ => Err(ParsePresetError(s.to_string())),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+use tokio::time::{Duration, Instant};
+
+/// How far back pressure is kept, the span trends are conventionally given over
+pub const WINDOW: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// Least span of readings to tell a trend from
+pub const MIN_SPAN: Duration = Duration::from_secs(60 * 60);
+
+/// Readings closer together than this are skipped, which keeps the history small
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Least change in hPa over 3 hours for pressure to count as rising or falling
+const STEADY_RATE: f32 = 1.6;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Trend {
+    Falling,
+    Steady,
+    Rising,
+}
+
+impl Display for Trend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trend::Falling => write!(f, "falling"),
+            Trend::Steady => write!(f, "steady"),
+            Trend::Rising => write!(f, "rising"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PressureTrend {
+    pub trend: Trend,
+    /// hPa per 3 hours
+    pub rate: f32,
+    /// Latest pressure in hPa
+    pub pressure: f32,
+}
+
+/// Pressure readings over the last 3 hours
+#[derive(Clone, Debug, Default)]
+pub struct PressureHistory {
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl PressureHistory {
+    pub fn push(&mut self, at: Instant, pressure: f32) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|(last, _)| at.saturating_duration_since(*last) < SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.samples.push_back((at, pressure));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(first, _)| at.saturating_duration_since(*first) > WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Fits a line through the readings, or `None` until they span at least an hour
+    pub fn trend(&self) -> Option<PressureTrend> {
+        let (first, _) = *self.samples.front()?;
+        let (last, pressure) = *self.samples.back()?;
+        if last.duration_since(first) < MIN_SPAN {
+            return None;
+        }
+        // least squares in f64, since seconds squared over 3 hours are too large for f32
+        let points = self
+            .samples
+            .iter()
+            .map(|(at, pressure)| (at.duration_since(first).as_secs_f64(), *pressure as f64))
+            .collect::<Vec<_>>();
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_p = points.iter().map(|(_, p)| p).sum::<f64>() / n;
+        let covariance = points
+            .iter()
+            .map(|(t, p)| (t - mean_t) * (p - mean_p))
+            .sum::<f64>();
+        let variance = points
+            .iter()
+            .map(|(t, _)| (t - mean_t).powi(2))
+            .sum::<f64>();
+        let rate = (covariance / variance * WINDOW.as_secs_f64()) as f32;
+        let trend = if rate >= STEADY_RATE {
+            Trend::Rising
+        } else if rate <= -STEADY_RATE {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        };
+        Some(PressureTrend {
+            trend,
+            rate,
+            pressure,
+        })
+    }
+}
+
+/// Negretti and Zambra's forecasts, numbered 1 to 9 when falling, 10 to 19 when steady and 20 to
+/// 32 when rising, along with a short form for the lcd
+const FORECASTS: [(&str, &str); 32] = [
+    ("Settled fine", "Fine"),
+    ("Fine weather", "Fine"),
+    ("Fine, becoming less settled", "Fair"),
+    ("Fairly fine, showery later", "Fair"),
+    ("Showery, becoming more unsettled", "Showery"),
+    ("Unsettled, rain later", "Unsettld"),
+    ("Rain at times, worse later", "Rain"),
+    ("Rain at times, becoming very unsettled", "Rain"),
+    ("Very unsettled, rain", "Rain"),
+    ("Settled fine", "Fine"),
+    ("Fine weather", "Fine"),
+    ("Fine, possibly showers", "Fair"),
+    ("Fairly fine, showers likely", "Showery"),
+    ("Showery, bright intervals", "Showery"),
+    ("Changeable, some rain", "Changing"),
+    ("Unsettled, rain at times", "Unsettld"),
+    ("Rain at frequent intervals", "Rain"),
+    ("Very unsettled, rain", "Rain"),
+    ("Stormy, much rain", "Stormy"),
+    ("Settled fine", "Fine"),
+    ("Fine weather", "Fine"),
+    ("Becoming fine", "Fair"),
+    ("Fairly fine, improving", "Fair"),
+    ("Fairly fine, possibly showers early", "Fair"),
+    ("Showery early, improving", "Showery"),
+    ("Changeable, mending", "Changing"),
+    ("Rather unsettled, clearing later", "Unsettld"),
+    ("Unsettled, probably improving", "Unsettld"),
+    ("Unsettled, short fine intervals", "Unsettld"),
+    ("Very unsettled, finer at times", "Unsettld"),
+    ("Stormy, possibly improving", "Stormy"),
+    ("Stormy, much rain", "Stormy"),
+];
+
+/// Local forecast for the next 12 hours or so by the Zambretti forecaster
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Forecast {
+    pub trend: PressureTrend,
+    /// hPa the forecast was made from
+    pub sea_level_pressure: f32,
+    /// Zambretti's forecast number, from 1 to 32
+    pub number: u8,
+}
+
+impl Forecast {
+    /// Forecasts from the sea level pressure, using the simplified linear fit of the
+    /// forecaster's dial for each trend
+    pub fn zambretti(sea_level_pressure: f32, trend: PressureTrend) -> Forecast {
+        let p = sea_level_pressure;
+        let (z, min, max) = match trend.trend {
+            Trend::Falling => (127.0 - 0.12 * p, 1, 9),
+            Trend::Steady => (144.0 - 0.13 * p, 10, 19),
+            Trend::Rising => (185.0 - 0.16 * p, 20, 32),
+        };
+        Forecast {
+            trend,
+            sea_level_pressure,
+            number: (z.round() as i32).clamp(min, max) as u8,
+        }
+    }
+
+    pub fn text(&self) -> &'static str {
+        FORECASTS[self.number as usize - 1].0
+    }
+
+    /// At most 8 characters, to fit a line of the lcd
+    pub fn short_text(&self) -> &'static str {
+        FORECASTS[self.number as usize - 1].1
+    }
+}
+
+impl Display for Forecast {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:.0} hPa, {} {:+.1} hPa/3h)",
+            self.text(),
+            self.sea_level_pressure,
+            self.trend.trend,
+            self.trend.rate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reading every minute, going in a straight line between hourly pressures
+    fn hourly(pressures: &[f32]) -> PressureHistory {
+        let start = Instant::now();
+        let mut history = PressureHistory::default();
+        for (hour, pair) in pressures.windows(2).enumerate() {
+            for minute in 0..60 {
+                let at = start + Duration::from_secs((hour * 60 + minute) as u64 * 60);
+                history.push(at, pair[0] + (pair[1] - pair[0]) * minute as f32 / 60.0);
+            }
+        }
+        let end = start + Duration::from_secs((pressures.len() as u64 - 1) * 60 * 60);
+        history.push(end, *pressures.last().unwrap());
+        history
+    }
+
+    #[test]
+    fn steady_pressure() {
+        let trend = hourly(&[1013.2, 1013.4, 1013.1, 1013.3]).trend().unwrap();
+        assert_eq!(trend.trend, Trend::Steady);
+        assert!(trend.rate.abs() < 0.5, "{}", trend.rate);
+        assert_eq!(Forecast::zambretti(1013.0, trend).number, 12);
+    }
+
+    #[test]
+    fn falling_pressure() {
+        let trend = hourly(&[1012.0, 1010.9, 1009.8, 1008.6]).trend().unwrap();
+        assert_eq!(trend.trend, Trend::Falling);
+        assert!((trend.rate + 3.4).abs() < 0.2, "{}", trend.rate);
+        assert_eq!(trend.pressure, 1008.6);
+        assert_eq!(Forecast::zambretti(1013.0, trend).number, 5);
+    }
+
+    #[test]
+    fn rising_pressure() {
+        let trend = hourly(&[1005.0, 1006.5, 1008.0, 1009.5]).trend().unwrap();
+        assert_eq!(trend.trend, Trend::Rising);
+        assert!((trend.rate - 4.5).abs() < 0.1, "{}", trend.rate);
+        assert_eq!(Forecast::zambretti(1013.0, trend).number, 23);
+    }
+
+    #[test]
+    fn no_trend_under_min_span() {
+        assert_eq!(PressureHistory::default().trend(), None);
+        let start = Instant::now();
+        let mut history = PressureHistory::default();
+        history.push(start, 1010.0);
+        history.push(start + MIN_SPAN - SAMPLE_INTERVAL, 1000.0);
+        assert_eq!(history.trend(), None);
+        history.push(start + MIN_SPAN, 1000.0);
+        assert!(history.trend().is_some());
+    }
+
+    #[test]
+    fn readings_within_a_minute_are_skipped() {
+        let start = Instant::now();
+        let mut history = PressureHistory::default();
+        for seconds in (0..=120).step_by(10) {
+            history.push(
+                start + Duration::from_secs(seconds),
+                1000.0 + seconds as f32,
+            );
+        }
+        let samples = history.samples.iter().map(|(_, p)| *p).collect::<Vec<_>>();
+        assert_eq!(samples, vec![1000.0, 1060.0, 1120.0]);
+    }
+
+    #[test]
+    fn readings_older_than_the_window_are_dropped() {
+        // fell for 3 hours, then held for 3, so only the steady part is left
+        let trend = hourly(&[1020.0, 1017.0, 1014.0, 1011.0, 1011.0, 1011.0, 1011.0])
+            .trend()
+            .unwrap();
+        assert_eq!(trend.trend, Trend::Steady);
+    }
+
+    #[test]
+    fn forecast_numbers_are_clamped_to_the_trend() {
+        let trend = hourly(&[1000.0, 1010.0]).trend().unwrap();
+        assert_eq!(trend.trend, Trend::Rising);
+        assert_eq!(Forecast::zambretti(1050.0, trend).number, 20);
+        assert_eq!(Forecast::zambretti(950.0, trend).number, 32);
+        for (trend, min, max) in [
+            (Trend::Falling, 1, 9),
+            (Trend::Steady, 10, 19),
+            (Trend::Rising, 20, 32),
+        ] {
+            let trend = PressureTrend {
+                trend,
+                rate: 0.0,
+                pressure: 1000.0,
+            };
+            for pressure in [900.0, 1013.0, 1100.0] {
+                let number = Forecast::zambretti(pressure, trend).number;
+                assert!((min..=max).contains(&number), "{:?} {}", trend, number);
+            }
+        }
+    }
+
+    #[test]
+    fn short_texts_fit_the_lcd() {
+        let trend = hourly(&[1013.0, 1013.0]).trend().unwrap();
+        for number in 1..=32 {
+            let forecast = Forecast {
+                number,
+                ..Forecast::zambretti(1013.0, trend)
+            };
+            assert!(
+                forecast.short_text().len() <= 8,
+                "{}",
+                forecast.short_text()
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt::{Display, Formatter};
+use std::time::SystemTime;
+
+use tokio::time::{Duration, Instant};
+
+/// Consecutive failed reads before the sensor is reset
+pub const RESET_AFTER: u32 = 3;
+
+/// Consecutive failed reads from which the sensor counts as failed rather than degraded
+pub const FAILED_AFTER: u32 = 10;
+
+/// Wait after the first reset before trying another, doubled after each one up to `MAX_BACKOFF`
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HealthState {
+    #[default]
+    Ok,
+    /// Reads are failing, but the sensor may still recover by itself or by being reset
+    Degraded,
+    /// Resetting has not brought the sensor back
+    Failed,
+}
+
+impl Display for HealthState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthState::Ok => write!(f, "ok"),
+            HealthState::Degraded => write!(f, "degraded"),
+            HealthState::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SensorHealth {
+    pub state: HealthState,
+    /// Failed reads since the last successful one
+    pub consecutive_errors: u32,
+    pub total_errors: u64,
+    pub resets: u32,
+    pub last_success: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+/// Follows how reads go, deciding when the sensor should be reset
+#[derive(Clone, Debug)]
+pub struct HealthMonitor {
+    health: SensorHealth,
+    backoff: Duration,
+    next_reset: Option<Instant>,
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        HealthMonitor {
+            health: SensorHealth::default(),
+            backoff: INITIAL_BACKOFF,
+            next_reset: None,
+        }
+    }
+}
+
+impl HealthMonitor {
+    pub fn health(&self) -> &SensorHealth {
+        &self.health
+    }
+
+    pub fn succeeded(&mut self, at: SystemTime) {
+        self.health.state = HealthState::Ok;
+        self.health.consecutive_errors = 0;
+        self.health.last_success = Some(at);
+        self.backoff = INITIAL_BACKOFF;
+        self.next_reset = None;
+    }
+
+    /// Records a failed read, returning whether the sensor should be reset now
+    pub fn failed(&mut self, now: Instant, error: String) -> bool {
+        self.health.consecutive_errors += 1;
+        self.health.total_errors += 1;
+        self.health.last_error = Some(error);
+        self.health.state = if self.health.consecutive_errors >= FAILED_AFTER {
+            HealthState::Failed
+        } else {
+            HealthState::Degraded
+        };
+        self.health.consecutive_errors >= RESET_AFTER
+            && self.next_reset.is_none_or(|next_reset| now >= next_reset)
+    }
+
+    /// Backs off from resetting again, whether or not the reset went through, as only a
+    /// successful read shows the sensor is back
+    pub fn reset(&mut self, now: Instant) {
+        self.health.resets += 1;
+        self.next_reset = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(monitor: &mut HealthMonitor, times: u32, at: Instant) -> bool {
+        (0..times).fold(false, |_, _| monitor.failed(at, "read failed".to_string()))
+    }
+
+    #[test]
+    fn resets_after_enough_failures() {
+        let mut monitor = HealthMonitor::default();
+        let start = Instant::now();
+        assert!(!fail(&mut monitor, RESET_AFTER - 1, start));
+        assert_eq!(monitor.health().state, HealthState::Degraded);
+        assert!(monitor.failed(start, "read failed".to_string()));
+        assert_eq!(monitor.health().consecutive_errors, RESET_AFTER);
+    }
+
+    #[test]
+    fn waits_out_the_backoff_before_resetting_again() {
+        let mut monitor = HealthMonitor::default();
+        let start = Instant::now();
+        assert!(fail(&mut monitor, RESET_AFTER, start));
+        monitor.reset(start);
+        assert_eq!(monitor.health().resets, 1);
+        let just_before = start + INITIAL_BACKOFF - Duration::from_millis(1);
+        assert!(!fail(&mut monitor, 1, just_before));
+        assert!(fail(&mut monitor, 1, start + INITIAL_BACKOFF));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_most() {
+        let mut monitor = HealthMonitor::default();
+        let mut now = Instant::now();
+        let mut backoffs = Vec::new();
+        for _ in 0..8 {
+            assert!(fail(&mut monitor, RESET_AFTER, now));
+            monitor.reset(now);
+            let next_reset = monitor.next_reset.unwrap();
+            backoffs.push((next_reset - now).as_secs());
+            now = next_reset;
+        }
+        assert_eq!(backoffs, vec![10, 20, 40, 80, 160, 320, 600, 600]);
+        assert_eq!(MAX_BACKOFF, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn fails_after_enough_failures() {
+        let mut monitor = HealthMonitor::default();
+        let start = Instant::now();
+        fail(&mut monitor, FAILED_AFTER - 1, start);
+        assert_eq!(monitor.health().state, HealthState::Degraded);
+        fail(&mut monitor, 1, start);
+        assert_eq!(monitor.health().state, HealthState::Failed);
+        assert_eq!(monitor.health().total_errors, FAILED_AFTER as u64);
+        assert_eq!(monitor.health().last_error.as_deref(), Some("read failed"));
+    }
+
+    #[test]
+    fn success_clears_the_backoff() {
+        let mut monitor = HealthMonitor::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            fail(&mut monitor, RESET_AFTER, start);
+            monitor.reset(start);
+        }
+        let at = SystemTime::now();
+        monitor.succeeded(at);
+        let health = monitor.health();
+        assert_eq!(health.state, HealthState::Ok);
+        assert_eq!(health.consecutive_errors, 0);
+        assert_eq!(health.last_success, Some(at));
+        assert_eq!(health.resets, 3);
+        // resets straight away again, then backs off from the start
+        assert!(fail(&mut monitor, RESET_AFTER, start));
+        monitor.reset(start);
+        assert_eq!(monitor.next_reset, Some(start + INITIAL_BACKOFF));
+    }
+}
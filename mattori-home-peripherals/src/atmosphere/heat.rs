@@ -0,0 +1,99 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// Estimates the indoor WBGT in celsius from air temperature and relative humidity, using the
+/// approximation recommended alongside the Ministry of the Environment's heatstroke index for
+/// rooms without direct sunlight
+pub fn wbgt(temperature: f32, humidity: f32) -> f32 {
+    0.725 * temperature + 0.0368 * humidity + 0.00364 * temperature * humidity - 3.246
+}
+
+/// Heatstroke risk bands for daily life by WBGT, from safest to most dangerous
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum HeatRisk {
+    /// ほぼ安全, below 21
+    Safe,
+    /// 注意, from 21
+    Caution,
+    /// 警戒, from 25
+    Warning,
+    /// 厳重警戒, from 28
+    SevereWarning,
+    /// 危険, from 31
+    Danger,
+}
+
+#[derive(Error, Clone, Debug)]
+#[error("Invalid heat risk {0}, expected safe, caution, warning, severe or danger")]
+pub struct ParseHeatRiskError(String);
+
+impl HeatRisk {
+    pub const ALL: [HeatRisk; 5] = [
+        HeatRisk::Safe,
+        HeatRisk::Caution,
+        HeatRisk::Warning,
+        HeatRisk::SevereWarning,
+        HeatRisk::Danger,
+    ];
+
+    pub fn from_wbgt(wbgt: f32) -> HeatRisk {
+        HeatRisk::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|risk| wbgt >= risk.lower_bound())
+            .unwrap_or(HeatRisk::Safe)
+    }
+
+    /// Lowest WBGT in the band
+    pub fn lower_bound(&self) -> f32 {
+        match self {
+            HeatRisk::Safe => f32::NEG_INFINITY,
+            HeatRisk::Caution => 21.0,
+            HeatRisk::Warning => 25.0,
+            HeatRisk::SevereWarning => 28.0,
+            HeatRisk::Danger => 31.0,
+        }
+    }
+
+    /// Name used by the Ministry of the Environment
+    pub fn official_name(&self) -> &'static str {
+        match self {
+            HeatRisk::Safe => "ほぼ安全",
+            HeatRisk::Caution => "注意",
+            HeatRisk::Warning => "警戒",
+            HeatRisk::SevereWarning => "厳重警戒",
+            HeatRisk::Danger => "危険",
+        }
+    }
+}
+
+impl FromStr for HeatRisk {
+    type Err = ParseHeatRiskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "safe" | "ほぼ安全" => Ok(HeatRisk::Safe),
+            "caution" | "注意" => Ok(HeatRisk::Caution),
+            "warning" | "警戒" => Ok(HeatRisk::Warning),
+            "severe" | "厳重警戒" => Ok(HeatRisk::SevereWarning),
+            "danger" | "危険" => Ok(HeatRisk::Danger),
+            _ => Err(ParseHeatRiskError(s.to_string())),
+        }
+    }
+}
+
+/// Short english names, which also fit on the lcd
+impl Display for HeatRisk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeatRisk::Safe => write!(f, "safe"),
+            HeatRisk::Caution => write!(f, "caution"),
+            HeatRisk::Warning => write!(f, "warning"),
+            HeatRisk::SevereWarning => write!(f, "severe"),
+            HeatRisk::Danger => write!(f, "danger"),
+        }
+    }
+}
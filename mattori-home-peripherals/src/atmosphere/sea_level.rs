@@ -0,0 +1,18 @@
+use std::ops::RangeInclusive;
+
+/// Pressure of the standard atmosphere at sea level, in hPa
+pub const STANDARD_PRESSURE: f32 = 1013.25;
+
+/// Sea level pressures that have been observed, with some leeway, in hPa
+pub const PLAUSIBLE_PRESSURE: RangeInclusive<f32> = 850.0..=1100.0;
+
+/// Metres above where the pressure would be `sea_level_pressure`, by the international
+/// barometric formula
+pub fn altitude(pressure: f32, sea_level_pressure: f32) -> f32 {
+    44330.0 * (1.0 - (pressure / sea_level_pressure).powf(0.1903))
+}
+
+/// Sea level pressure, or QNH, at which a station `altitude` metres up would measure `pressure`
+pub fn sea_level_pressure(pressure: f32, altitude: f32) -> f32 {
+    pressure / (1.0 - altitude / 44330.0).powf(1.0 / 0.1903)
+}
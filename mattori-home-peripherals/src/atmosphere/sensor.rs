@@ -0,0 +1,152 @@
+use tokio::time::Duration;
+
+use crate::atmosphere::comfort::{
+    absolute_humidity, apparent_temperature, dew_point, discomfort_index, heat_index,
+};
+use crate::atmosphere::config::Bme280Config;
+use crate::atmosphere::heat::wbgt;
+use crate::atmosphere::types::AtmoI2c;
+use crate::atmosphere::user_calibration::UserCalibration;
+use crate::atmosphere::{AtmosphereError, AtmosphereFeatures, Reading, Result};
+
+/// An environmental sensor, which an `Atmosphere` reads on its own thread
+pub trait Sensor: Send + 'static {
+    /// Model name, such as BME280
+    fn model(&self) -> String;
+
+    /// Everything the sensor can measure or derive, regardless of which are enabled
+    fn available_features(&self) -> AtmosphereFeatures;
+
+    /// Measures whichever of the enabled features the sensor supports
+    fn read(&mut self, features: &AtmosphereFeatures) -> Result<Reading>;
+
+    /// How often new data is available, for sensors that measure on their own
+    fn data_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    fn recalibrate(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Brings the sensor back to how it was set up, after it kept failing to read
+    fn reset(&mut self) -> Result<()> {
+        Err(AtmosphereError::Unsupported(self.model(), "reset"))
+    }
+
+    fn set_sea_level_pressure(&mut self, _sea_level_pressure: f32) {}
+
+    fn set_config(&mut self, _config: Bme280Config) -> Result<()> {
+        Err(AtmosphereError::Unsupported(
+            self.model(),
+            "sampling config",
+        ))
+    }
+
+    /// Sets the corrections applied to every reading after compensation
+    fn set_user_calibration(&mut self, calibration: UserCalibration);
+
+    /// Corrects a CO2 sensor against a known concentration, such as about 420 ppm outdoors
+    fn force_recalibration(&mut self, _reference_ppm: u16) -> Result<()> {
+        Err(AtmosphereError::Unsupported(
+            self.model(),
+            "forced recalibration",
+        ))
+    }
+}
+
+/// Temperature and humidity along with the comfort metrics derived from them
+pub(crate) fn climate_reading(
+    features: &AtmosphereFeatures,
+    temperature: f32,
+    humidity: Option<f32>,
+) -> Reading {
+    let derived = |enabled: bool, metric: fn(f32, f32) -> f32| {
+        humidity
+            .filter(|_| enabled)
+            .map(|humidity| metric(temperature, humidity))
+    };
+    Reading {
+        temperature: Some(temperature),
+        humidity: humidity.filter(|_| features.humidity),
+        wbgt: derived(features.wbgt, wbgt),
+        dew_point: derived(features.dew_point, dew_point),
+        absolute_humidity: derived(features.absolute_humidity, absolute_humidity),
+        discomfort_index: derived(features.discomfort_index, discomfort_index),
+        heat_index: derived(features.heat_index, heat_index),
+        apparent_temperature: derived(features.apparent_temperature, apparent_temperature),
+        ..Reading::empty()
+    }
+}
+
+impl Sensor for AtmoI2c {
+    fn model(&self) -> String {
+        self.chip.to_string()
+    }
+
+    fn available_features(&self) -> AtmosphereFeatures {
+        AtmosphereFeatures::available(self.chip)
+    }
+
+    fn read(&mut self, features: &AtmosphereFeatures) -> Result<Reading> {
+        if !features.temperature_enabled() {
+            trace!("no features enabled");
+            return Ok(Reading::empty());
+        }
+        let data = self.read_data()?;
+        trace!("read data: {:?}", data);
+        let (temp_fine, temperature) = self.read_temperature(&data);
+        trace!("read temperature: {:?} {:?}", temp_fine, temperature);
+        let temperature = self.user_calibration.temperature(temperature);
+
+        let pressure = (features.pressure_enabled() && self.measures_pressure())
+            .then(|| self.read_pressure(&data, temp_fine))
+            .transpose()?
+            .map(|pressure| self.user_calibration.pressure(pressure));
+        trace!("read pressure: {:?}", pressure);
+
+        let humidity = (features.humidity_enabled() && self.measures_humidity())
+            .then(|| self.read_humidity(&data, temp_fine))
+            .flatten()
+            .map(|humidity| self.user_calibration.humidity(humidity));
+        trace!("read humidity: {:?}", humidity);
+
+        let altitude =
+            pressure.and_then(|p| features.altitude_enabled().then(|| self.read_altitude(p)));
+        trace!("read altitude: {:?}", altitude);
+
+        Ok(Reading {
+            pressure,
+            altitude,
+            gas_resistance: data
+                .gas
+                .filter(|_| features.gas)
+                .and_then(|gas| self.read_gas_resistance(&gas)),
+            ..climate_reading(features, temperature, humidity)
+        })
+    }
+
+    fn data_interval(&self) -> Option<Duration> {
+        AtmoI2c::data_interval(self)
+    }
+
+    fn recalibrate(&mut self) -> Result<()> {
+        Ok(self.reload_calibration()?)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        Ok(self.reinitialize()?)
+    }
+
+    fn set_sea_level_pressure(&mut self, sea_level_pressure: f32) {
+        AtmoI2c::set_sea_level_pressure(self, sea_level_pressure)
+    }
+
+    fn set_config(&mut self, config: Bme280Config) -> Result<()> {
+        Ok(AtmoI2c::set_config(self, config)?)
+    }
+
+    fn set_user_calibration(&mut self, calibration: UserCalibration) {
+        self.user_calibration = calibration;
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::time::Duration;
+
+use crate::atmosphere::forecast::{Forecast, PressureTrend};
+use crate::atmosphere::health::SensorHealth;
+use crate::atmosphere::sea_level::{self, PLAUSIBLE_PRESSURE, STANDARD_PRESSURE};
+use crate::atmosphere::user_calibration::{CalibrationPoint, UserCalibration};
+use crate::atmosphere::{Atmosphere, AtmosphereError, Reading, ATMOSPHERE_ADDR, READ_RATE};
+use crate::co2::scd30::Scd30;
+use crate::co2::scd4x::Scd4x;
+use crate::persist::{self, PersistError};
+
+#[derive(Error, Clone, Debug)]
+pub enum SensorsError {
+    #[error("Unknown sensor {0}")]
+    UnknownSensor(String),
+    #[error("Could not start sensor {0}")]
+    Start(String, #[source] AtmosphereError),
+    #[error("Could not update sensor {0}")]
+    Update(String, #[source] AtmosphereError),
+    #[error("Sensor {0} has no {1} reading to calibrate against")]
+    NoReading(String, &'static str),
+    #[error("Sensor {0} needs at least an hour of pressure readings to forecast")]
+    NoPressureTrend(String),
+    #[error("Sea level pressure {0} hPa is implausible")]
+    ImplausiblePressure(f32),
+    #[error("Sensor {0} calibration has a temperature scale of 0")]
+    InvalidCalibration(String),
+    #[error("Could not acquire sensors mutex")]
+    Mutex,
+    #[error("Could not load sensors")]
+    Load(#[from] PersistError),
+    #[error("Could not save sensors")]
+    Save(#[source] PersistError),
+}
+
+fn default_read_rate_ms() -> u64 {
+    READ_RATE.as_millis() as u64
+}
+
+fn default_sea_level_pressure() -> f32 {
+    STANDARD_PRESSURE
+}
+
+/// Which driver reads a sensor
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorModel {
+    /// BMP280, BME280 or BME680, told apart by their chip id
+    #[default]
+    Bosch,
+    Scd30,
+    /// SCD40 or SCD41
+    Scd4x,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SensorConfig {
+    /// Human readable name, such as "Living room"
+    #[serde(default)]
+    pub name: String,
+    /// Where the sensor is, such as "indoor" or "balcony"
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub model: SensorModel,
+    pub address: u16,
+    /// Least time between readings, which a free-running sensor may shorten
+    #[serde(default = "default_read_rate_ms")]
+    pub read_rate_ms: u64,
+    /// QNH in hPa that altitude is measured from
+    #[serde(default = "default_sea_level_pressure")]
+    pub sea_level_pressure: f32,
+    /// Metres above sea level, once set, which forecasts reduce pressure to sea level with
+    pub station_altitude: Option<f32>,
+    #[serde(default)]
+    pub calibration: UserCalibration,
+}
+
+impl SensorConfig {
+    pub fn start(&self) -> Result<Atmosphere, AtmosphereError> {
+        let read_rate = Duration::from_millis(self.read_rate_ms);
+        let atmosphere = match self.model {
+            SensorModel::Bosch => Atmosphere::start_with_rate(self.address, read_rate)?,
+            SensorModel::Scd30 => {
+                Atmosphere::with_sensor(Scd30::new(self.address, read_rate)?, read_rate)
+            }
+            SensorModel::Scd4x => Atmosphere::with_sensor(Scd4x::new(self.address)?, read_rate),
+        };
+        atmosphere.change_sea_level_pressure(self.sea_level_pressure)?;
+        atmosphere.change_user_calibration(self.calibration)?;
+        Ok(atmosphere)
+    }
+
+    /// Takes a new QNH, placing the station by it if there's a pressure reading to do so with,
+    /// otherwise keeping whatever altitude it had
+    fn set_sea_level_pressure(&mut self, qnh: f32, latest_pressure: Option<f32>) {
+        self.sea_level_pressure = qnh;
+        if let Some(pressure) = latest_pressure {
+            self.station_altitude = Some(sea_level::altitude(pressure, qnh));
+        }
+    }
+
+    /// Reduces the trend's pressure to sea level from the station altitude, or if the station
+    /// hasn't been placed, from the altitude the QNH gives for the latest pressure
+    fn forecast(&self, trend: PressureTrend, latest_pressure: f32) -> Forecast {
+        let altitude = self
+            .station_altitude
+            .unwrap_or_else(|| sea_level::altitude(latest_pressure, self.sea_level_pressure));
+        Forecast::zambretti(
+            sea_level::sea_level_pressure(trend.pressure, altitude),
+            trend,
+        )
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SensorsFile {
+    /// Sensor the thermostat, dehumidifier, rules and alerts follow
+    primary: Option<String>,
+    #[serde(default)]
+    sensors: BTreeMap<String, SensorConfig>,
+}
+
+/// Sensor configs keyed by id, before any are started
+#[derive(Clone, Debug)]
+pub struct SensorsConfig {
+    primary: String,
+    sensors: BTreeMap<String, SensorConfig>,
+}
+
+impl Default for SensorsConfig {
+    /// A single indoor sensor at the default address
+    fn default() -> Self {
+        let mut sensors = BTreeMap::new();
+        sensors.insert(
+            "indoor".to_string(),
+            SensorConfig {
+                name: "Indoor".to_string(),
+                location: "indoor".to_string(),
+                model: SensorModel::Bosch,
+                address: ATMOSPHERE_ADDR,
+                read_rate_ms: default_read_rate_ms(),
+                sea_level_pressure: STANDARD_PRESSURE,
+                station_altitude: None,
+                calibration: UserCalibration::default(),
+            },
+        );
+        SensorsConfig {
+            primary: "indoor".to_string(),
+            sensors,
+        }
+    }
+}
+
+impl SensorsConfig {
+    /// Reads sensors from a toml file, treating a missing file or one without sensors as having
+    /// only the default indoor sensor
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SensorsConfig, SensorsError> {
+        SensorsConfig::from_file(persist::load(path)?)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<SensorsConfig, SensorsError> {
+        SensorsConfig::from_file(persist::from_str("sensors", contents)?)
+    }
+
+    fn from_file(file: SensorsFile) -> Result<SensorsConfig, SensorsError> {
+        if file.sensors.is_empty() {
+            return Ok(SensorsConfig::default());
+        }
+        if let Some((id, _)) = file
+            .sensors
+            .iter()
+            .find(|(_, sensor)| !sensor.calibration.is_valid())
+        {
+            return Err(SensorsError::InvalidCalibration(id.clone()));
+        }
+        let primary = match file.primary {
+            Some(primary) if !file.sensors.contains_key(&primary) => {
+                return Err(SensorsError::UnknownSensor(primary))
+            }
+            Some(primary) => primary,
+            None => file.sensors.keys().next().cloned().unwrap_or_default(),
+        };
+        Ok(SensorsConfig {
+            primary,
+            sensors: file.sensors,
+        })
+    }
+
+    fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SensorsError> {
+        persist::save(path, &self.to_file()).map_err(SensorsError::Save)
+    }
+
+    fn to_file(&self) -> SensorsFile {
+        SensorsFile {
+            primary: Some(self.primary.clone()),
+            sensors: self.sensors.clone(),
+        }
+    }
+
+    pub fn primary(&self) -> &str {
+        &self.primary
+    }
+
+    pub fn get(&self, id: &str) -> Result<&SensorConfig, SensorsError> {
+        self.sensors
+            .get(id)
+            .ok_or_else(|| SensorsError::UnknownSensor(id.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SensorConfig)> + '_ {
+        self.sensors
+            .iter()
+            .map(|(id, config)| (id.as_str(), config))
+    }
+}
+
+/// A running sensor with where it is
+#[derive(Debug)]
+pub struct NamedSensor {
+    pub id: String,
+    pub name: String,
+    pub location: String,
+    pub atmosphere: Atmosphere,
+}
+
+/// Sensor configs as saved, along with the first point of a temperature calibration in progress
+#[derive(Debug)]
+struct SavedSensors {
+    config: SensorsConfig,
+    temperature_points: BTreeMap<String, CalibrationPoint>,
+}
+
+/// Every configured sensor, each read on its own thread at its own rate, with calibration
+/// changes saved back to their file
+#[derive(Debug)]
+pub struct Sensors {
+    path: PathBuf,
+    primary: String,
+    sensors: BTreeMap<String, NamedSensor>,
+    saved: Mutex<SavedSensors>,
+}
+
+impl Sensors {
+    /// Starts the sensors in a toml file, treating a missing file as having only the default
+    /// indoor sensor
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Sensors, SensorsError> {
+        let config = SensorsConfig::load(&path)?;
+        let sensors = config
+            .iter()
+            .map(|(id, sensor)| {
+                let atmosphere = sensor
+                    .start()
+                    .map_err(|e| SensorsError::Start(id.to_string(), e))?;
+                let sensor = NamedSensor {
+                    id: id.to_string(),
+                    name: sensor.name.clone(),
+                    location: sensor.location.clone(),
+                    atmosphere,
+                };
+                Ok((id.to_string(), sensor))
+            })
+            .collect::<Result<_, SensorsError>>()?;
+        Ok(Sensors {
+            path: path.as_ref().to_path_buf(),
+            primary: config.primary.clone(),
+            sensors,
+            saved: Mutex::new(SavedSensors {
+                config,
+                temperature_points: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Sensor the thermostat, dehumidifier, rules and alerts follow
+    pub fn primary(&self) -> &Atmosphere {
+        &self.sensors[&self.primary].atmosphere
+    }
+
+    pub fn primary_id(&self) -> &str {
+        &self.primary
+    }
+
+    pub fn get(&self, id: &str) -> Result<&NamedSensor, SensorsError> {
+        self.sensors
+            .get(id)
+            .ok_or_else(|| SensorsError::UnknownSensor(id.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NamedSensor> + '_ {
+        self.sensors.values()
+    }
+
+    pub fn calibration(&self, id: &str) -> Result<UserCalibration, SensorsError> {
+        let saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        Ok(saved.config.get(id)?.calibration)
+    }
+
+    /// Applies the calibration to every following reading and saves it
+    pub fn set_calibration(
+        &self,
+        id: &str,
+        calibration: UserCalibration,
+    ) -> Result<(), SensorsError> {
+        let mut saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        saved.temperature_points.remove(id);
+        self.apply_calibration(&mut saved, id, calibration)
+    }
+
+    /// Calibrates the temperature against a reference thermometer. The first reference only
+    /// sets an offset, while another at a different temperature also sets the scale from the
+    /// two, until `restart` begins again with a new first point.
+    pub fn calibrate_temperature(
+        &self,
+        id: &str,
+        reference: f32,
+        restart: bool,
+    ) -> Result<UserCalibration, SensorsError> {
+        let mut saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        let calibration = saved.config.get(id)?.calibration;
+        let corrected = self.latest(id, "temperature", |reading| reading.temperature)?;
+        let point = CalibrationPoint {
+            measured: calibration.uncorrected_temperature(corrected),
+            reference,
+        };
+        if restart {
+            saved.temperature_points.remove(id);
+        }
+        let (temperature_scale, temperature_offset) = match saved.temperature_points.get(id) {
+            Some(first) => first.two_point(&point).unwrap_or_else(|| point.offset()),
+            None => {
+                saved.temperature_points.insert(id.to_string(), point);
+                point.offset()
+            }
+        };
+        let calibration = UserCalibration {
+            temperature_scale,
+            temperature_offset,
+            ..calibration
+        };
+        self.apply_calibration(&mut saved, id, calibration)?;
+        Ok(calibration)
+    }
+
+    fn apply_calibration(
+        &self,
+        saved: &mut SavedSensors,
+        id: &str,
+        calibration: UserCalibration,
+    ) -> Result<(), SensorsError> {
+        if !calibration.is_valid() {
+            return Err(SensorsError::InvalidCalibration(id.to_string()));
+        }
+        self.get(id)?
+            .atmosphere
+            .change_user_calibration(calibration)
+            .map_err(|e| SensorsError::Update(id.to_string(), e))?;
+        self.save_change(saved, id, |sensor| sensor.calibration = calibration)
+    }
+
+    pub fn sea_level_pressure(&self, id: &str) -> Result<f32, SensorsError> {
+        let saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        Ok(saved.config.get(id)?.sea_level_pressure)
+    }
+
+    /// Measures altitude from the given QNH in hPa from now on, and saves it along with the station
+    /// altitude it gives for the latest reading, if there is one
+    pub fn set_sea_level_pressure(&self, id: &str, pressure: f32) -> Result<(), SensorsError> {
+        let latest = self.latest(id, "pressure", |reading| reading.pressure).ok();
+        let mut saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        self.apply_sea_level_pressure(id, pressure)?;
+        self.save_change(&mut saved, id, |sensor| {
+            sensor.set_sea_level_pressure(pressure, latest)
+        })
+    }
+
+    /// Derives and saves the sea level pressure from the latest reading, given how many metres
+    /// above sea level the sensor is, returning the derived QNH
+    pub fn set_station_altitude(&self, id: &str, altitude: f32) -> Result<f32, SensorsError> {
+        let pressure = self.latest(id, "pressure", |reading| reading.pressure)?;
+        let sea_level_pressure = sea_level::sea_level_pressure(pressure, altitude);
+        let mut saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        self.apply_sea_level_pressure(id, sea_level_pressure)?;
+        self.save_change(&mut saved, id, |sensor| {
+            sensor.sea_level_pressure = sea_level_pressure;
+            sensor.station_altitude = Some(altitude);
+        })?;
+        Ok(sea_level_pressure)
+    }
+
+    /// Forecasts from the last 3 hours of pressure, reduced to sea level
+    pub fn forecast(&self, id: &str) -> Result<Forecast, SensorsError> {
+        let trend = self
+            .get(id)?
+            .atmosphere
+            .pressure_trend()
+            .map_err(|_| SensorsError::Mutex)?
+            .ok_or_else(|| SensorsError::NoPressureTrend(id.to_string()))?;
+        let latest = self
+            .latest(id, "pressure", |reading| reading.pressure)
+            .unwrap_or(trend.pressure);
+        let saved = self.saved.lock().map_err(|_| SensorsError::Mutex)?;
+        Ok(saved.config.get(id)?.forecast(trend, latest))
+    }
+
+    pub fn health(&self, id: &str) -> Result<SensorHealth, SensorsError> {
+        self.get(id)?
+            .atmosphere
+            .health()
+            .map_err(|_| SensorsError::Mutex)
+    }
+
+    /// Reloads the sensor's factory calibration
+    pub fn recalibrate(&self, id: &str) -> Result<(), SensorsError> {
+        self.get(id)?
+            .atmosphere
+            .recalibrate()
+            .map_err(|e| SensorsError::Update(id.to_string(), e))
+    }
+
+    fn apply_sea_level_pressure(&self, id: &str, pressure: f32) -> Result<(), SensorsError> {
+        if !PLAUSIBLE_PRESSURE.contains(&pressure) {
+            return Err(SensorsError::ImplausiblePressure(pressure));
+        }
+        self.get(id)?
+            .atmosphere
+            .change_sea_level_pressure(pressure)
+            .map_err(|e| SensorsError::Update(id.to_string(), e))
+    }
+
+    /// A field of the sensor's latest reading, named `name` in errors
+    fn latest(
+        &self,
+        id: &str,
+        name: &'static str,
+        field: fn(&Reading) -> Option<f32>,
+    ) -> Result<f32, SensorsError> {
+        self.get(id)?
+            .atmosphere
+            .subscribe()
+            .borrow()
+            .as_ref()
+            .ok()
+            .and_then(field)
+            .ok_or_else(|| SensorsError::NoReading(id.to_string(), name))
+    }
+
+    fn save_change<F: FnOnce(&mut SensorConfig)>(
+        &self,
+        saved: &mut SavedSensors,
+        id: &str,
+        change: F,
+    ) -> Result<(), SensorsError> {
+        if let Some(sensor) = saved.config.sensors.get_mut(id) {
+            change(sensor);
+        }
+        saved.config.save(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atmosphere::forecast::Trend;
+
+    #[test]
+    fn loads_calibration() {
+        let config = SensorsConfig::from_toml(
+            "[sensors.indoor]\naddress = 118\n[sensors.indoor.calibration]\ntemperature_offset = -2.1\n",
+        )
+        .unwrap();
+        let calibration = config.get("indoor").unwrap().calibration;
+        assert_eq!(calibration.temperature_offset, -2.1);
+        assert_eq!(calibration.temperature_scale, 1.0);
+        assert_eq!(calibration.humidity_scale, 1.0);
+    }
+
+    fn indoor() -> SensorConfig {
+        SensorsConfig::default().get("indoor").unwrap().clone()
+    }
+
+    fn steady(pressure: f32) -> PressureTrend {
+        PressureTrend {
+            trend: Trend::Steady,
+            rate: 0.0,
+            pressure,
+        }
+    }
+
+    #[test]
+    fn qnh_places_the_station_by_the_latest_reading() {
+        let mut sensor = indoor();
+        sensor.set_sea_level_pressure(1020.0, Some(1015.0));
+        assert_eq!(sensor.sea_level_pressure, 1020.0);
+        let altitude = sensor.station_altitude.unwrap();
+        assert!((altitude - 41.3).abs() < 0.5, "{}", altitude);
+    }
+
+    #[test]
+    fn qnh_before_any_reading_keeps_the_station_altitude() {
+        let mut sensor = SensorConfig {
+            station_altitude: Some(40.0),
+            ..indoor()
+        };
+        sensor.set_sea_level_pressure(1020.0, None);
+        assert_eq!(sensor.sea_level_pressure, 1020.0);
+        assert_eq!(sensor.station_altitude, Some(40.0));
+
+        // the forecast follows the pressure rather than the QNH
+        let forecast = sensor.forecast(steady(1000.0), 1000.0);
+        assert_eq!(
+            forecast.sea_level_pressure,
+            sea_level::sea_level_pressure(1000.0, 40.0)
+        );
+        let lower = sensor.forecast(steady(990.0), 990.0);
+        assert!(lower.sea_level_pressure < forecast.sea_level_pressure - 9.0);
+    }
+
+    #[test]
+    fn unplaced_stations_reduce_by_the_qnh() {
+        let sensor = SensorConfig {
+            sea_level_pressure: 1020.0,
+            ..indoor()
+        };
+        assert_eq!(sensor.station_altitude, None);
+        // the last hour's fit is a little behind the latest reading
+        let forecast = sensor.forecast(steady(1009.0), 1010.0);
+        let altitude = sea_level::altitude(1010.0, 1020.0);
+        assert_eq!(
+            forecast.sea_level_pressure,
+            sea_level::sea_level_pressure(1009.0, altitude)
+        );
+    }
+
+    #[test]
+    fn rejects_zero_temperature_scale() {
+        let config = SensorsConfig::from_toml(
+            "[sensors.indoor]\naddress = 118\n[sensors.indoor.calibration]\ntemperature_scale = 0\n",
+        );
+        assert!(matches!(
+            config,
+            Err(SensorsError::InvalidCalibration(id)) if id == "indoor"
+        ));
+    }
+}
@@ -2,8 +2,14 @@ use std::sync::{Mutex, MutexGuard};
 
 use rppal::i2c::I2c;
 use thiserror::Error;
+use tokio::time::Duration;
 
-use crate::atmosphere::calibration::Calibration;
+use crate::atmosphere::bme680::{Heater, RawGas};
+use crate::atmosphere::calibration::ChipCalibration;
+use crate::atmosphere::chip::Chip;
+use crate::atmosphere::config::{Bme280Config, Overscan, SensorMode};
+use crate::atmosphere::sea_level::STANDARD_PRESSURE;
+use crate::atmosphere::user_calibration::UserCalibration;
 use crate::{I2cError, RppalError};
 
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq)]
@@ -34,9 +40,17 @@ pub enum Register {
     DigT1,
     DigH1,
     DigH2,
-    TempData,
-    PressureData,
-    HumidData,
+    Data,
+    Bme680Coefficients1,
+    Bme680Coefficients2,
+    Bme680Coefficients3,
+    Bme680ResHeat0,
+    Bme680GasWait0,
+    Bme680CtrlGas1,
+    Bme680CtrlHum,
+    Bme680CtrlMeas,
+    Bme680Config,
+    Bme680Data,
 }
 
 impl From<Register> for u8 {
@@ -51,24 +65,59 @@ impl From<Register> for u8 {
             Register::DigT1 => 0x88,
             Register::DigH1 => 0xa1,
             Register::DigH2 => 0xe1,
-            Register::TempData => 0xfa,
-            Register::PressureData => 0xf7,
-            Register::HumidData => 0xfd,
+            Register::Data => 0xf7,
+            Register::Bme680Coefficients1 => 0x8a,
+            Register::Bme680Coefficients2 => 0xe1,
+            Register::Bme680Coefficients3 => 0x00,
+            Register::Bme680ResHeat0 => 0x5a,
+            Register::Bme680GasWait0 => 0x64,
+            Register::Bme680CtrlGas1 => 0x71,
+            Register::Bme680CtrlHum => 0x72,
+            Register::Bme680CtrlMeas => 0x74,
+            Register::Bme680Config => 0x75,
+            Register::Bme680Data => 0x1d,
         }
     }
 }
 
+/// Uncompensated values of a single measurement
 #[derive(Clone, Copy, Debug)]
-pub enum Overscan {
-    X1,
-    X16,
+pub struct RawData {
+    pub pressure: u32,
+    pub temperature: u32,
+    pub humidity: u16,
+    /// Only measured by the BME680
+    pub gas: Option<RawGas>,
 }
 
-impl From<Overscan> for u8 {
-    fn from(o: Overscan) -> Self {
-        match o {
-            Overscan::X1 => 0x01,
-            Overscan::X16 => 0x05,
+fn raw20(msb: u8, lsb: u8, xlsb: u8) -> u32 {
+    ((msb as u32) << 12) | ((lsb as u32) << 4) | ((xlsb as u32) >> 4)
+}
+
+impl From<[u8; 8]> for RawData {
+    fn from(buf: [u8; 8]) -> Self {
+        RawData {
+            pressure: raw20(buf[0], buf[1], buf[2]),
+            temperature: raw20(buf[3], buf[4], buf[5]),
+            humidity: ((buf[6] as u16) << 8) | buf[7] as u16,
+            gas: None,
+        }
+    }
+}
+
+impl RawData {
+    /// From the BME680's data block starting at its measurement status
+    pub fn from_bme680(buf: [u8; 15]) -> Self {
+        RawData {
+            pressure: raw20(buf[2], buf[3], buf[4]),
+            temperature: raw20(buf[5], buf[6], buf[7]),
+            humidity: ((buf[8] as u16) << 8) | buf[9] as u16,
+            gas: Some(RawGas {
+                adc: ((buf[13] as u16) << 2) | (buf[14] >> 6) as u16,
+                range: buf[14] & 0x0f,
+                valid: buf[14] & 0x20 != 0,
+                heat_stable: buf[14] & 0x10 != 0,
+            }),
         }
     }
 }
@@ -76,11 +125,13 @@ impl From<Overscan> for u8 {
 pub struct AtmoI2c {
     pub i2c: Mutex<I2c>,
     pub mode: Mode,
-    pub calibration: Calibration,
-    pub overscan_humidity: Overscan,
-    pub overscan_temperature: Overscan,
-    pub overscan_pressure: Overscan,
+    pub chip: Chip,
+    pub calibration: ChipCalibration,
+    pub config: Bme280Config,
+    /// Only used by the BME680
+    pub heater: Heater,
     pub sea_level_pressure: f32,
+    pub user_calibration: UserCalibration,
 }
 
 #[derive(Debug, Clone)]
@@ -118,6 +169,8 @@ pub enum AtmoI2cInternalError {
     ChipId(#[source] AtmoI2cBaseError),
     #[error("Invalid calculating result")]
     Calculation,
+    #[error("Sensor still measuring after {0:?}")]
+    StatusTimeout(Duration),
     #[error(transparent)]
     BaseError(#[from] AtmoI2cBaseError),
 }
@@ -131,14 +184,18 @@ pub enum AtmoI2cError {
     Calibration(#[source] AtmoI2cBaseError),
     #[error("Could not write to config register")]
     Config(#[source] AtmoI2cInternalError),
-    #[error("Could not find BME280")]
+    #[error("Temperature can't be skipped, as pressure and humidity are compensated with it")]
+    TemperatureSkipped,
+    #[error("Unknown chip id {0:#04x}, expected a BMP280, BME280 or BME680")]
+    UnknownChip(u8),
+    #[error("Chip id changed after reset")]
     Unverified,
-    #[error("Could not read temperature")]
-    Temperature(#[source] AtmoI2cInternalError),
+    #[error("{0} has no normal mode, only forced measurements")]
+    NormalModeUnsupported(Chip),
+    #[error("Could not read measurement data")]
+    Data(#[source] AtmoI2cInternalError),
     #[error("Could not read pressure")]
     Pressure(#[source] AtmoI2cInternalError),
-    #[error("Could not read humidity")]
-    Humidity(#[source] AtmoI2cInternalError),
     #[error(transparent)]
     Internal(#[from] AtmoI2cInternalError),
 }
@@ -148,32 +205,41 @@ pub type InternalResult<T> = std::result::Result<T, AtmoI2cInternalError>;
 pub type BaseResult<T> = std::result::Result<T, AtmoI2cBaseError>;
 
 impl AtmoI2c {
-    pub const CHIP_ID: u8 = 0x60;
-    const DEFAULT_SEA_LEVEL_PRESSURE: f32 = 1013.25;
-
     pub fn new(addr: u16) -> Result<AtmoI2c> {
         let mut i2c = I2c::new().map_err(|_| I2cError::Initialization)?;
         i2c.set_slave_address(addr)
             .map_err(|_| I2cError::SlaveAddr(addr))?;
         let i2c_mutex = Mutex::new(i2c);
-        let calibration = Self::read_calibration(
-            &i2c_mutex
+        let (chip, calibration) = {
+            let guard = i2c_mutex
                 .lock()
                 .map_err(|_| AtmoI2cBaseError::Mutex)
-                .map_err(AtmoI2cInternalError::BaseError)?,
-        )?;
+                .map_err(AtmoI2cInternalError::BaseError)?;
+            let id = Self::read_byte_from(&guard, Register::ChipId)
+                .map_err(AtmoI2cInternalError::ChipId)?;
+            let chip = Chip::from_id(id).ok_or(AtmoI2cError::UnknownChip(id))?;
+            (chip, Self::read_calibration(&guard, chip)?)
+        };
         let mut res = AtmoI2c {
             i2c: i2c_mutex,
             mode: Mode::Sleep,
+            chip,
             calibration,
-            overscan_humidity: Overscan::X1,
-            overscan_temperature: Overscan::X1,
-            overscan_pressure: Overscan::X16,
-            sea_level_pressure: Self::DEFAULT_SEA_LEVEL_PRESSURE,
+            config: Bme280Config::default(),
+            heater: Heater::default(),
+            sea_level_pressure: STANDARD_PRESSURE,
+            user_calibration: UserCalibration::default(),
         };
         res.reset_sensor()?;
-        res.write_ctrl_meas()?;
-        res.write_config()?;
+        let mode = if chip.has_normal_mode() {
+            SensorMode::Normal
+        } else {
+            SensorMode::Forced
+        };
+        res.set_config(Bme280Config {
+            mode,
+            ..Bme280Config::default()
+        })?;
         if !res.verify_id()? {
             Err(AtmoI2cError::Unverified)
         } else {
@@ -214,14 +280,6 @@ impl AtmoI2c {
         self.read_register(register, |buf| buf[0])
     }
 
-    pub fn read24(&self, register: Register) -> BaseResult<f32> {
-        Self::read_register_from(&self.lock_i2c()?, register, |buf| {
-            IntoIterator::into_iter(buf)
-                .take(3)
-                .fold(0.0, |acc, b| (acc * 256.0) + b as f32)
-        })
-    }
-
     pub fn write_register_to(
         i2c_guard: &MutexGuard<I2c>,
         register: Register,
@@ -244,17 +302,31 @@ impl AtmoI2c {
         Self::write_byte_to(&self.lock_i2c()?, register, byte)
     }
 
-    pub fn status_ok(guard: &MutexGuard<I2c>) -> BaseResult<bool> {
-        Self::read_byte_from(guard, Register::Status).map(|status| ((status & 0x8) >> 3) != 1)
+    pub fn status_ok(guard: &MutexGuard<I2c>, chip: Chip) -> BaseResult<bool> {
+        match chip {
+            // neither measuring nor gas_measuring
+            Chip::Bme680 => {
+                Self::read_byte_from(guard, Register::Bme680Data).map(|status| status & 0x60 == 0)
+            }
+            _ => Self::read_byte_from(guard, Register::Status)
+                .map(|status| ((status & 0x8) >> 3) != 1),
+        }
     }
 
     fn write_ctrl_meas(&mut self) -> InternalResult<()> {
-        self.write_byte(Register::CtrlHum, self.overscan_humidity.into())
-            .map_err(AtmoI2cInternalError::ControlMeasure)?;
+        let (ctrl_hum, ctrl_meas) = match self.chip {
+            Chip::Bme680 => (Register::Bme680CtrlHum, Register::Bme680CtrlMeas),
+            _ => (Register::CtrlHum, Register::CtrlMeas),
+        };
+        // humidity oversampling only takes effect once ctrl_meas is written after it
+        if self.chip.has_humidity() {
+            self.write_byte(ctrl_hum, self.config.humidity.into())
+                .map_err(AtmoI2cInternalError::ControlMeasure)?;
+        }
         self.write_byte(
-            Register::CtrlMeas,
-            (u8::from(self.overscan_temperature) << 5)
-                + (u8::from(self.overscan_pressure) << 2)
+            ctrl_meas,
+            (u8::from(self.config.temperature) << 5)
+                + (u8::from(self.config.pressure) << 2)
                 + u8::from(self.mode),
         )
         .map_err(AtmoI2cInternalError::ControlMeasure)?;
@@ -269,19 +341,31 @@ impl AtmoI2c {
         })
     }
 
+    /// Writes the standby time and filter, which the sensor may ignore unless it is asleep
     pub fn write_config(&mut self) -> Result<()> {
         let normal = self.mode == Mode::Normal;
         if normal {
             self.set_mode(Mode::Sleep).map_err(AtmoI2cError::Config)?;
         }
-        self.write_byte(
-            Register::Config,
-            if self.mode == Mode::Normal {
-                0x02 << 5
-            } else {
-                0
-            },
-        )
+        match &self.calibration {
+            ChipCalibration::Bme680(calibration) => {
+                // no standby time, and the filter codes give coefficients of 1, 3, 7 and 15
+                let res_heat = calibration.heater_resistance(&self.heater, 25.0);
+                [
+                    (Register::Bme680Config, u8::from(self.config.filter) << 2),
+                    (Register::Bme680ResHeat0, res_heat),
+                    (Register::Bme680GasWait0, self.heater.wait_code()),
+                    // run_gas with heater set-point 0
+                    (Register::Bme680CtrlGas1, 0x10),
+                ]
+                .iter()
+                .try_for_each(|(register, byte)| self.write_byte(*register, *byte))
+            }
+            ChipCalibration::Bme280(_) => self.write_byte(
+                Register::Config,
+                (u8::from(self.config.standby) << 5) + (u8::from(self.config.filter) << 2),
+            ),
+        }
         .map_err(AtmoI2cInternalError::BaseError)
         .map_err(AtmoI2cError::Config)?;
         if normal {
@@ -290,6 +374,41 @@ impl AtmoI2c {
         Ok(())
     }
 
+    /// Applies new sampling settings, leaving the sensor running if it should be in normal mode
+    pub fn set_config(&mut self, config: Bme280Config) -> Result<()> {
+        if config.temperature == Overscan::Skip
+            && (config.pressure != Overscan::Skip || config.humidity != Overscan::Skip)
+        {
+            return Err(AtmoI2cError::TemperatureSkipped);
+        }
+        if config.mode == SensorMode::Normal && !self.chip.has_normal_mode() {
+            return Err(AtmoI2cError::NormalModeUnsupported(self.chip));
+        }
+        self.set_mode(Mode::Sleep).map_err(AtmoI2cError::Config)?;
+        self.config = config;
+        self.write_config()?;
+        match config.mode {
+            SensorMode::Normal => self.set_mode(Mode::Normal),
+            // ctrl_meas still has to be written for new oversampling to take effect
+            SensorMode::Forced => self.set_mode(Mode::Sleep),
+        }
+        .map_err(AtmoI2cError::Config)
+    }
+
+    /// How often the sensor has new data while it free-runs in normal mode
+    pub fn data_interval(&self) -> Option<Duration> {
+        (self.mode == Mode::Normal)
+            .then(|| self.config.standby.duration() + self.config.max_measurement_time())
+    }
+
+    pub fn measures_pressure(&self) -> bool {
+        self.config.pressure != Overscan::Skip
+    }
+
+    pub fn measures_humidity(&self) -> bool {
+        self.chip.has_humidity() && self.config.humidity != Overscan::Skip
+    }
+
     pub fn set_sea_level_pressure(&mut self, sea_level_pressure: f32) {
         self.sea_level_pressure = sea_level_pressure;
     }
@@ -0,0 +1,183 @@
+use num_traits::clamp;
+use serde::{Deserialize, Serialize};
+
+/// Corrections for where a sensor sits, such as next to a Pi that warms it, applied after
+/// compensation and before anything is derived from the readings
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserCalibration {
+    /// Multiplies the temperature before the offset is added
+    pub temperature_scale: f32,
+    /// Celsius added to the temperature
+    pub temperature_offset: f32,
+    /// Multiplies the relative humidity before the offset is added
+    pub humidity_scale: f32,
+    /// Percent added to the relative humidity
+    pub humidity_offset: f32,
+    /// hPa added to the pressure
+    pub pressure_offset: f32,
+}
+
+impl Default for UserCalibration {
+    fn default() -> Self {
+        UserCalibration {
+            temperature_scale: 1.0,
+            temperature_offset: 0.0,
+            humidity_scale: 1.0,
+            humidity_offset: 0.0,
+            pressure_offset: 0.0,
+        }
+    }
+}
+
+impl UserCalibration {
+    /// Whether the temperature correction can be undone, which it can't with a scale of 0
+    pub fn is_valid(&self) -> bool {
+        self.temperature_scale.is_normal()
+    }
+
+    pub fn temperature(&self, celsius: f32) -> f32 {
+        celsius * self.temperature_scale + self.temperature_offset
+    }
+
+    /// Undoes the temperature correction, giving what the sensor itself measured
+    pub fn uncorrected_temperature(&self, celsius: f32) -> f32 {
+        (celsius - self.temperature_offset) / self.temperature_scale
+    }
+
+    pub fn humidity(&self, percent: f32) -> f32 {
+        clamp(
+            percent * self.humidity_scale + self.humidity_offset,
+            0.0,
+            100.0,
+        )
+    }
+
+    pub fn pressure(&self, hpa: f32) -> f32 {
+        hpa + self.pressure_offset
+    }
+}
+
+/// What the sensor measured while a reference instrument showed `reference`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CalibrationPoint {
+    pub measured: f32,
+    pub reference: f32,
+}
+
+impl CalibrationPoint {
+    /// Offset mapping the measurement onto the reference, with a scale of 1
+    pub fn offset(&self) -> (f32, f32) {
+        (1.0, self.reference - self.measured)
+    }
+
+    /// Scale and offset mapping both measurements onto their references, or `None` if they
+    /// were measured too close together to tell a scale apart from noise, or the references
+    /// are the same so there's no scale at all
+    pub fn two_point(&self, other: &CalibrationPoint) -> Option<(f32, f32)> {
+        let measured_span = other.measured - self.measured;
+        if measured_span.abs() < MIN_SPAN {
+            return None;
+        }
+        let scale = (other.reference - self.reference) / measured_span;
+        if !scale.is_normal() {
+            return None;
+        }
+        Some((scale, self.reference - self.measured * scale))
+    }
+}
+
+/// Least difference between two measurements for a two-point calibration
+const MIN_SPAN: f32 = 2.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_and_undoes_corrections() {
+        let calibration = UserCalibration {
+            temperature_scale: 0.9,
+            temperature_offset: -2.0,
+            humidity_scale: 1.1,
+            humidity_offset: 3.0,
+            pressure_offset: 0.5,
+        };
+        assert!((calibration.temperature(30.0) - 25.0).abs() < 1e-4);
+        assert!((calibration.uncorrected_temperature(25.0) - 30.0).abs() < 1e-4);
+        assert!((calibration.humidity(50.0) - 58.0).abs() < 1e-4);
+        assert_eq!(calibration.pressure(1000.0), 1000.5);
+        assert_eq!(UserCalibration::default().temperature(21.3), 21.3);
+    }
+
+    #[test]
+    fn humidity_is_clamped() {
+        let calibration = UserCalibration {
+            humidity_offset: 10.0,
+            ..UserCalibration::default()
+        };
+        assert_eq!(calibration.humidity(95.0), 100.0);
+        let calibration = UserCalibration {
+            humidity_offset: -10.0,
+            ..UserCalibration::default()
+        };
+        assert_eq!(calibration.humidity(5.0), 0.0);
+    }
+
+    #[test]
+    fn zero_temperature_scale_is_invalid() {
+        assert!(UserCalibration::default().is_valid());
+        let zero = UserCalibration {
+            temperature_scale: 0.0,
+            ..UserCalibration::default()
+        };
+        assert!(!zero.is_valid());
+    }
+
+    #[test]
+    fn one_point_offsets() {
+        let point = CalibrationPoint {
+            measured: 22.0,
+            reference: 20.0,
+        };
+        assert_eq!(point.offset(), (1.0, -2.0));
+    }
+
+    #[test]
+    fn two_points_map_both_measurements_onto_their_references() {
+        let first = CalibrationPoint {
+            measured: 22.0,
+            reference: 20.0,
+        };
+        let second = CalibrationPoint {
+            measured: 32.0,
+            reference: 29.0,
+        };
+        let (scale, offset) = first.two_point(&second).unwrap();
+        assert!((scale - 0.9).abs() < 1e-5);
+        assert!((22.0 * scale + offset - 20.0).abs() < 1e-4);
+        assert!((32.0 * scale + offset - 29.0).abs() < 1e-4);
+        // the same either way round
+        let (reversed_scale, reversed_offset) = second.two_point(&first).unwrap();
+        assert!((reversed_scale - scale).abs() < 1e-5);
+        assert!((reversed_offset - offset).abs() < 1e-4);
+    }
+
+    #[test]
+    fn two_points_need_a_span_and_a_scale() {
+        let first = CalibrationPoint {
+            measured: 22.0,
+            reference: 20.0,
+        };
+        let close = CalibrationPoint {
+            measured: 22.0 + MIN_SPAN - 0.1,
+            reference: 21.0,
+        };
+        assert_eq!(first.two_point(&close), None);
+        let same_reference = CalibrationPoint {
+            measured: 30.0,
+            reference: 20.0,
+        };
+        assert_eq!(first.two_point(&same_reference), None);
+    }
+}
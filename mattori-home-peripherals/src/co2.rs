@@ -0,0 +1,358 @@
+use std::thread::sleep;
+
+use rppal::i2c::I2c;
+use thiserror::Error;
+use tokio::time::Duration;
+
+use crate::atmosphere::sensor::climate_reading;
+use crate::atmosphere::user_calibration::UserCalibration;
+use crate::atmosphere::{AtmosphereFeatures, Reading};
+use crate::{I2cError, RppalError};
+
+pub mod scd30;
+pub mod scd4x;
+
+pub const SCD30_ADDR: u16 = 0x61;
+
+pub const SCD4X_ADDR: u16 = 0x62;
+
+/// Raw reads and writes to a single i2c device, which tests can simulate
+pub trait I2cBus {
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), RppalError>;
+
+    fn read(&mut self, buffer: &mut [u8]) -> std::result::Result<(), RppalError>;
+}
+
+impl I2cBus for I2c {
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), RppalError> {
+        match I2c::write(self, bytes)? {
+            written if written == bytes.len() => Ok(()),
+            _ => Err(RppalError::Io),
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> std::result::Result<(), RppalError> {
+        match I2c::read(self, buffer)? {
+            read if read == buffer.len() => Ok(()),
+            _ => Err(RppalError::Io),
+        }
+    }
+}
+
+/// Opens the i2c bus to the device at `addr`
+pub fn open_i2c(addr: u16) -> std::result::Result<I2c, I2cError> {
+    let mut i2c = I2c::new().map_err(|_| I2cError::Initialization)?;
+    i2c.set_slave_address(addr)
+        .map_err(|_| I2cError::SlaveAddr(addr))?;
+    Ok(i2c)
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum Co2Error {
+    #[error(transparent)]
+    I2c(#[from] I2cError),
+    #[error("Could not send command {0:#06x}")]
+    Write(u16, #[source] RppalError),
+    #[error("Could not read response to command {0:#06x}")]
+    Read(u16, #[source] RppalError),
+    #[error("Checksum of response to command {command:#06x} was {actual:#04x}, expected {expected:#04x}")]
+    Crc {
+        command: u16,
+        expected: u8,
+        actual: u8,
+    },
+    #[error("Reference concentration {0} ppm is outside of {1} to {2} ppm")]
+    ReferenceOutOfRange(u16, u16, u16),
+    #[error(
+        "Forced recalibration failed, the sensor may not have measured long enough beforehand"
+    )]
+    RecalibrationFailed,
+}
+
+pub type Result<T> = std::result::Result<T, Co2Error>;
+
+/// CRC-8 that Sensirion sensors append to every 16 bit word, with polynomial 0x31 and
+/// initialization 0xff
+pub fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0xff, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Sensirion's i2c framing, where commands are 16 bits and every word they take or return is
+/// followed by its checksum
+#[derive(Debug)]
+pub(crate) struct Sensirion<B> {
+    bus: B,
+}
+
+impl<B: I2cBus> Sensirion<B> {
+    pub fn new(bus: B) -> Self {
+        Sensirion { bus }
+    }
+
+    pub fn command(&mut self, command: u16) -> Result<()> {
+        self.bus
+            .write(&command.to_be_bytes())
+            .map_err(|source| Co2Error::Write(command, source))
+    }
+
+    pub fn command_with_arg(&mut self, command: u16, arg: u16) -> Result<()> {
+        let [command_msb, command_lsb] = command.to_be_bytes();
+        let [arg_msb, arg_lsb] = arg.to_be_bytes();
+        self.bus
+            .write(&[
+                command_msb,
+                command_lsb,
+                arg_msb,
+                arg_lsb,
+                crc8(&[arg_msb, arg_lsb]),
+            ])
+            .map_err(|source| Co2Error::Write(command, source))
+    }
+
+    /// Sends the command, then reads `N` words once the sensor had `delay` to process it
+    pub fn read_words<const N: usize>(
+        &mut self,
+        command: u16,
+        delay: Duration,
+    ) -> Result<[u16; N]> {
+        self.command(command)?;
+        sleep(delay);
+        self.read_response(command)
+    }
+
+    /// Reads `N` words the sensor is ready to give in response to `command`
+    pub fn read_response<const N: usize>(&mut self, command: u16) -> Result<[u16; N]> {
+        let mut buf = vec![0u8; N * 3];
+        self.bus
+            .read(&mut buf)
+            .map_err(|source| Co2Error::Read(command, source))?;
+        let mut words = [0u16; N];
+        for (word, chunk) in words.iter_mut().zip(buf.chunks_exact(3)) {
+            let expected = crc8(&chunk[..2]);
+            if chunk[2] != expected {
+                return Err(Co2Error::Crc {
+                    command,
+                    expected,
+                    actual: chunk[2],
+                });
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
+
+/// Checks a forced recalibration reference against what the sensor accepts
+fn check_reference(reference: u16, min: u16, max: u16) -> Result<u16> {
+    if (min..=max).contains(&reference) {
+        Ok(reference)
+    } else {
+        Err(Co2Error::ReferenceOutOfRange(reference, min, max))
+    }
+}
+
+/// Both sensors measure temperature and humidity alongside CO2, but not pressure
+fn available_features() -> AtmosphereFeatures {
+    AtmosphereFeatures {
+        pressure: false,
+        altitude: false,
+        gas: false,
+        ..AtmosphereFeatures::default()
+    }
+}
+
+/// One measurement, in ppm, celsius and percent relative humidity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub co2: f32,
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+impl Measurement {
+    fn reading(&self, features: &AtmosphereFeatures, calibration: &UserCalibration) -> Reading {
+        Reading {
+            co2: Some(self.co2).filter(|_| features.co2),
+            ..climate_reading(
+                features,
+                calibration.temperature(self.temperature),
+                Some(calibration.humidity(self.humidity)),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+
+    use super::scd30::Scd30;
+    use super::scd4x::Scd4x;
+    use super::*;
+
+    #[derive(Default)]
+    struct Device {
+        writes: Vec<Vec<u8>>,
+        responses: HashMap<u16, VecDeque<Vec<u16>>>,
+        last_command: u16,
+        corrupt: bool,
+    }
+
+    /// Answers each command with the words queued for it, checksummed as a sensor would
+    #[derive(Clone, Default)]
+    struct FakeBus(Arc<Mutex<Device>>);
+
+    impl FakeBus {
+        fn respond(&self, command: u16, words: &[u16]) {
+            self.0
+                .lock()
+                .unwrap()
+                .responses
+                .entry(command)
+                .or_default()
+                .push_back(words.to_vec());
+        }
+
+        fn writes(&self) -> Vec<Vec<u8>> {
+            self.0.lock().unwrap().writes.clone()
+        }
+
+        fn corrupt(&self) {
+            self.0.lock().unwrap().corrupt = true;
+        }
+    }
+
+    impl I2cBus for FakeBus {
+        fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), RppalError> {
+            let mut device = self.0.lock().unwrap();
+            device.last_command = u16::from_be_bytes([bytes[0], bytes[1]]);
+            device.writes.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) -> std::result::Result<(), RppalError> {
+            let mut device = self.0.lock().unwrap();
+            let command = device.last_command;
+            let words = device
+                .responses
+                .get_mut(&command)
+                .and_then(|responses| responses.pop_front())
+                .ok_or(RppalError::Io)?;
+            assert_eq!(words.len() * 3, buffer.len());
+            for (chunk, word) in buffer.chunks_exact_mut(3).zip(words) {
+                let [msb, lsb] = word.to_be_bytes();
+                chunk.copy_from_slice(&[msb, lsb, crc8(&[msb, lsb]) ^ device.corrupt as u8]);
+            }
+            Ok(())
+        }
+    }
+
+    fn float_words(value: f32) -> [u16; 2] {
+        [(value.to_bits() >> 16) as u16, value.to_bits() as u16]
+    }
+
+    #[test]
+    fn crc_matches_the_datasheet() {
+        assert_eq!(crc8(&[0xbe, 0xef]), 0x92);
+    }
+
+    #[test]
+    fn arguments_are_checksummed() {
+        let bus = FakeBus::default();
+        Sensirion::new(bus.clone())
+            .command_with_arg(0x362f, 420)
+            .unwrap();
+        assert_eq!(bus.writes(), vec![vec![0x36, 0x2f, 0x01, 0xa4, 0x4d]]);
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        let bus = FakeBus::default();
+        bus.respond(0xe4b8, &[0xbeef]);
+        bus.corrupt();
+        let mut sensirion = Sensirion::new(bus);
+        sensirion.command(0xe4b8).unwrap();
+        assert!(matches!(
+            sensirion.read_response::<1>(0xe4b8),
+            Err(Co2Error::Crc {
+                command: 0xe4b8,
+                expected: 0x92,
+                actual: 0x93,
+            })
+        ));
+    }
+
+    #[test]
+    fn scd30_decodes_float_words() {
+        let bus = FakeBus::default();
+        let mut scd30 = Scd30::with_bus(bus.clone(), Duration::from_secs(2)).unwrap();
+        assert_eq!(bus.writes()[0], vec![0x46, 0x00, 0x00, 0x02, crc8(&[0, 2])]);
+
+        bus.respond(0x0202, &[0]);
+        assert_eq!(scd30.measure().unwrap(), None);
+
+        let [co2, temperature, humidity] = [439.0, 27.25, 48.8].map(float_words);
+        bus.respond(0x0202, &[1]);
+        bus.respond(0x0300, &[co2, temperature, humidity].concat());
+        assert_eq!(
+            scd30.measure().unwrap(),
+            Some(Measurement {
+                co2: 439.0,
+                temperature: 27.25,
+                humidity: 48.8,
+            })
+        );
+    }
+
+    #[test]
+    fn scd4x_masks_data_ready_and_converts() {
+        let bus = FakeBus::default();
+        let mut scd4x = Scd4x::with_bus(bus.clone()).unwrap();
+
+        // only the lower 11 bits say whether data is ready
+        bus.respond(0xe4b8, &[0x8000]);
+        assert!(!scd4x.data_ready().unwrap());
+        bus.respond(0xe4b8, &[0x8006]);
+        assert!(scd4x.data_ready().unwrap());
+
+        // example from the datasheet
+        bus.respond(0xe4b8, &[0x0001]);
+        bus.respond(0xec05, &[0x01f4, 0x6667, 0x5eb9]);
+        let measurement = scd4x.measure().unwrap().unwrap();
+        assert_eq!(measurement.co2, 500.0);
+        assert!((measurement.temperature - 25.0).abs() < 0.01);
+        assert!((measurement.humidity - 37.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn scd4x_forced_recalibration() {
+        let bus = FakeBus::default();
+        let mut scd4x = Scd4x::with_bus(bus.clone()).unwrap();
+
+        bus.respond(0x362f, &[0x7fce]);
+        assert_eq!(scd4x.force_recalibration(420).unwrap(), -50);
+        let writes = bus.writes();
+        assert_eq!(writes[writes.len() - 2][..4], [0x36, 0x2f, 0x01, 0xa4]);
+        // measuring again afterwards
+        assert_eq!(writes[writes.len() - 1], vec![0x21, 0xb1]);
+
+        bus.respond(0x362f, &[0xffff]);
+        assert!(matches!(
+            scd4x.force_recalibration(420),
+            Err(Co2Error::RecalibrationFailed)
+        ));
+        assert!(matches!(
+            scd4x.force_recalibration(100),
+            Err(Co2Error::ReferenceOutOfRange(100, _, _))
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use std::thread::sleep;
+
+use rppal::i2c::I2c;
+use tokio::time::Duration;
+
+use crate::atmosphere::sensor::Sensor;
+use crate::atmosphere::user_calibration::UserCalibration;
+use crate::atmosphere::{AtmosphereFeatures, Reading};
+use crate::co2::{
+    available_features, check_reference, open_i2c, I2cBus, Measurement, Result, Sensirion,
+    SCD30_ADDR,
+};
+
+const START_CONTINUOUS: u16 = 0x0010;
+const MEASUREMENT_INTERVAL: u16 = 0x4600;
+const DATA_READY: u16 = 0x0202;
+const READ_MEASUREMENT: u16 = 0x0300;
+const FORCED_RECALIBRATION: u16 = 0x5204;
+const SOFT_RESET: u16 = 0xd304;
+
+/// Time the SCD30 needs between a command and reading its response
+const READ_DELAY: Duration = Duration::from_millis(3);
+/// Time the SCD30 takes to boot after a reset
+const RESET_DELAY: Duration = Duration::from_secs(2);
+
+/// Sensirion's NDIR CO2 sensor, which measures continuously at a configurable interval
+#[derive(Debug)]
+pub struct Scd30<B = I2c> {
+    sensirion: Sensirion<B>,
+    interval: Duration,
+    last: Option<Measurement>,
+    calibration: UserCalibration,
+}
+
+impl Scd30 {
+    pub fn new(addr: u16, interval: Duration) -> Result<Scd30> {
+        Scd30::with_bus(open_i2c(addr)?, interval)
+    }
+
+    pub fn default_addr(interval: Duration) -> Result<Scd30> {
+        Scd30::new(SCD30_ADDR, interval)
+    }
+}
+
+impl<B: I2cBus> Scd30<B> {
+    /// Starts continuous measurement every `interval`, kept within the 2 to 1800 seconds the
+    /// sensor supports
+    pub fn with_bus(bus: B, interval: Duration) -> Result<Scd30<B>> {
+        let mut scd30 = Scd30 {
+            sensirion: Sensirion::new(bus),
+            interval: Duration::from_secs(interval.as_secs().clamp(2, 1800)),
+            last: None,
+            calibration: UserCalibration::default(),
+        };
+        scd30.start()?;
+        Ok(scd30)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.sensirion
+            .command_with_arg(MEASUREMENT_INTERVAL, self.interval.as_secs() as u16)?;
+        // without ambient pressure compensation
+        self.sensirion.command_with_arg(START_CONTINUOUS, 0)
+    }
+
+    /// Restarts the sensor and measuring, dropping the last measurement
+    pub fn reset(&mut self) -> Result<()> {
+        self.sensirion.command(SOFT_RESET)?;
+        sleep(RESET_DELAY);
+        self.last = None;
+        self.start()
+    }
+
+    pub fn data_ready(&mut self) -> Result<bool> {
+        let [ready] = self.sensirion.read_words(DATA_READY, READ_DELAY)?;
+        Ok(ready == 1)
+    }
+
+    /// The latest measurement, or the one before if no new data is ready yet
+    pub fn measure(&mut self) -> Result<Option<Measurement>> {
+        if self.data_ready()? {
+            // each value is a big endian float split over two words
+            let words: [u16; 6] = self.sensirion.read_words(READ_MEASUREMENT, READ_DELAY)?;
+            let float = |i: usize| f32::from_bits(((words[i] as u32) << 16) | words[i + 1] as u32);
+            self.last = Some(Measurement {
+                co2: float(0),
+                temperature: float(2),
+                humidity: float(4),
+            });
+        }
+        Ok(self.last)
+    }
+
+    /// Sets the current measurement to `reference` ppm, between 400 and 2000
+    pub fn force_recalibration(&mut self, reference: u16) -> Result<()> {
+        let reference = check_reference(reference, 400, 2000)?;
+        self.sensirion
+            .command_with_arg(FORCED_RECALIBRATION, reference)
+    }
+}
+
+impl<B: I2cBus + Send + 'static> Sensor for Scd30<B> {
+    fn model(&self) -> String {
+        "SCD30".to_string()
+    }
+
+    fn available_features(&self) -> AtmosphereFeatures {
+        available_features()
+    }
+
+    fn read(&mut self, features: &AtmosphereFeatures) -> crate::atmosphere::Result<Reading> {
+        if !features.temperature_enabled() && !features.co2 {
+            trace!("no features enabled");
+            return Ok(Reading::empty());
+        }
+        let measurement = self.measure()?;
+        trace!("read measurement: {:?}", measurement);
+        Ok(measurement.map_or_else(Reading::empty, |measurement| {
+            measurement.reading(features, &self.calibration)
+        }))
+    }
+
+    fn data_interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    fn set_user_calibration(&mut self, calibration: UserCalibration) {
+        self.calibration = calibration;
+    }
+
+    fn force_recalibration(&mut self, reference_ppm: u16) -> crate::atmosphere::Result<()> {
+        Ok(Scd30::force_recalibration(self, reference_ppm)?)
+    }
+
+    fn reset(&mut self) -> crate::atmosphere::Result<()> {
+        Ok(Scd30::reset(self)?)
+    }
+}
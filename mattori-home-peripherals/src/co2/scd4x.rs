@@ -0,0 +1,150 @@
+use std::thread::sleep;
+
+use rppal::i2c::I2c;
+use tokio::time::Duration;
+
+use crate::atmosphere::sensor::Sensor;
+use crate::atmosphere::user_calibration::UserCalibration;
+use crate::atmosphere::{AtmosphereFeatures, Reading};
+use crate::co2::{
+    available_features, check_reference, open_i2c, Co2Error, I2cBus, Measurement, Result,
+    Sensirion, SCD4X_ADDR,
+};
+
+const START_PERIODIC: u16 = 0x21b1;
+const STOP_PERIODIC: u16 = 0x3f86;
+const DATA_READY: u16 = 0xe4b8;
+const READ_MEASUREMENT: u16 = 0xec05;
+const FORCED_RECALIBRATION: u16 = 0x362f;
+const REINIT: u16 = 0x3646;
+
+/// Time the SCD4x needs between most commands and reading their response
+const READ_DELAY: Duration = Duration::from_millis(1);
+/// Time the SCD4x needs to finish its current measurement after being stopped
+const STOP_DELAY: Duration = Duration::from_millis(500);
+const RECALIBRATION_DELAY: Duration = Duration::from_millis(400);
+const REINIT_DELAY: Duration = Duration::from_millis(20);
+/// How often the SCD4x measures in periodic mode
+const PERIOD: Duration = Duration::from_secs(5);
+
+/// Sensirion's photoacoustic CO2 sensors, the SCD40 and SCD41, which measure every 5 seconds
+#[derive(Debug)]
+pub struct Scd4x<B = I2c> {
+    sensirion: Sensirion<B>,
+    last: Option<Measurement>,
+    calibration: UserCalibration,
+}
+
+impl Scd4x {
+    pub fn new(addr: u16) -> Result<Scd4x> {
+        Scd4x::with_bus(open_i2c(addr)?)
+    }
+
+    pub fn default_addr() -> Result<Scd4x> {
+        Scd4x::new(SCD4X_ADDR)
+    }
+}
+
+impl<B: I2cBus> Scd4x<B> {
+    /// Starts periodic measurement, stopping it first in case it was left running, since the
+    /// sensor ignores most commands while measuring
+    pub fn with_bus(bus: B) -> Result<Scd4x<B>> {
+        let mut scd4x = Scd4x {
+            sensirion: Sensirion::new(bus),
+            last: None,
+            calibration: UserCalibration::default(),
+        };
+        scd4x.sensirion.command(STOP_PERIODIC)?;
+        sleep(STOP_DELAY);
+        scd4x.sensirion.command(START_PERIODIC)?;
+        Ok(scd4x)
+    }
+
+    /// Reloads the sensor's settings from its EEPROM and starts measuring again, dropping the
+    /// last measurement
+    pub fn reset(&mut self) -> Result<()> {
+        self.sensirion.command(STOP_PERIODIC)?;
+        sleep(STOP_DELAY);
+        self.sensirion.command(REINIT)?;
+        sleep(REINIT_DELAY);
+        self.last = None;
+        self.sensirion.command(START_PERIODIC)
+    }
+
+    pub fn data_ready(&mut self) -> Result<bool> {
+        let [status] = self.sensirion.read_words(DATA_READY, READ_DELAY)?;
+        Ok(status & 0x07ff != 0)
+    }
+
+    /// The latest measurement, or the one before if no new data is ready yet
+    pub fn measure(&mut self) -> Result<Option<Measurement>> {
+        if self.data_ready()? {
+            let [co2, temperature, humidity] =
+                self.sensirion.read_words(READ_MEASUREMENT, READ_DELAY)?;
+            self.last = Some(Measurement {
+                co2: co2 as f32,
+                temperature: -45.0 + 175.0 * temperature as f32 / 65535.0,
+                humidity: 100.0 * humidity as f32 / 65535.0,
+            });
+        }
+        Ok(self.last)
+    }
+
+    /// Sets the current measurement to `reference` ppm, pausing periodic measurement to do so,
+    /// and returns the correction in ppm
+    pub fn force_recalibration(&mut self, reference: u16) -> Result<i32> {
+        let reference = check_reference(reference, 400, 2000)?;
+        self.sensirion.command(STOP_PERIODIC)?;
+        sleep(STOP_DELAY);
+        self.sensirion
+            .command_with_arg(FORCED_RECALIBRATION, reference)?;
+        sleep(RECALIBRATION_DELAY);
+        let correction = self.sensirion.read_response(FORCED_RECALIBRATION);
+        // measure again even if recalibration failed
+        self.sensirion.command(START_PERIODIC)?;
+        match correction? {
+            [0xffff] => Err(Co2Error::RecalibrationFailed),
+            [correction] => Ok(correction as i32 - 0x8000),
+        }
+    }
+}
+
+impl<B: I2cBus + Send + 'static> Sensor for Scd4x<B> {
+    fn model(&self) -> String {
+        "SCD4x".to_string()
+    }
+
+    fn available_features(&self) -> AtmosphereFeatures {
+        available_features()
+    }
+
+    fn read(&mut self, features: &AtmosphereFeatures) -> crate::atmosphere::Result<Reading> {
+        if !features.temperature_enabled() && !features.co2 {
+            trace!("no features enabled");
+            return Ok(Reading::empty());
+        }
+        let measurement = self.measure()?;
+        trace!("read measurement: {:?}", measurement);
+        Ok(measurement.map_or_else(Reading::empty, |measurement| {
+            measurement.reading(features, &self.calibration)
+        }))
+    }
+
+    fn data_interval(&self) -> Option<Duration> {
+        Some(PERIOD)
+    }
+
+    fn set_user_calibration(&mut self, calibration: UserCalibration) {
+        self.calibration = calibration;
+    }
+
+    fn force_recalibration(&mut self, reference_ppm: u16) -> crate::atmosphere::Result<()> {
+        let correction = Scd4x::force_recalibration(self, reference_ppm)?;
+        info!("SCD4x recalibrated by {} ppm", correction);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> crate::atmosphere::Result<()> {
+        Ok(Scd4x::reset(self)?)
+    }
+}
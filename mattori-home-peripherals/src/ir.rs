@@ -1,5 +1,11 @@
+pub mod dynamic;
 pub mod format;
+pub mod fujitsu;
 pub mod input;
+pub mod mitsubishi;
 pub mod output;
+pub mod panasonic;
+pub mod remote;
 pub mod sanyo;
+pub mod scene;
 pub mod types;
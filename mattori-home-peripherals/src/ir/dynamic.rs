@@ -0,0 +1,339 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+
+use thiserror::Error;
+
+use crate::ir::fujitsu::Fujitsu;
+use crate::ir::mitsubishi::Mitsubishi;
+use crate::ir::panasonic::Panasonic;
+use crate::ir::sanyo::Sanyo;
+use crate::ir::types::{
+    ACMode, IrDecodeError, IrEncodeError, IrFormat, IrFraming, IrPulseBytes, IrSequence, IrStatus,
+    IrTarget, IrTargetFormat, Temperature, TemperatureCode, TemperatureRange,
+};
+
+#[derive(Error, Clone, Debug)]
+pub enum AnyIrTargetError {
+    #[error("Temperature {0} out of range {1}")]
+    TemperatureRange(Temperature, TemperatureRange),
+    #[error("{0}")]
+    Target(String),
+}
+
+impl AnyIrTargetError {
+    fn target<E: Display>(e: E) -> Self {
+        AnyIrTargetError::Target(e.to_string())
+    }
+}
+
+type DynResult<T> = Result<T, AnyIrTargetError>;
+
+/// Object safe mirror of `IrTarget`, with temperatures as plain `Temperature`s
+trait DynIrTarget: Debug + Send + Sync {
+    fn framing(&self) -> IrFraming;
+    fn encode(&self, bytes: &[u8]) -> Result<IrSequence, IrEncodeError>;
+    fn decode(&self, seq: &IrSequence) -> Result<IrPulseBytes, IrDecodeError>;
+    fn temperature_range(&self) -> TemperatureRange;
+    fn power_off(&mut self) -> DynResult<IrSequence>;
+    fn power_on(&mut self) -> DynResult<IrSequence>;
+    fn is_powered(&self) -> bool;
+    fn temp_up(&mut self) -> DynResult<IrSequence>;
+    fn temp_down(&mut self) -> DynResult<IrSequence>;
+    fn temp_set(&mut self, temp: Temperature) -> Option<DynResult<IrSequence>>;
+    fn temperature(&self) -> Temperature;
+    fn mode_set(&mut self, mode: ACMode) -> DynResult<IrSequence>;
+    fn mode(&self) -> &ACMode;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T> DynIrTarget for T
+where
+    T: IrTargetFormat + Debug + Send + Sync + 'static,
+    T::Temperature: Clone,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    fn framing(&self) -> IrFraming {
+        IrTarget::framing(self)
+    }
+
+    fn encode(&self, bytes: &[u8]) -> Result<IrSequence, IrEncodeError> {
+        T::Format::encode(bytes)
+    }
+
+    fn decode(&self, seq: &IrSequence) -> Result<IrPulseBytes, IrDecodeError> {
+        T::Format::decode(seq)
+    }
+
+    fn temperature_range(&self) -> TemperatureRange {
+        IrTarget::temperature_range(self)
+    }
+
+    fn power_off(&mut self) -> DynResult<IrSequence> {
+        IrTarget::power_off(self).map_err(AnyIrTargetError::target)
+    }
+
+    fn power_on(&mut self) -> DynResult<IrSequence> {
+        IrTarget::power_on(self).map_err(AnyIrTargetError::target)
+    }
+
+    fn is_powered(&self) -> bool {
+        IrTarget::is_powered(self)
+    }
+
+    fn temp_up(&mut self) -> DynResult<IrSequence> {
+        IrTarget::temp_up(self).map_err(AnyIrTargetError::target)
+    }
+
+    fn temp_down(&mut self) -> DynResult<IrSequence> {
+        IrTarget::temp_down(self).map_err(AnyIrTargetError::target)
+    }
+
+    fn temp_set(&mut self, temp: Temperature) -> Option<DynResult<IrSequence>> {
+        match T::Temperature::try_from(temp) {
+            Ok(temp) => {
+                IrTarget::temp_set(self, temp).map(|res| res.map_err(AnyIrTargetError::target))
+            }
+            Err(_) => Some(Err(AnyIrTargetError::TemperatureRange(
+                temp,
+                IrTarget::temperature_range(self),
+            ))),
+        }
+    }
+
+    fn temperature(&self) -> Temperature {
+        IrTarget::temperature(self).clone().into()
+    }
+
+    fn mode_set(&mut self, mode: ACMode) -> DynResult<IrSequence> {
+        IrTarget::mode_set(self, mode).map_err(AnyIrTargetError::target)
+    }
+
+    fn mode(&self) -> &ACMode {
+        IrTarget::mode(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Temperature of a target only known at runtime, so range checks happen in the target itself
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct AnyTemperatureCode(pub Temperature);
+
+impl TemperatureCode for AnyTemperatureCode {}
+
+impl From<Temperature> for AnyTemperatureCode {
+    fn from(value: Temperature) -> Self {
+        AnyTemperatureCode(value)
+    }
+}
+
+impl From<AnyTemperatureCode> for Temperature {
+    fn from(code: AnyTemperatureCode) -> Self {
+        code.0
+    }
+}
+
+/// A target chosen at runtime, usually by name through an `IrTargetRegistry`
+#[derive(Debug)]
+pub struct AnyIrTarget {
+    name: &'static str,
+    inner: Box<dyn DynIrTarget>,
+    // kept alongside the inner target so it can be handed out by reference
+    temperature: AnyTemperatureCode,
+}
+
+impl AnyIrTarget {
+    pub fn new<T>(name: &'static str, target: T) -> AnyIrTarget
+    where
+        T: IrTargetFormat + Debug + Send + Sync + 'static,
+        T::Temperature: Clone,
+        <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+    {
+        let temperature = AnyTemperatureCode(DynIrTarget::temperature(&target));
+        AnyIrTarget {
+            name,
+            inner: Box::new(target),
+            temperature,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn encode<T: AsRef<[u8]>>(&self, bytes: T) -> Result<IrSequence, IrEncodeError> {
+        self.inner.encode(bytes.as_ref())
+    }
+
+    pub fn decode(&self, seq: &IrSequence) -> Result<IrPulseBytes, IrDecodeError> {
+        self.inner.decode(seq)
+    }
+
+    /// Converts `temp` into the target's unit and step, failing if it is out of range
+    pub fn check_temperature(&self, temp: Temperature) -> Result<Temperature, AnyIrTargetError> {
+        let range = self.inner.temperature_range();
+        range
+            .normalize(temp)
+            .ok_or(AnyIrTargetError::TemperatureRange(temp, range))
+    }
+
+    /// Access to the concrete target, for commands beyond what `IrTarget` covers
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.inner.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.inner.as_any_mut().downcast_mut()
+    }
+
+    fn sync_temperature<R>(&mut self, res: R) -> R {
+        self.temperature = AnyTemperatureCode(self.inner.temperature());
+        res
+    }
+}
+
+impl IrTarget for AnyIrTarget {
+    type Error = AnyIrTargetError;
+    type Temperature = AnyTemperatureCode;
+    // varies between targets, and is only known by the inner target
+    const SEQ_LENGTH: usize = 0;
+
+    fn framing(&self) -> IrFraming {
+        self.inner.framing()
+    }
+
+    fn temperature_range(&self) -> TemperatureRange {
+        self.inner.temperature_range()
+    }
+
+    fn power_off(&mut self) -> Result<IrSequence, Self::Error> {
+        self.inner.power_off()
+    }
+
+    fn power_on(&mut self) -> Result<IrSequence, Self::Error> {
+        self.inner.power_on()
+    }
+
+    fn is_powered(&self) -> bool {
+        self.inner.is_powered()
+    }
+
+    fn temp_up(&mut self) -> Result<IrSequence, Self::Error> {
+        let res = self.inner.temp_up();
+        self.sync_temperature(res)
+    }
+
+    fn temp_down(&mut self) -> Result<IrSequence, Self::Error> {
+        let res = self.inner.temp_down();
+        self.sync_temperature(res)
+    }
+
+    fn temp_set(&mut self, temp: Self::Temperature) -> Option<Result<IrSequence, Self::Error>> {
+        let temp = match self.check_temperature(temp.0) {
+            Ok(temp) => temp,
+            Err(e) => return Some(Err(e)),
+        };
+        let res = self.inner.temp_set(temp);
+        self.sync_temperature(res)
+    }
+
+    fn temperature(&self) -> &Self::Temperature {
+        &self.temperature
+    }
+
+    fn mode_set(&mut self, mode: ACMode) -> Result<IrSequence, Self::Error> {
+        self.inner.mode_set(mode)
+    }
+
+    fn mode(&self) -> &ACMode {
+        self.inner.mode()
+    }
+
+    fn status(&self) -> IrStatus<Self> {
+        IrStatus {
+            powered: self.inner.is_powered(),
+            mode: self.inner.mode().clone(),
+            temperature: self.temperature,
+        }
+    }
+}
+
+#[derive(Error, Clone, Debug)]
+#[error("Unknown ir target {0}, expected one of: {1}")]
+pub struct UnknownIrTarget(String, String);
+
+/// Targets that can be picked by name at runtime
+#[derive(Clone)]
+pub struct IrTargetRegistry {
+    targets: BTreeMap<&'static str, fn(&'static str) -> AnyIrTarget>,
+}
+
+impl IrTargetRegistry {
+    pub fn empty() -> IrTargetRegistry {
+        IrTargetRegistry {
+            targets: BTreeMap::new(),
+        }
+    }
+
+    pub fn register<T>(&mut self, name: &'static str)
+    where
+        T: IrTargetFormat + Default + Debug + Send + Sync + 'static,
+        T::Temperature: Clone,
+        <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+    {
+        self.targets
+            .insert(name, |name| AnyIrTarget::new(name, T::default()));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.targets.keys().copied()
+    }
+
+    pub fn create(&self, name: &str) -> Result<AnyIrTarget, UnknownIrTarget> {
+        self.targets
+            .get_key_value(name.to_lowercase().as_str())
+            .map(|(name, construct)| construct(name))
+            .ok_or_else(|| {
+                UnknownIrTarget(
+                    name.to_string(),
+                    self.names().collect::<Vec<_>>().join(", "),
+                )
+            })
+    }
+}
+
+impl Default for IrTargetRegistry {
+    fn default() -> Self {
+        let mut registry = IrTargetRegistry::empty();
+        registry.register::<Sanyo>("sanyo");
+        registry.register::<Mitsubishi>("mitsubishi");
+        registry.register::<Panasonic>("panasonic");
+        registry.register::<Fujitsu>("fujitsu");
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::mitsubishi::types::MitsubishiTemperatureCode;
+
+    #[test]
+    fn reports_the_inner_range() {
+        let mut target = IrTargetRegistry::default().create("mitsubishi").unwrap();
+        assert_eq!(target.temperature_range(), MitsubishiTemperatureCode::RANGE);
+        assert!(target
+            .temp_set(AnyTemperatureCode(Temperature::celsius(40)))
+            .unwrap()
+            .is_err());
+    }
+}
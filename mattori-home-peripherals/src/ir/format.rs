@@ -1,3 +1,3 @@
 mod aeha;
 
-pub use aeha::Aeha;
+pub use aeha::{Aeha, Kaseikyo};
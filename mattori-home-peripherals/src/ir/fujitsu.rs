@@ -0,0 +1,215 @@
+pub mod types;
+
+use thiserror::Error;
+
+use crate::ir::format::Aeha;
+use crate::ir::fujitsu::types::{
+    FujitsuCommand, FujitsuFan, FujitsuFrame, FujitsuState, FujitsuTemperatureCode,
+    STATE_FRAME_LENGTH,
+};
+use crate::ir::types::{
+    ACMode, IrDecodeError, IrEncodeError, IrFormat, IrSequence, IrStatus, IrTarget, IrTargetFormat,
+    TemperatureRange,
+};
+
+#[derive(Error, Clone, Debug)]
+pub enum FujitsuError {
+    #[error("Temperature out of range")]
+    TemperatureRange,
+    #[error("Could not encode ir sequence")]
+    EncodeError(#[from] IrEncodeError),
+    #[error("Could not decode ir sequence")]
+    DecodeError(#[from] IrDecodeError),
+    #[error("Not a valid Fujitsu frame")]
+    InvalidFrame,
+}
+
+/// Fujitsu General AC, which sends the whole state for settings but short frames for turning off
+/// and toggles
+#[derive(Debug)]
+pub struct Fujitsu {
+    state: FujitsuState,
+}
+
+impl Default for Fujitsu {
+    fn default() -> Self {
+        Fujitsu {
+            state: FujitsuState {
+                powered: false,
+                mode: ACMode::default(),
+                temperature: FujitsuTemperatureCode::default(),
+                fan: FujitsuFan::default(),
+                swing: false,
+            },
+        }
+    }
+}
+
+impl Fujitsu {
+    fn as_ir_sequence(
+        &self,
+        frame: FujitsuFrame,
+    ) -> Result<IrSequence, <Fujitsu as IrTarget>::Error> {
+        Ok(<Self as IrTargetFormat>::Format::encode(frame.to_bytes())?)
+    }
+
+    fn state_sequence(&self, turn_on: bool) -> Result<IrSequence, FujitsuError> {
+        self.as_ir_sequence(FujitsuFrame::State {
+            state: self.state.clone(),
+            turn_on,
+        })
+    }
+
+    /// State frames always leave the unit running, so changing a setting while it is off turns
+    /// it on as well
+    fn settings_sequence(&mut self) -> Result<IrSequence, FujitsuError> {
+        let turn_on = !self.state.powered;
+        self.state.powered = true;
+        self.state_sequence(turn_on)
+    }
+
+    /// Reads a single frame of either kind back out of a sequence
+    pub fn decode(seq: &IrSequence) -> Result<FujitsuFrame, FujitsuError> {
+        let bytes = <Self as IrTargetFormat>::Format::decode(seq)?;
+        FujitsuFrame::from_bytes(&bytes.0).ok_or(FujitsuError::InvalidFrame)
+    }
+
+    pub fn state(&self) -> &FujitsuState {
+        &self.state
+    }
+
+    pub fn fan_set(&mut self, fan: FujitsuFan) -> Result<IrSequence, FujitsuError> {
+        self.state.fan = fan;
+        self.settings_sequence()
+    }
+
+    pub fn fan(&self) -> &FujitsuFan {
+        &self.state.fan
+    }
+
+    /// The unit only knows to flip its swing, so the tracked state may drift if the physical
+    /// remote is also used
+    pub fn swing_toggle(&mut self) -> Result<IrSequence, FujitsuError> {
+        self.state.swing = !self.state.swing;
+        self.as_ir_sequence(FujitsuFrame::Command(FujitsuCommand::SwingToggle))
+    }
+
+    pub fn swing(&self) -> bool {
+        self.state.swing
+    }
+
+    /// Runs at full power for a while, after which the unit returns to its previous settings by
+    /// itself
+    pub fn powerful(&mut self) -> Result<IrSequence, FujitsuError> {
+        self.as_ir_sequence(FujitsuFrame::Command(FujitsuCommand::Powerful))
+    }
+}
+
+impl IrTargetFormat for Fujitsu {
+    type Format = Aeha;
+}
+
+impl IrTarget for Fujitsu {
+    type Error = FujitsuError;
+    type Temperature = FujitsuTemperatureCode;
+    const SEQ_LENGTH: usize = STATE_FRAME_LENGTH * 8;
+
+    fn temperature_range(&self) -> TemperatureRange {
+        FujitsuTemperatureCode::RANGE
+    }
+
+    fn power_off(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = false;
+        self.as_ir_sequence(FujitsuFrame::Command(FujitsuCommand::TurnOff))
+    }
+
+    fn power_on(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = true;
+        self.state_sequence(true)
+    }
+
+    fn is_powered(&self) -> bool {
+        self.state.powered
+    }
+
+    fn temp_up(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .up()
+            .ok_or(FujitsuError::TemperatureRange)?;
+        self.settings_sequence()
+    }
+
+    fn temp_down(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .down()
+            .ok_or(FujitsuError::TemperatureRange)?;
+        self.settings_sequence()
+    }
+
+    fn temp_set(&mut self, temp: Self::Temperature) -> Option<Result<IrSequence, Self::Error>> {
+        if self.state.temperature == temp {
+            return None;
+        }
+        self.state.temperature = temp;
+        Some(self.settings_sequence())
+    }
+
+    fn temperature(&self) -> &Self::Temperature {
+        &self.state.temperature
+    }
+
+    fn mode_set(&mut self, mode: ACMode) -> Result<IrSequence, Self::Error> {
+        self.state.mode = mode;
+        self.settings_sequence()
+    }
+
+    fn mode(&self) -> &ACMode {
+        &self.state.mode
+    }
+
+    fn status(&self) -> IrStatus<Self> {
+        IrStatus {
+            powered: self.state.powered,
+            mode: self.state.mode.clone(),
+            temperature: self.state.temperature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded_state(seq: &IrSequence) -> (FujitsuState, bool) {
+        match Fujitsu::decode(seq).unwrap() {
+            FujitsuFrame::State { state, turn_on } => (state, turn_on),
+            frame => panic!("expected a state frame, got {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn settings_round_trip_while_on() {
+        let mut fujitsu = Fujitsu::default();
+        let (state, turn_on) = decoded_state(&fujitsu.power_on().unwrap());
+        assert!(turn_on);
+        assert_eq!(&state, fujitsu.state());
+
+        let (state, turn_on) = decoded_state(&fujitsu.mode_set(ACMode::Cool).unwrap());
+        assert!(!turn_on);
+        assert_eq!(&state, fujitsu.state());
+    }
+
+    #[test]
+    fn settings_while_off_turn_on() {
+        let mut fujitsu = Fujitsu::default();
+        assert!(!fujitsu.is_powered());
+        let (state, turn_on) = decoded_state(&fujitsu.fan_set(FujitsuFan::Quiet).unwrap());
+        assert!(turn_on);
+        assert!(fujitsu.is_powered());
+        assert_eq!(&state, fujitsu.state());
+    }
+}
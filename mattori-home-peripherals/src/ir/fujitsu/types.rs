@@ -0,0 +1,253 @@
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+use crate::ir::types::{ACMode, IrPulseBytes, Temperature, TemperatureCode, TemperatureRange};
+use core::convert;
+use std::str::FromStr;
+
+pub const STATE_FRAME_LENGTH: usize = 16;
+pub const COMMAND_FRAME_LENGTH: usize = 7;
+
+const HEADER: [u8; 5] = [0x14, 0x63, 0x00, 0x10, 0x10];
+const STATE_COMMAND: u8 = 0xFE;
+const STATE_REMAINING_LENGTH: u8 = 0x09;
+const POWER_BIT: u8 = 0x01;
+const SWING_VERTICAL: u8 = 0x10;
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct FujitsuTemperatureCode(Temperature);
+
+impl FujitsuTemperatureCode {
+    pub const RANGE: TemperatureRange = TemperatureRange::celsius(16, 30, 10);
+
+    /// Whole degrees above the lowest temperature, which is what goes on the wire
+    pub fn offset(&self) -> u8 {
+        (self.0.whole_degrees() - Self::RANGE.min.whole_degrees()) as u8
+    }
+
+    pub fn from_offset(offset: u8) -> Option<FujitsuTemperatureCode> {
+        Self::try_from(Temperature::celsius(
+            Self::RANGE.min.whole_degrees() + offset as i32,
+        ))
+        .ok()
+    }
+
+    pub fn down(&self) -> Option<FujitsuTemperatureCode> {
+        Self::RANGE.down(self.0).map(FujitsuTemperatureCode)
+    }
+
+    pub fn up(&self) -> Option<FujitsuTemperatureCode> {
+        Self::RANGE.up(self.0).map(FujitsuTemperatureCode)
+    }
+}
+
+impl Default for FujitsuTemperatureCode {
+    fn default() -> Self {
+        FujitsuTemperatureCode(Temperature::celsius(24))
+    }
+}
+
+impl TemperatureCode for FujitsuTemperatureCode {}
+
+#[derive(Error, Debug)]
+#[error("Invalid temperature code")]
+pub struct InvalidFujitsuTemperatureCode;
+
+impl TryFrom<Temperature> for FujitsuTemperatureCode {
+    type Error = InvalidFujitsuTemperatureCode;
+
+    fn try_from(value: Temperature) -> Result<Self, Self::Error> {
+        Self::RANGE
+            .normalize(value)
+            .map(FujitsuTemperatureCode)
+            .ok_or(InvalidFujitsuTemperatureCode)
+    }
+}
+
+impl FromStr for FujitsuTemperatureCode {
+    type Err = InvalidFujitsuTemperatureCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Temperature>()
+            .map_err(|_| InvalidFujitsuTemperatureCode)
+            .map(FujitsuTemperatureCode::try_from)
+            .and_then(convert::identity)
+    }
+}
+
+impl From<FujitsuTemperatureCode> for Temperature {
+    fn from(code: FujitsuTemperatureCode) -> Self {
+        code.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum FujitsuFan {
+    #[default]
+    Auto,
+    High,
+    Medium,
+    Low,
+    Quiet,
+}
+
+impl From<FujitsuFan> for u8 {
+    fn from(fan: FujitsuFan) -> Self {
+        match fan {
+            FujitsuFan::Auto => 0x0,
+            FujitsuFan::High => 0x1,
+            FujitsuFan::Medium => 0x2,
+            FujitsuFan::Low => 0x3,
+            FujitsuFan::Quiet => 0x4,
+        }
+    }
+}
+
+impl FujitsuFan {
+    fn from_byte(byte: u8) -> Option<FujitsuFan> {
+        match byte & 0x07 {
+            0x0 => Some(FujitsuFan::Auto),
+            0x1 => Some(FujitsuFan::High),
+            0x2 => Some(FujitsuFan::Medium),
+            0x3 => Some(FujitsuFan::Low),
+            0x4 => Some(FujitsuFan::Quiet),
+            _ => None,
+        }
+    }
+}
+
+/// One-off commands that are sent as short frames rather than as the whole state
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum FujitsuCommand {
+    TurnOff,
+    SwingToggle,
+    Powerful,
+}
+
+impl From<FujitsuCommand> for u8 {
+    fn from(command: FujitsuCommand) -> Self {
+        match command {
+            FujitsuCommand::TurnOff => 0x02,
+            FujitsuCommand::Powerful => 0x39,
+            FujitsuCommand::SwingToggle => 0x6D,
+        }
+    }
+}
+
+impl FujitsuCommand {
+    fn from_byte(byte: u8) -> Option<FujitsuCommand> {
+        match byte {
+            0x02 => Some(FujitsuCommand::TurnOff),
+            0x39 => Some(FujitsuCommand::Powerful),
+            0x6D => Some(FujitsuCommand::SwingToggle),
+            _ => None,
+        }
+    }
+}
+
+fn mode_bits(mode: &ACMode) -> u8 {
+    match mode {
+        ACMode::Auto => 0x0,
+        ACMode::Cool => 0x1,
+        ACMode::Dry => 0x2,
+        ACMode::Fan => 0x3,
+        ACMode::Warm => 0x4,
+    }
+}
+
+fn mode_from_byte(byte: u8) -> Option<ACMode> {
+    match byte & 0x07 {
+        0x0 => Some(ACMode::Auto),
+        0x1 => Some(ACMode::Cool),
+        0x2 => Some(ACMode::Dry),
+        0x3 => Some(ACMode::Fan),
+        0x4 => Some(ACMode::Warm),
+        _ => None,
+    }
+}
+
+/// Two's complement of the sum of the bytes after the length byte, so the whole section sums to 0
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |acc, b| acc.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct FujitsuState {
+    pub powered: bool,
+    pub mode: ACMode,
+    pub temperature: FujitsuTemperatureCode,
+    pub fan: FujitsuFan,
+    pub swing: bool,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub enum FujitsuFrame {
+    /// Full settings, with whether this frame is the one that turns the unit on
+    State {
+        state: FujitsuState,
+        turn_on: bool,
+    },
+    Command(FujitsuCommand),
+}
+
+impl FujitsuFrame {
+    pub fn to_bytes(&self) -> IrPulseBytes {
+        match self {
+            FujitsuFrame::State { state, turn_on } => {
+                let mut bytes = [0u8; STATE_FRAME_LENGTH];
+                bytes[..HEADER.len()].copy_from_slice(&HEADER);
+                bytes[5] = STATE_COMMAND;
+                bytes[6] = STATE_REMAINING_LENGTH;
+                bytes[7] = 0x30;
+                bytes[8] = (state.temperature.offset() << 4) | if *turn_on { POWER_BIT } else { 0 };
+                bytes[9] = mode_bits(&state.mode);
+                bytes[10] = u8::from(state.fan) | if state.swing { SWING_VERTICAL } else { 0 };
+                bytes[14] = 0x20;
+                bytes[STATE_FRAME_LENGTH - 1] = checksum(&bytes[7..STATE_FRAME_LENGTH - 1]);
+                IrPulseBytes(bytes.to_vec())
+            }
+            FujitsuFrame::Command(command) => {
+                let mut bytes = [0u8; COMMAND_FRAME_LENGTH];
+                bytes[..HEADER.len()].copy_from_slice(&HEADER);
+                bytes[5] = u8::from(*command);
+                bytes[6] = !bytes[5];
+                IrPulseBytes(bytes.to_vec())
+            }
+        }
+    }
+
+    /// Parses either kind of frame, returning `None` if it isn't a valid Fujitsu AC frame
+    pub fn from_bytes(bytes: &[u8]) -> Option<FujitsuFrame> {
+        if bytes.len() < COMMAND_FRAME_LENGTH || bytes[..HEADER.len()] != HEADER {
+            return None;
+        }
+        match bytes.len() {
+            COMMAND_FRAME_LENGTH if bytes[6] == !bytes[5] => {
+                FujitsuCommand::from_byte(bytes[5]).map(FujitsuFrame::Command)
+            }
+            STATE_FRAME_LENGTH
+                if bytes[5] == STATE_COMMAND
+                    && bytes[6] == STATE_REMAINING_LENGTH
+                    && checksum(&bytes[7..STATE_FRAME_LENGTH - 1])
+                        == bytes[STATE_FRAME_LENGTH - 1] =>
+            {
+                Some(FujitsuFrame::State {
+                    state: FujitsuState {
+                        // a state frame always leaves the unit running
+                        powered: true,
+                        mode: mode_from_byte(bytes[9])?,
+                        temperature: FujitsuTemperatureCode::from_offset(bytes[8] >> 4)?,
+                        fan: FujitsuFan::from_byte(bytes[10])?,
+                        swing: bytes[10] & SWING_VERTICAL != 0,
+                    },
+                    turn_on: bytes[8] & POWER_BIT != 0,
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+pub mod types;
+
+use thiserror::Error;
+
+use crate::ir::format::Aeha;
+use crate::ir::mitsubishi::types::{
+    MitsubishiFan, MitsubishiState, MitsubishiTemperatureCode, MitsubishiVane, FRAME_LENGTH,
+};
+use crate::ir::types::{
+    ACMode, IrDecodeError, IrEncodeError, IrFormat, IrFraming, IrSequence, IrStatus, IrTarget,
+    IrTargetFormat, TemperatureRange,
+};
+
+#[derive(Error, Clone, Debug)]
+pub enum MitsubishiError {
+    #[error("Temperature out of range")]
+    TemperatureRange,
+    #[error("Could not encode ir sequence")]
+    EncodeError(#[from] IrEncodeError),
+    #[error("Could not decode ir sequence")]
+    DecodeError(#[from] IrDecodeError),
+    #[error("Not a valid Mitsubishi frame")]
+    InvalidFrame,
+    #[error("Repeated frames did not match")]
+    FrameMismatch,
+}
+
+/// Mitsubishi Electric (Kirigamine) AC, which sends its whole state in every frame
+#[derive(Debug)]
+pub struct Mitsubishi {
+    state: MitsubishiState,
+}
+
+impl Default for Mitsubishi {
+    fn default() -> Self {
+        Mitsubishi {
+            state: MitsubishiState {
+                powered: false,
+                mode: ACMode::default(),
+                temperature: MitsubishiTemperatureCode::default(),
+                fan: MitsubishiFan::default(),
+                vane: MitsubishiVane::default(),
+            },
+        }
+    }
+}
+
+impl Mitsubishi {
+    fn as_ir_sequence(&self) -> Result<IrSequence, <Mitsubishi as IrTarget>::Error> {
+        Ok(<Self as IrTargetFormat>::Format::encode(
+            self.state.to_bytes(),
+        )?)
+    }
+
+    /// Reads the target state back out of a sequence, which may contain the frame more than once
+    pub fn decode(seq: &IrSequence) -> Result<Mitsubishi, MitsubishiError> {
+        let bytes = <Self as IrTargetFormat>::Format::decode(seq)?;
+        if bytes.0.is_empty() || bytes.0.len() % FRAME_LENGTH != 0 {
+            return Err(MitsubishiError::InvalidFrame);
+        }
+        let (first, rest) = bytes.0.split_at(FRAME_LENGTH);
+        if rest.chunks(FRAME_LENGTH).any(|frame| frame != first) {
+            return Err(MitsubishiError::FrameMismatch);
+        }
+        MitsubishiState::from_bytes(first)
+            .map(|state| Mitsubishi { state })
+            .ok_or(MitsubishiError::InvalidFrame)
+    }
+
+    pub fn state(&self) -> &MitsubishiState {
+        &self.state
+    }
+
+    pub fn fan_set(&mut self, fan: MitsubishiFan) -> Result<IrSequence, MitsubishiError> {
+        self.state.fan = fan;
+        self.as_ir_sequence()
+    }
+
+    pub fn fan(&self) -> &MitsubishiFan {
+        &self.state.fan
+    }
+
+    pub fn vane_set(&mut self, vane: MitsubishiVane) -> Result<IrSequence, MitsubishiError> {
+        self.state.vane = vane;
+        self.as_ir_sequence()
+    }
+
+    pub fn vane(&self) -> &MitsubishiVane {
+        &self.state.vane
+    }
+}
+
+impl IrTargetFormat for Mitsubishi {
+    type Format = Aeha;
+}
+
+impl IrTarget for Mitsubishi {
+    type Error = MitsubishiError;
+    type Temperature = MitsubishiTemperatureCode;
+    const SEQ_LENGTH: usize = FRAME_LENGTH * 8;
+    const FRAMING: IrFraming = IrFraming::repeated(2, 17100);
+
+    fn temperature_range(&self) -> TemperatureRange {
+        MitsubishiTemperatureCode::RANGE
+    }
+
+    fn power_off(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = false;
+        self.as_ir_sequence()
+    }
+
+    fn power_on(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = true;
+        self.as_ir_sequence()
+    }
+
+    fn is_powered(&self) -> bool {
+        self.state.powered
+    }
+
+    fn temp_up(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .up()
+            .ok_or(MitsubishiError::TemperatureRange)?;
+        self.as_ir_sequence()
+    }
+
+    fn temp_down(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .down()
+            .ok_or(MitsubishiError::TemperatureRange)?;
+        self.as_ir_sequence()
+    }
+
+    fn temp_set(&mut self, temp: Self::Temperature) -> Option<Result<IrSequence, Self::Error>> {
+        if self.state.temperature == temp {
+            return None;
+        }
+        self.state.temperature = temp;
+        Some(self.as_ir_sequence())
+    }
+
+    fn temperature(&self) -> &Self::Temperature {
+        &self.state.temperature
+    }
+
+    fn mode_set(&mut self, mode: ACMode) -> Result<IrSequence, Self::Error> {
+        self.state.mode = mode;
+        self.as_ir_sequence()
+    }
+
+    fn mode(&self) -> &ACMode {
+        &self.state.mode
+    }
+
+    fn status(&self) -> IrStatus<Self> {
+        IrStatus {
+            powered: self.state.powered,
+            mode: self.state.mode.clone(),
+            temperature: self.state.temperature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framed(mitsubishi: &mut Mitsubishi) -> IrSequence {
+        mitsubishi
+            .power_on()
+            .unwrap()
+            .framed(&<Mitsubishi as IrTarget>::FRAMING)
+    }
+
+    #[test]
+    fn round_trip_through_repeated_frames() {
+        let mut mitsubishi = Mitsubishi::default();
+        mitsubishi.mode_set(ACMode::Cool).unwrap();
+        let decoded = Mitsubishi::decode(&framed(&mut mitsubishi)).unwrap();
+        assert_eq!(decoded.state(), mitsubishi.state());
+        assert!(decoded.is_powered());
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut bytes = Mitsubishi::default().state.to_bytes().0;
+        bytes[FRAME_LENGTH - 1] = bytes[FRAME_LENGTH - 1].wrapping_add(1);
+        assert!(MitsubishiState::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn rejects_mismatched_repeats() {
+        let mut mitsubishi = Mitsubishi::default();
+        let off = mitsubishi.power_off().unwrap();
+        let on = mitsubishi.power_on().unwrap();
+        let framing = <Mitsubishi as IrTarget>::FRAMING;
+        let seq = IrSequence::concat(vec![on, off], framing.gap);
+        assert!(matches!(
+            Mitsubishi::decode(&seq),
+            Err(MitsubishiError::FrameMismatch)
+        ));
+    }
+}
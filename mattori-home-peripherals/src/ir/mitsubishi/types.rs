@@ -0,0 +1,246 @@
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+use crate::ir::types::{
+    ACMode, IrPulseBytes, Temperature, TemperatureCode, TemperatureRange, TemperatureUnit,
+};
+use core::convert;
+use std::str::FromStr;
+
+pub const FRAME_LENGTH: usize = 18;
+
+const HEADER: [u8; 5] = [0x23, 0xCB, 0x26, 0x01, 0x00];
+const POWER_BIT: u8 = 0x20;
+const FAN_AUTO_BIT: u8 = 0x80;
+const VANE_BIT: u8 = 0x40;
+const HALF_DEGREE_BIT: u8 = 0x10;
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct MitsubishiTemperatureCode(Temperature);
+
+impl MitsubishiTemperatureCode {
+    pub const RANGE: TemperatureRange = TemperatureRange::celsius(16, 31, 5);
+
+    /// Whole degrees above the lowest temperature, which is what goes on the wire
+    pub fn offset(&self) -> u8 {
+        (self.0.whole_degrees() - Self::RANGE.min.whole_degrees()) as u8
+    }
+
+    pub fn from_offset(offset: u8, half: bool) -> Option<MitsubishiTemperatureCode> {
+        Self::try_from(Temperature::from_tenths(
+            Self::RANGE.min.tenths() + offset as i32 * 10 + if half { 5 } else { 0 },
+            TemperatureUnit::Celsius,
+        ))
+        .ok()
+    }
+
+    pub fn is_half(&self) -> bool {
+        !self.0.is_whole()
+    }
+
+    pub fn down(&self) -> Option<MitsubishiTemperatureCode> {
+        Self::RANGE.down(self.0).map(MitsubishiTemperatureCode)
+    }
+
+    pub fn up(&self) -> Option<MitsubishiTemperatureCode> {
+        Self::RANGE.up(self.0).map(MitsubishiTemperatureCode)
+    }
+}
+
+impl Default for MitsubishiTemperatureCode {
+    fn default() -> Self {
+        MitsubishiTemperatureCode(Temperature::celsius(22))
+    }
+}
+
+impl TemperatureCode for MitsubishiTemperatureCode {}
+
+#[derive(Error, Debug)]
+#[error("Invalid temperature code")]
+pub struct InvalidMitsubishiTemperatureCode;
+
+impl TryFrom<Temperature> for MitsubishiTemperatureCode {
+    type Error = InvalidMitsubishiTemperatureCode;
+
+    fn try_from(value: Temperature) -> Result<Self, Self::Error> {
+        Self::RANGE
+            .normalize(value)
+            .map(MitsubishiTemperatureCode)
+            .ok_or(InvalidMitsubishiTemperatureCode)
+    }
+}
+
+impl FromStr for MitsubishiTemperatureCode {
+    type Err = InvalidMitsubishiTemperatureCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Temperature>()
+            .map_err(|_| InvalidMitsubishiTemperatureCode)
+            .map(MitsubishiTemperatureCode::try_from)
+            .and_then(convert::identity)
+    }
+}
+
+impl From<MitsubishiTemperatureCode> for Temperature {
+    fn from(code: MitsubishiTemperatureCode) -> Self {
+        code.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum MitsubishiFan {
+    #[default]
+    Auto,
+    Low,
+    Medium,
+    High,
+    Max,
+    Quiet,
+}
+
+impl From<MitsubishiFan> for u8 {
+    fn from(fan: MitsubishiFan) -> Self {
+        match fan {
+            MitsubishiFan::Auto => FAN_AUTO_BIT,
+            MitsubishiFan::Low => 1,
+            MitsubishiFan::Medium => 2,
+            MitsubishiFan::High => 3,
+            MitsubishiFan::Max => 4,
+            MitsubishiFan::Quiet => 5,
+        }
+    }
+}
+
+impl MitsubishiFan {
+    fn from_byte(byte: u8) -> Option<MitsubishiFan> {
+        if byte & FAN_AUTO_BIT != 0 {
+            return Some(MitsubishiFan::Auto);
+        }
+        match byte & 0x07 {
+            1 => Some(MitsubishiFan::Low),
+            2 => Some(MitsubishiFan::Medium),
+            3 => Some(MitsubishiFan::High),
+            4 => Some(MitsubishiFan::Max),
+            5 => Some(MitsubishiFan::Quiet),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum MitsubishiVane {
+    #[default]
+    Auto,
+    Highest,
+    High,
+    Middle,
+    Low,
+    Lowest,
+    Swing,
+}
+
+impl From<MitsubishiVane> for u8 {
+    fn from(vane: MitsubishiVane) -> Self {
+        VANE_BIT
+            | (match vane {
+                MitsubishiVane::Auto => 0,
+                MitsubishiVane::Highest => 1,
+                MitsubishiVane::High => 2,
+                MitsubishiVane::Middle => 3,
+                MitsubishiVane::Low => 4,
+                MitsubishiVane::Lowest => 5,
+                MitsubishiVane::Swing => 7,
+            } << 3)
+    }
+}
+
+impl MitsubishiVane {
+    fn from_byte(byte: u8) -> Option<MitsubishiVane> {
+        match (byte >> 3) & 0x07 {
+            0 => Some(MitsubishiVane::Auto),
+            1 => Some(MitsubishiVane::Highest),
+            2 => Some(MitsubishiVane::High),
+            3 => Some(MitsubishiVane::Middle),
+            4 => Some(MitsubishiVane::Low),
+            5 => Some(MitsubishiVane::Lowest),
+            7 => Some(MitsubishiVane::Swing),
+            _ => None,
+        }
+    }
+}
+
+/// Mode nibble in byte 6, paired with the mode dependent byte 8
+fn mode_bytes(mode: &ACMode) -> (u8, u8) {
+    match mode {
+        ACMode::Warm => (0x08, 0x30),
+        ACMode::Dry => (0x10, 0x32),
+        ACMode::Cool => (0x18, 0x36),
+        ACMode::Auto => (0x20, 0x30),
+        ACMode::Fan => (0x38, 0x37),
+    }
+}
+
+fn mode_from_byte(byte: u8) -> Option<ACMode> {
+    match byte & 0x38 {
+        0x08 => Some(ACMode::Warm),
+        0x10 => Some(ACMode::Dry),
+        0x18 => Some(ACMode::Cool),
+        0x20 => Some(ACMode::Auto),
+        0x38 => Some(ACMode::Fan),
+        _ => None,
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct MitsubishiState {
+    pub powered: bool,
+    pub mode: ACMode,
+    pub temperature: MitsubishiTemperatureCode,
+    pub fan: MitsubishiFan,
+    pub vane: MitsubishiVane,
+}
+
+impl MitsubishiState {
+    pub fn to_bytes(&self) -> IrPulseBytes {
+        let (mode, mode_extra) = mode_bytes(&self.mode);
+        let mut bytes = [0u8; FRAME_LENGTH];
+        bytes[..HEADER.len()].copy_from_slice(&HEADER);
+        bytes[5] = if self.powered { POWER_BIT } else { 0 };
+        bytes[6] = mode;
+        bytes[7] = self.temperature.offset()
+            | if self.temperature.is_half() {
+                HALF_DEGREE_BIT
+            } else {
+                0
+            };
+        bytes[8] = mode_extra;
+        bytes[9] = u8::from(self.fan) | u8::from(self.vane);
+        bytes[FRAME_LENGTH - 1] = checksum(&bytes[..FRAME_LENGTH - 1]);
+        IrPulseBytes(bytes.to_vec())
+    }
+
+    /// Parses a single frame, returning `None` if it isn't a valid Mitsubishi AC frame
+    pub fn from_bytes(bytes: &[u8]) -> Option<MitsubishiState> {
+        if bytes.len() != FRAME_LENGTH
+            || bytes[..HEADER.len()] != HEADER
+            || checksum(&bytes[..FRAME_LENGTH - 1]) != bytes[FRAME_LENGTH - 1]
+        {
+            return None;
+        }
+        Some(MitsubishiState {
+            powered: bytes[5] & POWER_BIT != 0,
+            mode: mode_from_byte(bytes[6])?,
+            temperature: MitsubishiTemperatureCode::from_offset(
+                bytes[7] & 0x0f,
+                bytes[7] & HALF_DEGREE_BIT != 0,
+            )?,
+            fan: MitsubishiFan::from_byte(bytes[9])?,
+            vane: MitsubishiVane::from_byte(bytes[9])?,
+        })
+    }
+}
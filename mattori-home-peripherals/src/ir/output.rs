@@ -1,12 +1,13 @@
 use std::sync::{mpsc, Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rppal::gpio::{Gpio, PwmPulse, PwmStep};
 use thiserror::Error;
 use tokio::sync::watch;
 use tokio::task::spawn_blocking;
 
-use crate::ir::types::{IrSequence, IrStatus, IrTarget};
+use crate::ir::remote::{Remote, RemoteError};
+use crate::ir::types::{IrSequence, IrStatus, IrTarget, Temperature};
 use crate::I2cError;
 use core::iter;
 use std::convert::TryFrom;
@@ -19,12 +20,14 @@ const WAIT_TIMEOUT: Duration = Duration::from_micros(100);
 #[derive(Error, Debug)]
 pub enum IrOutError<E: IrTarget + Debug>
 where
-    <<E as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<E as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     #[error(transparent)]
     I2cError(#[from] I2cError),
     #[error(transparent)]
     IrTarget(E::Error),
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
     #[error("Could not send message to ir thread")]
     Send,
     #[error("Could not acquire message sender mutex")]
@@ -36,16 +39,17 @@ pub type Result<T, E> = std::result::Result<T, IrOutError<E>>;
 #[derive(Debug)]
 pub struct IrOut<T: 'static + IrTarget>
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     target: T,
     sequence_sender: Mutex<mpsc::Sender<IrSequence>>,
     send_stop_sender: watch::Sender<bool>,
+    busy_until: Mutex<Instant>,
 }
 
 impl<T: 'static + IrTarget + Debug> IrOut<T>
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     pub fn start(pin: u8, target: T) -> Result<IrOut<T>, T> {
         let out = Arc::new(Mutex::new(
@@ -110,6 +114,7 @@ where
             target,
             sequence_sender: Mutex::new(sequence_sender),
             send_stop_sender,
+            busy_until: Mutex::new(Instant::now()),
         })
     }
 
@@ -119,6 +124,10 @@ where
 
     pub fn send(&self, seq: IrSequence) -> Result<(), T> {
         debug!("sending sequence: {:?}", seq);
+        {
+            let mut busy_until = self.busy_until.lock().map_err(|_| IrOutError::Mutex)?;
+            *busy_until = Instant::now().max(*busy_until) + seq.duration();
+        }
         self.sequence_sender
             .lock()
             .map_err(|_| IrOutError::Mutex)?
@@ -126,6 +135,14 @@ where
             .map_err(|_| IrOutError::Send)
     }
 
+    /// When everything sent so far should have finished transmitting
+    pub fn idle_at(&self) -> Instant {
+        self.busy_until
+            .lock()
+            .map(|busy_until| *busy_until)
+            .unwrap_or_else(|_| Instant::now())
+    }
+
     pub fn stop(&mut self) -> Result<(), T> {
         self.send_stop_sender
             .send(true)
@@ -138,7 +155,12 @@ where
     ) -> Result<(), T> {
         let sequence = action(&mut self.target).map_err(IrOutError::IrTarget)?;
         debug!("sending sequence to target {:?}", sequence);
-        self.send(sequence)
+        self.send_framed(sequence)
+    }
+
+    /// Sends a sequence produced by the target, repeated according to its framing
+    pub fn send_framed(&self, seq: IrSequence) -> Result<(), T> {
+        self.send(seq.framed(&self.target.framing()))
     }
 
     pub fn send_status(
@@ -166,7 +188,7 @@ where
 
         if let Some(res) = results.iter().find(|r| r.is_ok()) {
             match res {
-                Ok(seq) => self.send(seq.clone()),
+                Ok(seq) => self.send_framed(seq.clone()),
                 Err(e) => Err(IrOutError::IrTarget((*e).clone())),
             }
         } else {
@@ -176,7 +198,18 @@ where
         }
     }
 
+    /// Presses a button on a remote other than the target, such as a TV or light
+    pub fn send_remote(&self, remote: &mut Remote, button: &str) -> Result<(), T> {
+        let sequence = remote.press(button)?;
+        debug!("sending {} on remote {}", button, remote.name());
+        self.send(sequence)
+    }
+
     pub fn status(&self) -> IrStatus<T> {
         self.target.status()
     }
+
+    pub fn target(&self) -> &T {
+        &self.target
+    }
 }
@@ -0,0 +1,226 @@
+pub mod types;
+
+use thiserror::Error;
+
+use crate::ir::format::Kaseikyo;
+use crate::ir::panasonic::types::{
+    PanasonicFan, PanasonicState, PanasonicSwing, PanasonicTemperatureCode, HEADER_FRAME,
+    STATE_FRAME_LENGTH,
+};
+use crate::ir::types::{
+    ACMode, IrDecodeError, IrEncodeError, IrFormat, IrPulse, IrSequence, IrStatus, IrTarget,
+    IrTargetFormat, TemperatureRange,
+};
+
+/// Space between the header and state frames
+const FRAME_GAP: u128 = 10000;
+
+#[derive(Error, Clone, Debug)]
+pub enum PanasonicError {
+    #[error("Temperature out of range")]
+    TemperatureRange,
+    #[error("Could not encode ir sequence")]
+    EncodeError(#[from] IrEncodeError),
+    #[error("Could not decode ir sequence")]
+    DecodeError(#[from] IrDecodeError),
+    #[error("Not a valid Panasonic frame")]
+    InvalidFrame,
+}
+
+/// Panasonic AC, which sends a fixed header frame before a frame carrying the whole state
+#[derive(Debug)]
+pub struct Panasonic {
+    state: PanasonicState,
+}
+
+impl Default for Panasonic {
+    fn default() -> Self {
+        Panasonic {
+            state: PanasonicState {
+                powered: false,
+                mode: ACMode::default(),
+                temperature: PanasonicTemperatureCode::default(),
+                fan: PanasonicFan::default(),
+                swing: PanasonicSwing::default(),
+            },
+        }
+    }
+}
+
+impl Panasonic {
+    fn as_ir_sequence(&self) -> Result<IrSequence, <Panasonic as IrTarget>::Error> {
+        Ok(IrSequence::concat(
+            [
+                <Self as IrTargetFormat>::Format::encode(HEADER_FRAME)?,
+                <Self as IrTargetFormat>::Format::encode(self.state.to_bytes())?,
+            ],
+            IrPulse(FRAME_GAP),
+        ))
+    }
+
+    /// Reads the target state back out of a header and state frame pair
+    pub fn decode(seq: &IrSequence) -> Result<Panasonic, PanasonicError> {
+        let bytes = <Self as IrTargetFormat>::Format::decode(seq)?;
+        if bytes.0.len() != HEADER_FRAME.len() + STATE_FRAME_LENGTH {
+            return Err(PanasonicError::InvalidFrame);
+        }
+        let (header, state) = bytes.0.split_at(HEADER_FRAME.len());
+        if header != HEADER_FRAME {
+            return Err(PanasonicError::InvalidFrame);
+        }
+        PanasonicState::from_bytes(state)
+            .map(|state| Panasonic { state })
+            .ok_or(PanasonicError::InvalidFrame)
+    }
+
+    pub fn state(&self) -> &PanasonicState {
+        &self.state
+    }
+
+    pub fn fan_set(&mut self, fan: PanasonicFan) -> Result<IrSequence, PanasonicError> {
+        self.state.fan = fan;
+        self.as_ir_sequence()
+    }
+
+    pub fn fan(&self) -> &PanasonicFan {
+        &self.state.fan
+    }
+
+    pub fn swing_set(&mut self, swing: PanasonicSwing) -> Result<IrSequence, PanasonicError> {
+        self.state.swing = swing;
+        self.as_ir_sequence()
+    }
+
+    pub fn swing(&self) -> &PanasonicSwing {
+        &self.state.swing
+    }
+}
+
+impl IrTargetFormat for Panasonic {
+    type Format = Kaseikyo;
+}
+
+impl IrTarget for Panasonic {
+    type Error = PanasonicError;
+    type Temperature = PanasonicTemperatureCode;
+    const SEQ_LENGTH: usize = (HEADER_FRAME.len() + STATE_FRAME_LENGTH) * 8;
+
+    fn temperature_range(&self) -> TemperatureRange {
+        PanasonicTemperatureCode::RANGE
+    }
+
+    fn power_off(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = false;
+        self.as_ir_sequence()
+    }
+
+    fn power_on(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.powered = true;
+        self.as_ir_sequence()
+    }
+
+    fn is_powered(&self) -> bool {
+        self.state.powered
+    }
+
+    fn temp_up(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .up()
+            .ok_or(PanasonicError::TemperatureRange)?;
+        self.as_ir_sequence()
+    }
+
+    fn temp_down(&mut self) -> Result<IrSequence, Self::Error> {
+        self.state.temperature = self
+            .state
+            .temperature
+            .down()
+            .ok_or(PanasonicError::TemperatureRange)?;
+        self.as_ir_sequence()
+    }
+
+    fn temp_set(&mut self, temp: Self::Temperature) -> Option<Result<IrSequence, Self::Error>> {
+        if self.state.temperature == temp {
+            return None;
+        }
+        self.state.temperature = temp;
+        Some(self.as_ir_sequence())
+    }
+
+    fn temperature(&self) -> &Self::Temperature {
+        &self.state.temperature
+    }
+
+    fn mode_set(&mut self, mode: ACMode) -> Result<IrSequence, Self::Error> {
+        self.state.mode = mode;
+        self.as_ir_sequence()
+    }
+
+    fn mode(&self) -> &ACMode {
+        &self.state.mode
+    }
+
+    fn status(&self) -> IrStatus<Self> {
+        IrStatus {
+            powered: self.state.powered,
+            mode: self.state.mode.clone(),
+            temperature: self.state.temperature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::panasonic::types::checksum;
+
+    #[test]
+    fn round_trip_through_header_and_state_frames() {
+        let mut panasonic = Panasonic::default();
+        panasonic.power_on().unwrap();
+        panasonic.mode_set(ACMode::Warm).unwrap();
+        panasonic.temp_up().unwrap();
+        panasonic.swing_set(PanasonicSwing::Low).unwrap();
+        let seq = panasonic
+            .fan_set(PanasonicFan::High)
+            .unwrap()
+            .framed(&<Panasonic as IrTarget>::FRAMING);
+        let decoded = Panasonic::decode(&seq).unwrap();
+        assert_eq!(decoded.state(), panasonic.state());
+        assert!(decoded.is_powered());
+    }
+
+    #[test]
+    fn frames_are_separated_by_the_gap() {
+        let header = <Panasonic as IrTargetFormat>::Format::encode(HEADER_FRAME).unwrap();
+        let seq = Panasonic::default().power_on().unwrap();
+        assert_eq!(seq.0[..header.0.len()], header.0[..]);
+        assert_eq!(seq.0[header.0.len()], IrPulse(FRAME_GAP));
+    }
+
+    #[test]
+    fn checksum_is_seeded() {
+        assert_eq!(checksum(&[]), 0xF4);
+        let bytes = Panasonic::default().state.to_bytes().0;
+        assert_eq!(bytes[STATE_FRAME_LENGTH - 1], 0xD5);
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut bytes = Panasonic::default().state.to_bytes().0;
+        bytes[STATE_FRAME_LENGTH - 1] = bytes[STATE_FRAME_LENGTH - 1].wrapping_add(1);
+        assert!(PanasonicState::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn rejects_state_frame_without_header() {
+        let state = Panasonic::default().state.to_bytes();
+        let seq = <Panasonic as IrTargetFormat>::Format::encode(state.0).unwrap();
+        assert!(matches!(
+            Panasonic::decode(&seq),
+            Err(PanasonicError::InvalidFrame)
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+use crate::ir::types::{ACMode, IrPulseBytes, Temperature, TemperatureCode, TemperatureRange};
+use core::convert;
+use std::str::FromStr;
+
+pub const HEADER_FRAME: [u8; 8] = [0x02, 0x20, 0xE0, 0x04, 0x00, 0x00, 0x00, 0x06];
+pub const STATE_FRAME_LENGTH: usize = 19;
+
+const STATE_HEADER: [u8; 5] = [0x02, 0x20, 0xE0, 0x04, 0x00];
+const POWER_BIT: u8 = 0x01;
+const CHECKSUM_INIT: u8 = 0xF4;
+const SWING_HORIZONTAL_AUTO: u8 = 0x0D;
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct PanasonicTemperatureCode(Temperature);
+
+impl PanasonicTemperatureCode {
+    pub const RANGE: TemperatureRange = TemperatureRange::celsius(16, 30, 10);
+
+    pub fn down(&self) -> Option<PanasonicTemperatureCode> {
+        Self::RANGE.down(self.0).map(PanasonicTemperatureCode)
+    }
+
+    pub fn up(&self) -> Option<PanasonicTemperatureCode> {
+        Self::RANGE.up(self.0).map(PanasonicTemperatureCode)
+    }
+}
+
+impl Default for PanasonicTemperatureCode {
+    fn default() -> Self {
+        PanasonicTemperatureCode(Temperature::celsius(24))
+    }
+}
+
+impl TemperatureCode for PanasonicTemperatureCode {}
+
+#[derive(Error, Debug)]
+#[error("Invalid temperature code")]
+pub struct InvalidPanasonicTemperatureCode;
+
+impl TryFrom<Temperature> for PanasonicTemperatureCode {
+    type Error = InvalidPanasonicTemperatureCode;
+
+    fn try_from(value: Temperature) -> Result<Self, Self::Error> {
+        Self::RANGE
+            .normalize(value)
+            .map(PanasonicTemperatureCode)
+            .ok_or(InvalidPanasonicTemperatureCode)
+    }
+}
+
+impl FromStr for PanasonicTemperatureCode {
+    type Err = InvalidPanasonicTemperatureCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Temperature>()
+            .map_err(|_| InvalidPanasonicTemperatureCode)
+            .map(PanasonicTemperatureCode::try_from)
+            .and_then(convert::identity)
+    }
+}
+
+impl From<PanasonicTemperatureCode> for Temperature {
+    fn from(code: PanasonicTemperatureCode) -> Self {
+        code.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PanasonicFan {
+    Min,
+    Low,
+    Medium,
+    High,
+    Max,
+    #[default]
+    Auto,
+}
+
+impl From<PanasonicFan> for u8 {
+    fn from(fan: PanasonicFan) -> Self {
+        match fan {
+            PanasonicFan::Min => 0x3,
+            PanasonicFan::Low => 0x4,
+            PanasonicFan::Medium => 0x5,
+            PanasonicFan::High => 0x6,
+            PanasonicFan::Max => 0x7,
+            PanasonicFan::Auto => 0xA,
+        }
+    }
+}
+
+impl PanasonicFan {
+    fn from_nibble(nibble: u8) -> Option<PanasonicFan> {
+        match nibble {
+            0x3 => Some(PanasonicFan::Min),
+            0x4 => Some(PanasonicFan::Low),
+            0x5 => Some(PanasonicFan::Medium),
+            0x6 => Some(PanasonicFan::High),
+            0x7 => Some(PanasonicFan::Max),
+            0xA => Some(PanasonicFan::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Vertical louver position
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PanasonicSwing {
+    Highest,
+    High,
+    Middle,
+    Low,
+    Lowest,
+    #[default]
+    Auto,
+}
+
+impl From<PanasonicSwing> for u8 {
+    fn from(swing: PanasonicSwing) -> Self {
+        match swing {
+            PanasonicSwing::Highest => 0x1,
+            PanasonicSwing::High => 0x2,
+            PanasonicSwing::Middle => 0x3,
+            PanasonicSwing::Low => 0x4,
+            PanasonicSwing::Lowest => 0x5,
+            PanasonicSwing::Auto => 0xF,
+        }
+    }
+}
+
+impl PanasonicSwing {
+    fn from_nibble(nibble: u8) -> Option<PanasonicSwing> {
+        match nibble {
+            0x1 => Some(PanasonicSwing::Highest),
+            0x2 => Some(PanasonicSwing::High),
+            0x3 => Some(PanasonicSwing::Middle),
+            0x4 => Some(PanasonicSwing::Low),
+            0x5 => Some(PanasonicSwing::Lowest),
+            0xF => Some(PanasonicSwing::Auto),
+            _ => None,
+        }
+    }
+}
+
+fn mode_nibble(mode: &ACMode) -> u8 {
+    match mode {
+        ACMode::Auto => 0x0,
+        ACMode::Dry => 0x2,
+        ACMode::Cool => 0x3,
+        ACMode::Warm => 0x4,
+        ACMode::Fan => 0x6,
+    }
+}
+
+fn mode_from_nibble(nibble: u8) -> Option<ACMode> {
+    match nibble {
+        0x0 => Some(ACMode::Auto),
+        0x2 => Some(ACMode::Dry),
+        0x3 => Some(ACMode::Cool),
+        0x4 => Some(ACMode::Warm),
+        0x6 => Some(ACMode::Fan),
+        _ => None,
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(CHECKSUM_INIT, |acc, b| acc.wrapping_add(*b))
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct PanasonicState {
+    pub powered: bool,
+    pub mode: ACMode,
+    pub temperature: PanasonicTemperatureCode,
+    pub fan: PanasonicFan,
+    pub swing: PanasonicSwing,
+}
+
+impl PanasonicState {
+    /// Encodes the state frame, which always follows the fixed header frame
+    pub fn to_bytes(&self) -> IrPulseBytes {
+        let mut bytes = [0u8; STATE_FRAME_LENGTH];
+        bytes[..STATE_HEADER.len()].copy_from_slice(&STATE_HEADER);
+        bytes[5] = (mode_nibble(&self.mode) << 4) | if self.powered { POWER_BIT } else { 0 };
+        bytes[6] = (self.temperature.0.whole_degrees() as u8) << 1;
+        bytes[7] = 0x80;
+        bytes[8] = (u8::from(self.fan) << 4) | u8::from(self.swing);
+        bytes[9] = SWING_HORIZONTAL_AUTO;
+        bytes[11] = 0x0E;
+        bytes[12] = 0xE0;
+        bytes[15] = 0x81;
+        bytes[STATE_FRAME_LENGTH - 1] = checksum(&bytes[..STATE_FRAME_LENGTH - 1]);
+        IrPulseBytes(bytes.to_vec())
+    }
+
+    /// Parses a state frame, returning `None` if it isn't a valid Panasonic AC frame
+    pub fn from_bytes(bytes: &[u8]) -> Option<PanasonicState> {
+        if bytes.len() != STATE_FRAME_LENGTH
+            || bytes[..STATE_HEADER.len()] != STATE_HEADER
+            || checksum(&bytes[..STATE_FRAME_LENGTH - 1]) != bytes[STATE_FRAME_LENGTH - 1]
+        {
+            return None;
+        }
+        Some(PanasonicState {
+            powered: bytes[5] & POWER_BIT != 0,
+            mode: mode_from_nibble(bytes[5] >> 4)?,
+            temperature: PanasonicTemperatureCode::try_from(Temperature::celsius(
+                ((bytes[6] >> 1) & 0x1F) as i32,
+            ))
+            .ok()?,
+            fan: PanasonicFan::from_nibble(bytes[8] >> 4)?,
+            swing: PanasonicSwing::from_nibble(bytes[8] & 0x0F)?,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+pub mod types;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::ir::remote::types::{ButtonAction, RemoteConfig, RemoteState};
+use crate::ir::types::{IrEncodeError, IrSequence};
+use crate::persist::{self, PersistError};
+
+#[derive(Error, Clone, Debug)]
+pub enum RemoteError {
+    #[error("Unknown remote {0}")]
+    UnknownRemote(String),
+    #[error("Remote {0} has no button {1}")]
+    UnknownButton(String, String),
+    #[error("Could not encode ir sequence")]
+    EncodeError(#[from] IrEncodeError),
+    #[error("Could not load remotes")]
+    Load(#[from] PersistError),
+}
+
+/// A stateless button remote, such as for a TV, fan or ceiling light
+#[derive(Clone, Debug)]
+pub struct Remote {
+    name: String,
+    config: RemoteConfig,
+    state: RemoteState,
+}
+
+impl Remote {
+    pub fn new<S: Into<String>>(name: S, config: RemoteConfig) -> Remote {
+        Remote {
+            name: name.into(),
+            config,
+            state: RemoteState::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn buttons(&self) -> impl Iterator<Item = &str> + '_ {
+        self.config.buttons.keys().map(String::as_str)
+    }
+
+    pub fn brightness_steps(&self) -> Option<u8> {
+        self.config.brightness_steps
+    }
+
+    pub fn state(&self) -> RemoteState {
+        self.state
+    }
+
+    /// Builds the sequence for a button, already repeated according to its framing, and updates
+    /// the tracked state as if it was received
+    pub fn press(&mut self, button: &str) -> Result<IrSequence, RemoteError> {
+        let button = self
+            .config
+            .buttons
+            .get(button)
+            .ok_or_else(|| RemoteError::UnknownButton(self.name.clone(), button.to_string()))?;
+        let seq = button.code.to_sequence()?.framed(&button.framing());
+        let steps = self.config.brightness_steps;
+        match button.action {
+            ButtonAction::Press => {}
+            ButtonAction::PowerToggle => {
+                // an unknown state is most likely off, as that is how devices are left
+                let powered = !self.state.powered.unwrap_or(false);
+                self.set_powered(powered, steps);
+            }
+            ButtonAction::PowerOn => self.set_powered(true, steps),
+            ButtonAction::PowerOff => self.set_powered(false, steps),
+            ButtonAction::BrightnessUp => {
+                self.state.brightness = self
+                    .state
+                    .brightness
+                    .zip(steps)
+                    .map(|(brightness, steps)| brightness.saturating_add(1).min(steps));
+            }
+            ButtonAction::BrightnessDown => {
+                self.state.brightness = self
+                    .state
+                    .brightness
+                    .map(|brightness| brightness.saturating_sub(1));
+            }
+        }
+        Ok(seq)
+    }
+
+    fn set_powered(&mut self, powered: bool, steps: Option<u8>) {
+        self.state.powered = Some(powered);
+        // lights come back on at full brightness
+        self.state.brightness = steps.map(|steps| if powered { steps } else { 0 });
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct RemotesFile {
+    #[serde(default)]
+    remotes: BTreeMap<String, RemoteConfig>,
+}
+
+/// All the configured remotes, keyed by name
+#[derive(Clone, Debug, Default)]
+pub struct Remotes {
+    remotes: BTreeMap<String, Remote>,
+}
+
+impl Remotes {
+    /// Reads remotes from a toml file, treating a missing file as having none configured
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Remotes, RemoteError> {
+        Ok(Remotes::from_file(persist::load(path)?))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Remotes, RemoteError> {
+        Ok(Remotes::from_file(persist::from_str("remotes", contents)?))
+    }
+
+    fn from_file(file: RemotesFile) -> Remotes {
+        Remotes {
+            remotes: file
+                .remotes
+                .into_iter()
+                .map(|(name, config)| (name.clone(), Remote::new(name, config)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Remote, RemoteError> {
+        self.remotes
+            .get(name)
+            .ok_or_else(|| RemoteError::UnknownRemote(name.to_string()))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut Remote, RemoteError> {
+        self.remotes
+            .get_mut(name)
+            .ok_or_else(|| RemoteError::UnknownRemote(name.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Remote> + '_ {
+        self.remotes.values()
+    }
+}
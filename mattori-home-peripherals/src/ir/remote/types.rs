@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ir::format::{Aeha, Kaseikyo};
+use crate::ir::types::{IrEncodeError, IrFormat, IrFraming, IrPulse, IrSequence};
+
+/// Gap between repeats of a button's frame when the config doesn't give one, in micros
+pub const DEFAULT_REPEAT_GAP: u64 = 40000;
+
+/// What a button sends, either as decoded bytes in a known format or as a recorded sequence
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteCode {
+    Aeha(Vec<u8>),
+    Kaseikyo(Vec<u8>),
+    /// Alternating mark and space lengths in micros
+    Raw(Vec<u64>),
+}
+
+impl RemoteCode {
+    pub fn to_sequence(&self) -> Result<IrSequence, IrEncodeError> {
+        match self {
+            RemoteCode::Aeha(bytes) => Aeha::encode(bytes),
+            RemoteCode::Kaseikyo(bytes) => Kaseikyo::encode(bytes),
+            RemoteCode::Raw(pulses) => Ok(IrSequence(
+                pulses.iter().map(|p| IrPulse(*p as u128)).collect(),
+            )),
+        }
+    }
+}
+
+/// How pressing a button changes what we believe the device's state to be
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonAction {
+    /// No tracked state, such as a channel or input button
+    #[default]
+    Press,
+    PowerToggle,
+    PowerOn,
+    PowerOff,
+    BrightnessUp,
+    BrightnessDown,
+}
+
+fn default_repeat() -> usize {
+    1
+}
+
+fn default_gap() -> u64 {
+    DEFAULT_REPEAT_GAP
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Button {
+    pub code: RemoteCode,
+    #[serde(default)]
+    pub action: ButtonAction,
+    /// Number of times the frame is sent per press
+    #[serde(default = "default_repeat")]
+    pub repeat: usize,
+    #[serde(default = "default_gap")]
+    pub gap: u64,
+}
+
+impl Button {
+    pub fn framing(&self) -> IrFraming {
+        IrFraming::repeated(self.repeat, self.gap as u128)
+    }
+}
+
+/// Device state as far as we know it, since button remotes never report back
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct RemoteState {
+    pub powered: Option<bool>,
+    pub brightness: Option<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// Number of brightness steps between off and full, if the device dims in steps
+    #[serde(default)]
+    pub brightness_steps: Option<u8>,
+    pub buttons: BTreeMap<String, Button>,
+}
@@ -4,7 +4,10 @@ use thiserror::Error;
 
 use crate::ir::format::Aeha;
 use crate::ir::sanyo::types::{sanyo_sequence, SanyoTemperatureCode, SanyoTrigger};
-use crate::ir::types::{ACMode, IrEncodeError, IrFormat, IrSequence, IrStatus, IrTarget};
+use crate::ir::types::{
+    ACMode, IrEncodeError, IrFormat, IrSequence, IrStatus, IrTarget, IrTargetFormat,
+    TemperatureRange,
+};
 use std::cmp::Ordering;
 
 #[derive(Error, Clone, Debug)]
@@ -27,7 +30,7 @@ impl Sanyo {
         &self,
         trigger: SanyoTrigger,
     ) -> Result<IrSequence, <Sanyo as IrTarget>::Error> {
-        Ok(<Self as IrTarget>::Format::encode(sanyo_sequence(
+        Ok(<Self as IrTargetFormat>::Format::encode(sanyo_sequence(
             self.mode.clone(),
             self.temp.clone(),
             trigger,
@@ -35,12 +38,19 @@ impl Sanyo {
     }
 }
 
-impl IrTarget for Sanyo {
+impl IrTargetFormat for Sanyo {
     type Format = Aeha;
+}
+
+impl IrTarget for Sanyo {
     type Error = SanyoError;
     type Temperature = SanyoTemperatureCode;
     const SEQ_LENGTH: usize = 136;
 
+    fn temperature_range(&self) -> TemperatureRange {
+        SanyoTemperatureCode::RANGE
+    }
+
     fn power_off(&mut self) -> Result<IrSequence, Self::Error> {
         self.powered = false;
         self.as_ir_sequence(SanyoTrigger::Off)
@@ -5,7 +5,9 @@ use strum_macros::EnumIter;
 use thiserror::Error;
 use tokio::sync::OnceCell;
 
-use crate::ir::types::{ACMode, IrPulse, IrPulseBytes, TemperatureCode};
+use crate::ir::types::{
+    ACMode, IrPulse, IrPulseBytes, Temperature, TemperatureCode, TemperatureRange,
+};
 use core::convert;
 use std::str::FromStr;
 
@@ -29,6 +31,8 @@ pub enum SanyoTemperatureCode {
 }
 
 impl SanyoTemperatureCode {
+    pub const RANGE: TemperatureRange = TemperatureRange::celsius(16, 30, 10);
+
     pub fn ind(&self) -> u8 {
         u8::from(self) - 16
     }
@@ -72,12 +76,23 @@ impl TryFrom<u32> for SanyoTemperatureCode {
     }
 }
 
+impl TryFrom<Temperature> for SanyoTemperatureCode {
+    type Error = InvalidSanyoTemperatureCode;
+
+    fn try_from(value: Temperature) -> Result<Self, Self::Error> {
+        Self::RANGE
+            .normalize(value)
+            .ok_or(InvalidSanyoTemperatureCode)
+            .map(|temp| SanyoTemperatureCode::try_from(temp.whole_degrees() as u32))
+            .and_then(convert::identity)
+    }
+}
+
 impl FromStr for SanyoTemperatureCode {
     type Err = InvalidSanyoTemperatureCode;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.to_lowercase()
-            .parse::<u32>()
+        s.parse::<Temperature>()
             .map_err(|_| InvalidSanyoTemperatureCode)
             .map(SanyoTemperatureCode::try_from)
             .and_then(convert::identity)
@@ -112,6 +127,12 @@ impl From<SanyoTemperatureCode> for u32 {
     }
 }
 
+impl From<SanyoTemperatureCode> for Temperature {
+    fn from(code: SanyoTemperatureCode) -> Self {
+        Temperature::celsius(u8::from(&code) as i32)
+    }
+}
+
 impl SanyoTemperatureCode {
     pub const fn down(&self) -> Option<SanyoTemperatureCode> {
         use SanyoTemperatureCode::*;
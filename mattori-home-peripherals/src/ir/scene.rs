@@ -0,0 +1,131 @@
+pub mod types;
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::ir::output::IrOut;
+use crate::ir::remote::Remotes;
+use crate::ir::scene::types::{Scene, SceneStep};
+use crate::ir::types::{IrTarget, Temperature};
+use crate::persist::{self, PersistError};
+
+/// Space left between frames of different steps, so receivers see them as separate presses
+pub const STEP_GAP: Duration = Duration::from_millis(300);
+
+#[derive(Error, Clone, Debug)]
+pub enum SceneError {
+    #[error("Unknown scene {0}")]
+    UnknownScene(String),
+    #[error("Step {0} of scene failed: {1}")]
+    Step(usize, String),
+    #[error("Could not persist scenes")]
+    Persist(#[from] PersistError),
+}
+
+impl Scene {
+    /// Sends each step in order, only holding the locks while a step is being sent
+    pub async fn run<T: 'static + IrTarget + Debug>(
+        &self,
+        ir_out: &Mutex<IrOut<T>>,
+        remotes: &Mutex<Remotes>,
+    ) -> Result<(), SceneError>
+    where
+        <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+    {
+        for (i, step) in self.steps.iter().enumerate() {
+            let step_error = |e: &dyn Display| SceneError::Step(i, e.to_string());
+            let idle_at = Instant::from_std(ir_out.lock().await.idle_at());
+            if let SceneStep::Delay(millis) = step {
+                sleep_until(idle_at + Duration::from_millis(*millis)).await;
+                continue;
+            }
+            sleep_until(idle_at + STEP_GAP).await;
+            let mut ir_out = ir_out.lock().await;
+            trace!("running scene step {}: {}", i, step);
+            match step {
+                SceneStep::Ac(change) => {
+                    let status = change.apply(ir_out.status()).map_err(|e| step_error(&e))?;
+                    ir_out.send_status(status)
+                }
+                SceneStep::Button { remote, button } => {
+                    let mut remotes = remotes.lock().await;
+                    let remote = remotes.get_mut(remote).map_err(|e| step_error(&e))?;
+                    ir_out.send_remote(remote, button)
+                }
+                SceneStep::Code(code) => {
+                    ir_out.send(code.to_sequence().map_err(|e| step_error(&e))?)
+                }
+                SceneStep::Delay(_) => unreachable!(),
+            }
+            .map_err(|e| step_error(&e))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ScenesFile {
+    #[serde(default)]
+    scenes: BTreeMap<String, Scene>,
+}
+
+/// Named scenes, saved back to their file whenever they change
+#[derive(Debug)]
+pub struct Scenes {
+    path: PathBuf,
+    scenes: BTreeMap<String, Scene>,
+}
+
+impl Scenes {
+    /// Reads scenes from a toml file, treating a missing file as having none saved
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scenes, SceneError> {
+        let file: ScenesFile = persist::load(&path)?;
+        Ok(Scenes {
+            path: path.as_ref().to_path_buf(),
+            scenes: file.scenes,
+        })
+    }
+
+    fn save(&self) -> Result<(), SceneError> {
+        Ok(persist::save(
+            &self.path,
+            &ScenesFile {
+                scenes: self.scenes.clone(),
+            },
+        )?)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Scene, SceneError> {
+        self.scenes
+            .get(name)
+            .ok_or_else(|| SceneError::UnknownScene(name.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Scene)> + '_ {
+        self.scenes
+            .iter()
+            .map(|(name, scene)| (name.as_str(), scene))
+    }
+
+    /// Adds or replaces a scene
+    pub fn insert<S: Into<String>>(&mut self, name: S, scene: Scene) -> Result<(), SceneError> {
+        self.scenes.insert(name.into(), scene);
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Scene, SceneError> {
+        let scene = self
+            .scenes
+            .remove(name)
+            .ok_or_else(|| SceneError::UnknownScene(name.to_string()))?;
+        self.save()?;
+        Ok(scene)
+    }
+}
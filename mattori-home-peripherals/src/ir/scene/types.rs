@@ -0,0 +1,173 @@
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::ir::remote::types::RemoteCode;
+use crate::ir::types::{ACMode, IrStatus, IrTarget, Temperature};
+
+/// Change to the AC's status, where unset fields keep whatever the target currently has
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AcChange {
+    pub powered: bool,
+    pub mode: Option<ACMode>,
+    pub temperature: Option<Temperature>,
+}
+
+impl AcChange {
+    pub fn apply<T: IrTarget>(&self, current: IrStatus<T>) -> Result<IrStatus<T>, String>
+    where
+        <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+    {
+        Ok(IrStatus {
+            powered: self.powered,
+            mode: self.mode.clone().unwrap_or(current.mode),
+            temperature: match self.temperature {
+                Some(temperature) => {
+                    T::Temperature::try_from(temperature).map_err(|e| e.to_string())?
+                }
+                None => current.temperature,
+            },
+        })
+    }
+}
+
+/// Written in files in the same compact form used by the CLI and gRPC, such as `ac:cool:26`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SceneStep {
+    Ac(AcChange),
+    Button {
+        remote: String,
+        button: String,
+    },
+    Code(RemoteCode),
+    /// Wait in millis after the previous frame has finished sending
+    Delay(u64),
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid scene step {0}, expected ac:<on|off|mode|temperature>..., button:<remote>:<button>, aeha:<hex>, kaseikyo:<hex>, raw:<micros>,... or delay:<millis>")]
+pub struct InvalidSceneStep(String);
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+impl FromStr for SceneStep {
+    type Err = InvalidSceneStep;
+
+    /// Parses the compact form used by the CLI and gRPC, such as `ac:cool:26`, `ac:off`,
+    /// `button:light:off` or `delay:500`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidSceneStep(s.to_string());
+        let (kind, args) = s.split_once(':').ok_or_else(invalid)?;
+        match kind.to_lowercase().as_str() {
+            "ac" => {
+                let mut change = AcChange {
+                    powered: true,
+                    mode: None,
+                    temperature: None,
+                };
+                for arg in args.split(':') {
+                    match arg.to_lowercase().as_str() {
+                        "on" => change.powered = true,
+                        "off" => change.powered = false,
+                        arg => match arg.parse::<ACMode>() {
+                            Ok(mode) => change.mode = Some(mode),
+                            Err(_) => {
+                                change.temperature = Some(arg.parse().map_err(|_| invalid())?)
+                            }
+                        },
+                    }
+                }
+                Ok(SceneStep::Ac(change))
+            }
+            "button" => args
+                .split_once(':')
+                .map(|(remote, button)| SceneStep::Button {
+                    remote: remote.to_string(),
+                    button: button.to_string(),
+                })
+                .ok_or_else(invalid),
+            "aeha" => parse_hex(args)
+                .map(|bytes| SceneStep::Code(RemoteCode::Aeha(bytes)))
+                .ok_or_else(invalid),
+            "kaseikyo" => parse_hex(args)
+                .map(|bytes| SceneStep::Code(RemoteCode::Kaseikyo(bytes)))
+                .ok_or_else(invalid),
+            "raw" => args
+                .split(',')
+                .map(|pulse| pulse.trim().parse().ok())
+                .collect::<Option<Vec<_>>>()
+                .map(|pulses| SceneStep::Code(RemoteCode::Raw(pulses)))
+                .ok_or_else(invalid),
+            "delay" => args.parse().map(SceneStep::Delay).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for SceneStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneStep::Ac(change) => {
+                write!(f, "ac:{}", if change.powered { "on" } else { "off" })?;
+                if let Some(mode) = &change.mode {
+                    write!(f, ":{}", mode)?;
+                }
+                if let Some(temperature) = change.temperature {
+                    write!(f, ":{}", temperature)?;
+                }
+                Ok(())
+            }
+            SceneStep::Button { remote, button } => write!(f, "button:{}:{}", remote, button),
+            SceneStep::Code(RemoteCode::Aeha(bytes)) => {
+                write!(f, "aeha:{:02x}", bytes.iter().format(""))
+            }
+            SceneStep::Code(RemoteCode::Kaseikyo(bytes)) => {
+                write!(f, "kaseikyo:{:02x}", bytes.iter().format(""))
+            }
+            SceneStep::Code(RemoteCode::Raw(pulses)) => {
+                write!(f, "raw:{}", pulses.iter().format(","))
+            }
+            SceneStep::Delay(millis) => write!(f, "delay:{}", millis),
+        }
+    }
+}
+
+impl Serialize for SceneStep {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SceneStep {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Steps sent one after the other, such as turning off the lights and TV at night
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub steps: Vec<SceneStep>,
+}
+
+impl Display for Scene {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.steps.iter().format(" "))
+    }
+}
@@ -44,6 +44,29 @@ impl IrSequence {
     pub fn as_hex<T: IrFormat>(&self) -> Result<String, IrDecodeError> {
         T::decode(self).map(|bytes| bytes.to_string())
     }
+
+    /// Joins frames into one sequence, separating them with a space of `gap` length
+    pub fn concat<I: IntoIterator<Item = IrSequence>>(frames: I, gap: IrPulse) -> IrSequence {
+        IrSequence(frames.into_iter().map(IrSequence::into_inner).fold(
+            Vec::new(),
+            |mut acc, frame| {
+                if !acc.is_empty() {
+                    acc.push(gap);
+                }
+                acc.extend(frame);
+                acc
+            },
+        ))
+    }
+
+    pub fn framed(self, framing: &IrFraming) -> IrSequence {
+        IrSequence::concat(vec![self; framing.repeat.max(1)], framing.gap)
+    }
+
+    /// Time taken to transmit the whole sequence
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_micros(self.0.iter().map(|p| p.0 as u64).sum())
+    }
 }
 
 impl AsRef<[IrPulse]> for IrSequence {
@@ -66,9 +89,200 @@ pub trait IrFormat {
 
 // target
 
-pub trait TemperatureCode: TryFrom<u32> + Into<u32>
+/// How a target's frames are put on the wire
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IrFraming {
+    /// Number of times each frame is sent
+    pub repeat: usize,
+    /// Space between repeated frames
+    pub gap: IrPulse,
+}
+
+impl IrFraming {
+    pub const SINGLE: IrFraming = IrFraming::repeated(1, 0);
+
+    pub const fn repeated(repeat: usize, gap: u128) -> IrFraming {
+        IrFraming {
+            repeat,
+            gap: IrPulse(gap),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+}
+
+/// A set temperature, kept in tenths of a degree so that half degree steps stay exact
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Temperature {
+    tenths: i32,
+    unit: TemperatureUnit,
+}
+
+impl Temperature {
+    pub const fn from_tenths(tenths: i32, unit: TemperatureUnit) -> Temperature {
+        Temperature { tenths, unit }
+    }
+
+    pub const fn celsius(degrees: i32) -> Temperature {
+        Temperature::from_tenths(degrees * 10, TemperatureUnit::Celsius)
+    }
+
+    pub const fn fahrenheit(degrees: i32) -> Temperature {
+        Temperature::from_tenths(degrees * 10, TemperatureUnit::Fahrenheit)
+    }
+
+    /// Rounds to the nearest tenth of a degree
+    pub fn from_degrees(degrees: f32, unit: TemperatureUnit) -> Temperature {
+        Temperature::from_tenths((degrees * 10.0).round() as i32, unit)
+    }
+
+    pub fn tenths(&self) -> i32 {
+        self.tenths
+    }
+
+    pub fn unit(&self) -> TemperatureUnit {
+        self.unit
+    }
+
+    pub fn degrees(&self) -> f32 {
+        self.tenths as f32 / 10.0
+    }
+
+    /// Whole degrees, with any fraction dropped
+    pub fn whole_degrees(&self) -> i32 {
+        self.tenths.div_euclid(10)
+    }
+
+    pub fn is_whole(&self) -> bool {
+        self.tenths % 10 == 0
+    }
+
+    /// Converts to another unit, rounding to the nearest tenth of a degree
+    pub fn to_unit(&self, unit: TemperatureUnit) -> Temperature {
+        let tenths = match (self.unit, unit) {
+            (TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit) => {
+                (self.tenths as f64 * 9.0 / 5.0).round() as i32 + 320
+            }
+            (TemperatureUnit::Fahrenheit, TemperatureUnit::Celsius) => {
+                ((self.tenths - 320) as f64 * 5.0 / 9.0).round() as i32
+            }
+            _ => self.tenths,
+        };
+        Temperature::from_tenths(tenths, unit)
+    }
+}
+
+impl Display for Temperature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_whole() {
+            write!(f, "{}{}", self.whole_degrees(), self.unit.symbol())
+        } else {
+            write!(f, "{:.1}{}", self.degrees(), self.unit.symbol())
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid temperature")]
+pub struct InvalidTemperature;
+
+impl FromStr for Temperature {
+    type Err = InvalidTemperature;
+
+    /// Parses degrees with an optional unit suffix, such as `25`, `18.5c` or `77°F`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (degrees, unit) = match s.strip_suffix('f') {
+            Some(degrees) => (degrees, TemperatureUnit::Fahrenheit),
+            None => (s.strip_suffix('c').unwrap_or(&s), TemperatureUnit::Celsius),
+        };
+        degrees
+            .trim_end_matches('°')
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| InvalidTemperature)
+            .map(|degrees| Temperature::from_degrees(degrees, unit))
+    }
+}
+
+/// Temperatures a target accepts, in the unit it uses on the wire
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TemperatureRange {
+    pub min: Temperature,
+    pub max: Temperature,
+    /// Smallest change the target can make, in tenths of a degree
+    pub step: i32,
+}
+
+impl TemperatureRange {
+    pub const fn celsius(min: i32, max: i32, step: i32) -> TemperatureRange {
+        TemperatureRange {
+            min: Temperature::celsius(min),
+            max: Temperature::celsius(max),
+            step,
+        }
+    }
+
+    pub fn unit(&self) -> TemperatureUnit {
+        self.min.unit
+    }
+
+    /// Converts into the range's unit and snaps onto its nearest step, or `None` if that falls
+    /// outside of the range
+    pub fn normalize(&self, temp: Temperature) -> Option<Temperature> {
+        let offset = temp.to_unit(self.unit()).tenths - self.min.tenths;
+        let offset = (offset + self.step / 2).div_euclid(self.step) * self.step;
+        (0..=self.max.tenths - self.min.tenths)
+            .contains(&offset)
+            .then(|| Temperature::from_tenths(self.min.tenths + offset, self.unit()))
+    }
+
+    pub fn contains(&self, temp: Temperature) -> bool {
+        self.normalize(temp).is_some()
+    }
+
+    pub fn up(&self, temp: Temperature) -> Option<Temperature> {
+        self.normalize(temp).and_then(|temp| {
+            self.normalize(Temperature::from_tenths(temp.tenths + self.step, temp.unit))
+        })
+    }
+
+    pub fn down(&self, temp: Temperature) -> Option<Temperature> {
+        self.normalize(temp).and_then(|temp| {
+            self.normalize(Temperature::from_tenths(temp.tenths - self.step, temp.unit))
+        })
+    }
+}
+
+impl Display for TemperatureRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}..={} in steps of {}",
+            self.min,
+            self.max,
+            Temperature::from_tenths(self.step, self.unit())
+        )
+    }
+}
+
+pub trait TemperatureCode: TryFrom<Temperature> + Into<Temperature>
 where
-    <Self as TryFrom<u32>>::Error: Display,
+    <Self as TryFrom<Temperature>>::Error: Display,
 {
 }
 
@@ -106,23 +320,22 @@ impl FromStr for ACMode {
     }
 }
 
-impl ToString for ACMode {
-    fn to_string(&self) -> String {
+impl Display for ACMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ACMode::Auto => "auto",
-            ACMode::Warm => "warm",
-            ACMode::Dry => "dry",
-            ACMode::Cool => "cool",
-            ACMode::Fan => "fan",
+            ACMode::Auto => write!(f, "auto"),
+            ACMode::Warm => write!(f, "warm"),
+            ACMode::Dry => write!(f, "dry"),
+            ACMode::Cool => write!(f, "cool"),
+            ACMode::Fan => write!(f, "fan"),
         }
-        .to_string()
     }
 }
 
 #[derive(Debug)]
 pub struct IrStatus<T: IrTarget>
 where
-    <<T as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<T as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
     pub powered: bool,
     pub mode: ACMode,
@@ -131,12 +344,17 @@ where
 
 pub trait IrTarget
 where
-    <<Self as IrTarget>::Temperature as TryFrom<u32>>::Error: Display,
+    <<Self as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
 {
-    type Format: IrFormat;
     type Error: std::error::Error + Send + Sync + Clone;
     type Temperature: TemperatureCode + Send + Sync;
     const SEQ_LENGTH: usize;
+    const FRAMING: IrFraming = IrFraming::SINGLE;
+    fn framing(&self) -> IrFraming {
+        Self::FRAMING
+    }
+    /// Temperatures the target accepts, and how finely it can set them
+    fn temperature_range(&self) -> TemperatureRange;
     fn power_off(&mut self) -> Result<IrSequence, Self::Error>;
     fn power_on(&mut self) -> Result<IrSequence, Self::Error>;
     fn is_powered(&self) -> bool;
@@ -151,6 +369,14 @@ where
         Self: Sized;
 }
 
+/// Targets that always use the same wire format
+pub trait IrTargetFormat: IrTarget
+where
+    <<Self as IrTarget>::Temperature as TryFrom<Temperature>>::Error: Display,
+{
+    type Format: IrFormat;
+}
+
 // source
 
 fn in_bounds<L: AsPrimitive<f64>, T: AsPrimitive<f64>>(length: L, target: T) -> bool {
@@ -204,6 +204,13 @@ impl Lcd {
         s.bytes().try_for_each(|c| self.push_char(c))
     }
 
+    /// Replaces whatever is displayed with `s`, wrapping onto the second line
+    pub fn show(&mut self, s: &str) -> Result<()> {
+        self.clear()?;
+        self.first_line_head()?;
+        self.push_str(s)
+    }
+
     pub async fn shutdown(&mut self) -> Result<()> {
         trace!("shutting down lcd");
         self.clear()?;
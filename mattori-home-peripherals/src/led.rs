@@ -1,10 +1,11 @@
 use rppal::gpio::{Gpio, OutputPin};
 
 use crate::I2cError;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use thiserror::Error;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Leds {
     Green,
     Yellow,
@@ -26,6 +27,15 @@ impl FromStr for Leds {
     }
 }
 
+impl Display for Leds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Leds::Green => write!(f, "green"),
+            Leds::Yellow => write!(f, "yellow"),
+        }
+    }
+}
+
 impl From<Leds> for u8 {
     fn from(l: Leds) -> Self {
         match l {
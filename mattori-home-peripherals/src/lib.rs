@@ -7,9 +7,11 @@ use rppal::gpio::Error;
 use thiserror::Error;
 
 pub mod atmosphere;
+pub mod co2;
 pub mod ir;
 pub mod lcd;
 pub mod led;
+pub mod persist;
 
 #[derive(Error, Clone, Debug)]
 pub enum I2cError {
@@ -0,0 +1,47 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Clone, Debug)]
+pub enum PersistError {
+    #[error("Could not access {0}: {1}")]
+    Io(String, String),
+    #[error("Invalid contents in {0}: {1}")]
+    Parse(String, String),
+    #[error("Could not serialize: {0}")]
+    Serialize(String),
+}
+
+pub type Result<T> = std::result::Result<T, PersistError>;
+
+fn io_error<P: AsRef<Path>>(path: P) -> impl FnOnce(io::Error) -> PersistError {
+    move |e| PersistError::Io(path.as_ref().display().to_string(), e.to_string())
+}
+
+/// Reads a toml file, treating a missing file as the default value
+pub fn load<T: DeserializeOwned + Default, P: AsRef<Path>>(path: P) -> Result<T> {
+    match fs::read_to_string(&path) {
+        Ok(contents) => from_str(&path, &contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(io_error(path)(e)),
+    }
+}
+
+/// Parses toml contents, with `path` only used to describe errors
+pub fn from_str<T: DeserializeOwned, P: AsRef<Path>>(path: P, contents: &str) -> Result<T> {
+    toml::from_str(contents)
+        .map_err(|e| PersistError::Parse(path.as_ref().display().to_string(), e.to_string()))
+}
+
+/// Writes a toml file by replacing it, so an interrupted write never leaves a partial file
+pub fn save<T: Serialize, P: AsRef<Path>>(path: P, value: &T) -> Result<()> {
+    let contents =
+        toml::to_string_pretty(value).map_err(|e| PersistError::Serialize(e.to_string()))?;
+    let tmp = path.as_ref().with_extension("tmp");
+    fs::write(&tmp, contents).map_err(io_error(&tmp))?;
+    fs::rename(&tmp, &path).map_err(io_error(&path))
+}